@@ -0,0 +1,88 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+// Sends requests to a server and measures their latencies with the client library, without the
+// client binary or client.toml; e.g. to drive an experiment from another program.
+//
+//     cargo run --example simple_client -- <server-ip> [<client-ip>]
+
+extern crate client;
+
+use client::packet::PROTO_VERSION;
+use client::{ClientConfig, Receiver, Sender};
+
+use std::env;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Return the configuration for a short run at a low rate; the other settings keep their
+/// defaults. A receiver isn't `Send`, so each thread builds its own copy.
+fn config(server_ip: &str) -> ClientConfig {
+    let mut config = ClientConfig::default();
+    config.server_ip = server_ip.to_string();
+    config.num_tenants = 8;
+    config.num_reqs = 10000;
+    config.num_resps = 10000;
+    config.req_rate = 10000;
+    config.recv_timeout_ms = 1000;
+    config.proto_version = PROTO_VERSION;
+    config
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let server_ip = args
+        .get(1)
+        .cloned()
+        .unwrap_or_else(|| "127.0.0.1".to_string());
+    let client_ip: IpAddr = args
+        .get(2)
+        .map_or("0.0.0.0", |ip| ip.as_str())
+        .parse()
+        .unwrap();
+
+    // The sender and the receiver share the socket; the responses come back to its port.
+    let socket = UdpSocket::bind(SocketAddr::new(client_ip, 0)).expect("couldn't bind to address");
+    let socket = Arc::new(socket);
+
+    // The receiver prints the throughput and the latencies when it is dropped.
+    let recv_socket = Arc::clone(&socket);
+    let recv_ip = server_ip.clone();
+    let receiver = thread::spawn(move || {
+        let mut receiver = Receiver::new(recv_socket, &config(&recv_ip), true, None);
+        receiver.recv();
+        let p999 = receiver.percentile(0.999);
+        (receiver.recvd(), p999)
+    });
+
+    let sent = Arc::new(AtomicU64::new(0));
+    let mut sender = Sender::new(socket, &config(&server_ip), None, Arc::clone(&sent));
+    sender.send();
+    let stats = sender.stats();
+    println!(
+        "Sent {} requests at {:.2} requests/sec",
+        stats.sent, stats.achieved_rate_rps
+    );
+
+    let (recvd, p999) = receiver.join().unwrap();
+    println!(
+        "Received {} of {} responses; p99.9(ns) {:?}",
+        recvd,
+        sent.load(Ordering::Relaxed),
+        p999
+    );
+}
@@ -0,0 +1,44 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! A minimal binary without std, which only links the cycles module of the client. It uses the C
+//! runtime for the entry point, and exits with a non-zero status if a cycles call misbehaves.
+
+#![no_std]
+#![no_main]
+
+extern crate client;
+
+use client::cycles;
+use core::panic::PanicInfo;
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
+
+#[no_mangle]
+pub extern "C" fn main(_argc: isize, _argv: *const *const u8) -> isize {
+    let start = cycles::rdtsc();
+    cycles::sleep_for(cycles::cycles_per_second() / 1000);
+    let stop = cycles::rdtsc();
+
+    // The 1ms wait must take at least 1ms, and well under a second.
+    let seconds = cycles::to_seconds(stop - start);
+    if stop <= start || seconds < 0.001 || seconds > 1.0 {
+        return 1;
+    }
+    0
+}
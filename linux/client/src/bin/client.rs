@@ -13,207 +13,177 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+#[macro_use]
 extern crate client;
 extern crate core_affinity;
 
-use client::config::ClientConfig;
+use client::calibrate;
+use client::config::{ClientConfig, LatencyUnit};
 use client::cycles;
+use client::receiver::{delivery_warning, run_interleaved, Receiver};
+use client::report::{self, OutputFormat};
+use client::samples;
+use client::sender::Sender;
+use client::socket;
 
-use rand::distributions::{Distribution, Uniform};
-use rand::prelude::*;
-use rand::rngs::ThreadRng;
-
+use std::env;
 use std::fs;
-use std::mem::transmute;
 use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 
-struct Sender {
-    // Socket to send the packets.
+fn setup_send(
     socket: Arc<UdpSocket>,
-
-    // The server ip-address.
-    server_ip: String,
-
-    // Total number of requests to be sent out.
-    requests: u64,
-
-    // Number of requests that have been sent out so far.
-    sent: u64,
-
-    // The inverse of the rate at which requests are to be generated. Basically, the time interval
-    // between two request generations in cycles.
-    rate_inv: u64,
-
-    // The time stamp at which the workload started generating requests in cycles.
-    start: u64,
-
-    // The time stamp at which the next request must be issued in cycles.
-    next: u64,
-
-    // The tenant random number generator.
-    tenant_rng: Box<Uniform<u16>>,
-
-    // Random number generator.
-    rng: Box<ThreadRng>,
+    config: &ClientConfig,
+    control: Option<UdpSocket>,
+    shared_sent: Arc<AtomicU64>,
+) {
+    let mut sender = Sender::new(socket, config, control, shared_sent);
+    sender.send();
+    let stats = sender.stats();
+    diag!(
+        "Sent {} Dropped {} Rate {:.2} Time(sec) {:.2}",
+        stats.sent,
+        stats.dropped_sends,
+        stats.achieved_rate_rps,
+        cycles::to_seconds(stats.elapsed_cycles)
+    );
+    sender.print_summary();
 }
 
-impl Sender {
-    fn new(socket: Arc<UdpSocket>, config: &ClientConfig) -> Sender {
-        Sender {
-            socket: socket,
-            server_ip: config.server_ip.clone(),
-            requests: config.num_reqs,
-            sent: 0,
-            rate_inv: cycles::cycles_per_second() / config.req_rate as u64,
-            start: cycles::rdtsc(),
-            next: 0,
-            tenant_rng: Box::new(Uniform::from(1024..(1024 + config.num_tenants as u16))),
-            rng: Box::new(thread_rng()),
-        }
+/// Run the client on the calling thread, with a single socket; for the machines with too few
+/// cores for a sender and a receiver thread per core pair. The cores aren't pinned, so the
+/// latencies are noisier than with the threads.
+fn run_single_thread(config: &ClientConfig, baseline: u64) {
+    let ipaddr: IpAddr = config.client_ip.parse().unwrap();
+    let socket = UdpSocket::bind(SocketAddr::new(ipaddr, 49000)).expect("couldn't bind to address");
+    socket::set_buffer_sizes(&socket, config.sndbuf, config.rcvbuf)
+        .expect("couldn't set the socket buffer sizes");
+    let socket = Arc::new(socket);
+
+    let mut control = None;
+    let mut ack_addr = None;
+    if config.max_in_flight != 0 || config.per_tenant_inflight != 0 {
+        let control_socket =
+            UdpSocket::bind(SocketAddr::new(ipaddr, 0)).expect("couldn't bind the control socket");
+        ack_addr = Some(control_socket.local_addr().unwrap());
+        control = Some(control_socket);
     }
 
-    fn send(&mut self) {
-        let mut buf = [0; 8];
-        loop {
-            if self.requests <= self.sent {
-                return;
-            }
-
-            let curr: u64 = cycles::rdtsc();
-            if curr >= self.next || self.next == 0 {
-                unsafe {
-                    buf[0..8].copy_from_slice(&{ transmute::<u64, [u8; 8]>(curr.to_le()) });
-                }
-
-                // Pick a random port to send the request to a random tenant.
-                let ip_address = self.server_ip.parse().unwrap();
-                let addr = SocketAddr::new(ip_address, self.tenant_rng.sample(&mut *self.rng));
-                self.socket.send_to(&buf, addr).expect("couldn't send data");
-
-                // Update the time stamp at which the next request should be generated, assuming that
-                // the first request was sent out at self.start.
-                self.sent += 1;
-                self.next = self.start + self.sent * self.rate_inv;
-            }
-        }
+    let sent = Arc::new(AtomicU64::new(0));
+    let mut sender = Sender::new(Arc::clone(&socket), config, control, sent);
+    let mut receiver = Receiver::new(socket, config, true, ack_addr);
+    receiver.set_baseline(baseline);
+    run_interleaved(&mut sender, &mut receiver);
+    let stats = sender.stats();
+    diag!(
+        "Sent {} Dropped {} Rate {:.2} Time(sec) {:.2}",
+        stats.sent,
+        stats.dropped_sends,
+        stats.achieved_rate_rps,
+        cycles::to_seconds(stats.elapsed_cycles)
+    );
+    if let Some(warning) =
+        delivery_warning(stats.sent, receiver.recvd(), config.responses_per_send())
+    {
+        diag!("{}", warning);
     }
 }
 
-struct Receiver {
-    // The network socket required to receives response packets from the network.
+fn setup_recv(
     socket: Arc<UdpSocket>,
-
-    // The number of response packets to wait for before printing out statistics.
-    responses: u64,
-
-    // Time stamp in cycles at which measurement started. Required to calculate observed
-    // throughput of the Sandstorm server.
-    start: u64,
-
-    // The total number of responses received so far.
-    recvd: u64,
-
-    // Vector of sampled request latencies. Required to calculate distributions once all responses
-    // have been received.
-    latencies: Vec<u64>,
-
-    // If true, this receiver will make latency measurements.
+    config: &ClientConfig,
     master: bool,
-
-    // Time stamp in cycles at which measurement stopped.
-    stop: u64,
+    ack_addr: Option<SocketAddr>,
+    baseline: u64,
+) -> u64 {
+    let mut receiver = Receiver::new(socket, config, master, ack_addr);
+    receiver.set_baseline(baseline);
+    receiver.recv();
+    receiver.recvd()
 }
 
-impl Receiver {
-    fn new(socket: Arc<UdpSocket>, config: &ClientConfig, master: bool) -> Receiver {
-        Receiver {
-            socket: socket,
-            responses: config.num_resps,
-            start: cycles::rdtsc(),
-            recvd: 0,
-            latencies: Vec::with_capacity(config.num_resps as usize),
-            master: master,
-            stop: 0,
-        }
+/// Merge the raw latency files written by `raw_latency_output` on several client machines, and
+/// print the percentiles over all of their samples.
+///
+/// # Arguments
+/// *`paths`: The raw latency files.
+/// *`unit`: The unit to print the latencies in.
+fn merge_latency_files(paths: &[String], unit: LatencyUnit) {
+    if paths.is_empty() {
+        eprintln!("--merge expects one or more raw latency files");
+        process::exit(1);
     }
-
-    fn recv(&mut self) {
-        let mut buf = [0; 8];
-        loop {
-            // Receieved maximum number of packets, exit now.
-            if self.responses <= self.recvd {
-                return;
-            }
-
-            // Check the responses; add latency to the vector.
-            match self.socket.recv(&mut buf) {
-                Ok(_received) => {
-                    self.recvd += 1;
-                    let timestamp = u64::from_le_bytes(buf);
-
-                    // Take latency measurement after warmup; say after 2M responses.
-                    if self.recvd > 2 * 1000 * 1000 && self.master {
-                        self.latencies.push(cycles::rdtsc() - timestamp);
-                        if self.recvd % 1000000 == 0 {
-                            println!("Recvd {} responses", self.recvd);
-                        }
-                    }
-                }
-                Err(e) => println!("recv function failed: {:?}", e),
-            }
-
-            // Update the stop timestamp, if received the required number of responses.
-            if self.responses <= self.recvd {
-                self.stop = cycles::rdtsc();
-            }
-        }
+    let latencies = samples::merge_latencies(paths).unwrap_or_else(|e| {
+        eprintln!("Couldn't read the latency files: {}", e);
+        process::exit(1);
+    });
+    match samples::MergedPercentiles::from_sorted(&latencies) {
+        Some(p) => println!(
+            "Merged {} samples from {} files: p50({}) {:.2} p99({}) {:.2} p99.9({}) {:.2}",
+            p.count,
+            paths.len(),
+            unit.name(),
+            unit.convert(p.p50),
+            unit.name(),
+            unit.convert(p.p99),
+            unit.name(),
+            unit.convert(p.p999)
+        ),
+        None => println!("No latency samples in {} files", paths.len()),
     }
 }
 
-impl Drop for Receiver {
-    fn drop(&mut self) {
-        // Calculate & print the throughput for all client threads.
-        println!(
-            "Throughput {}",
-            self.recvd as f64 / cycles::to_seconds(self.stop - self.start)
-        );
-
-        // Calculate & print median & tail latency only on the master thread.
-        if self.master {
-            self.latencies.sort();
-
-            let m;
-            let t = self.latencies[(self.latencies.len() * 99) / 100];
-            match self.latencies.len() % 2 {
-                0 => {
-                    let n = self.latencies.len();
-                    m = (self.latencies[n / 2] + self.latencies[(n / 2) + 1]) / 2;
-                }
+// This is the `main` thread
+fn main() {
+    // The --output-format flag overrides the output_format in client.toml.
+    let args: Vec<String> = env::args().collect();
+    let mut config = ClientConfig::load();
+    let mut output_format = config.output_format;
+    if let Some(format) = args
+        .iter()
+        .position(|arg| arg == "--output-format")
+        .and_then(|i| args.get(i + 1))
+    {
+        output_format = OutputFormat::parse(format).unwrap_or_else(|| {
+            eprintln!(
+                "Invalid --output-format {}; expected text, json or csv",
+                format
+            );
+            process::exit(1);
+        });
+    }
+    report::STATS_ONLY.store(output_format != OutputFormat::Text, Ordering::Relaxed);
 
-                _ => m = self.latencies[self.latencies.len() / 2],
-            }
+    // --merge <file>... merges the raw latency files of several client machines, and prints the
+    // global percentiles instead of running an experiment.
+    if let Some(i) = args.iter().position(|arg| arg == "--merge") {
+        merge_latency_files(&args[i + 1..], config.latency_unit);
+        return;
+    }
 
-            println!(
-                ">>> {} {}",
-                cycles::to_seconds(m) * 1e9,
-                cycles::to_seconds(t) * 1e9
-            );
-        }
+    // A wrong TSC frequency skews all the rates and latencies; print it to help debug that.
+    if config.verbose {
+        diag!("TSC(MHz) {}", cycles::frequency_mhz());
     }
-}
 
-fn setup_send(socket: Arc<UdpSocket>, config: &ClientConfig) {
-    Sender::new(socket, config).send();
-}
+    // Measure the client's own overhead before any request is sent.
+    let mut baseline = 0;
+    if config.subtract_loopback {
+        baseline = calibrate::loopback_rtt(&config.client_ip, 10000)
+            .expect("couldn't measure the loopback round-trip time");
+        diag!("Loopback RTT(ns) {:.2}", cycles::to_seconds(baseline) * 1e9);
+    }
 
-fn setup_recv(socket: Arc<UdpSocket>, config: &ClientConfig, master: bool) {
-    Receiver::new(socket, config, master).recv();
-}
+    // The single-threaded mode doesn't need the NUMA layout from sysfs.
+    if config.single_thread {
+        config.output_format = output_format;
+        run_single_thread(&config, baseline);
+        return;
+    }
 
-// This is the `main` thread
-fn main() {
     // Find all the core on numa node 0 and start the clients only on those cores.
     let core_ids = core_affinity::get_core_ids().unwrap();
     assert_eq!(core_ids.len() % 2, 0);
@@ -234,6 +204,9 @@ fn main() {
     // Make a vector to hold the children which are spawned.
     let mut children = vec![];
 
+    // The sender threads, along with the number of requests sent by each.
+    let mut senders = vec![];
+
     // The latencies are printed only for the master thread.
     let mut master = false;
 
@@ -246,14 +219,36 @@ fn main() {
             start_port += i as u16;
             let ipaddr: IpAddr = config.client_ip.parse().unwrap();
             let addr = SocketAddr::new(ipaddr, start_port);
-            let socket = Arc::new(UdpSocket::bind(addr).expect("couldn't bind to address"));
+            let socket = UdpSocket::bind(addr).expect("couldn't bind to address");
+            socket::set_buffer_sizes(&socket, config.sndbuf, config.rcvbuf)
+                .expect("couldn't set the socket buffer sizes");
+            let socket = Arc::new(socket);
             let socket_clone = Arc::clone(&socket);
 
+            // The receiver ACKs the responses on a separate control socket for back-pressure.
+            let mut control = None;
+            let mut ack_addr = None;
+            if config.max_in_flight != 0 || config.per_tenant_inflight != 0 {
+                let control_socket = UdpSocket::bind(SocketAddr::new(ipaddr, 0))
+                    .expect("couldn't bind the control socket");
+                ack_addr = Some(control_socket.local_addr().unwrap());
+                control = Some(control_socket);
+            }
+
             // Alternative sender and receivers.
-            thread::spawn(move || {
+            let sent = Arc::new(AtomicU64::new(0));
+            let sent_clone = Arc::clone(&sent);
+            let flow_control = control.is_some();
+            let sender = thread::spawn(move || {
                 core_affinity::set_for_current(id);
-                setup_send(Arc::clone(&socket), &ClientConfig::load());
+                setup_send(
+                    Arc::clone(&socket),
+                    &ClientConfig::load(),
+                    control,
+                    sent_clone,
+                );
             });
+            senders.push((sender, sent, flow_control));
             i += 1;
 
             let id = core_ids[i];
@@ -263,7 +258,15 @@ fn main() {
 
             children.push(thread::spawn(move || {
                 core_affinity::set_for_current(id);
-                setup_recv(Arc::clone(&socket_clone), &ClientConfig::load(), master);
+                let mut config = ClientConfig::load();
+                config.output_format = output_format;
+                setup_recv(
+                    Arc::clone(&socket_clone),
+                    &config,
+                    master,
+                    ack_addr,
+                    baseline,
+                )
             }));
             i += 1;
         } else {
@@ -271,8 +274,24 @@ fn main() {
         }
     }
 
+    let mut recvd = vec![];
     for child in children {
         // Wait for the thread to finish. Returns a result.
-        let _ = child.join();
+        recvd.push(child.join().unwrap_or(0));
+    }
+
+    // Check that each receiver got a response for each request sent on its socket.
+    for ((sender, sent, flow_control), recvd) in senders.into_iter().zip(recvd) {
+        // With flow control, the sender waits forever for the ACKs once its receiver stopped.
+        if !flow_control {
+            let _ = sender.join();
+        }
+        if let Some(warning) = delivery_warning(
+            sent.load(Ordering::Relaxed),
+            recvd,
+            config.responses_per_send(),
+        ) {
+            diag!("{}", warning);
+        }
     }
 }
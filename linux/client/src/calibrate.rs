@@ -0,0 +1,82 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+use super::packet::{ResponsePacket, PACKET_SIZE, PROTO_VERSION};
+
+use std::io;
+use std::net::UdpSocket;
+
+/// Measure the median round-trip time of a request over the loopback interface; the time the
+/// client itself spends in the syscalls and the network stack, without any server.
+///
+/// # Arguments
+/// *`ip`: The local IP address to bind the two sockets to, e.g. the client_ip in client.toml.
+/// *`pings`: The number of round-trips to measure.
+///
+/// # Return
+/// The median round-trip time in cycles.
+pub fn loopback_rtt(ip: &str, pings: usize) -> io::Result<u64> {
+    let client = UdpSocket::bind((ip, 0))?;
+    let echo = UdpSocket::bind((ip, 0))?;
+    let echo_addr = echo.local_addr()?;
+
+    let mut rtts = Vec::with_capacity(pings);
+    let mut buf = [0; PACKET_SIZE];
+    for i in 0..pings {
+        let start = cycles::rdtsc();
+        client.send_to(
+            &ResponsePacket::new(PROTO_VERSION, start, i as u64, 1).to_bytes(),
+            echo_addr,
+        )?;
+        let (amt, src) = echo.recv_from(&mut buf)?;
+        echo.send_to(&buf[..amt], src)?;
+        client.recv(&mut buf)?;
+        rtts.push(cycles::rdtsc() - start);
+    }
+    rtts.sort();
+    Ok(rtts.get(pings / 2).cloned().unwrap_or(0))
+}
+
+/// Subtract the client's own loopback round-trip time from each latency sample, so that the
+/// samples are closer to the time spent on the network and the server.
+///
+/// # Arguments
+/// *`latencies`: The latency samples in cycles.
+/// *`baseline`: The loopback round-trip time in cycles; samples below it become zero.
+pub fn subtract_baseline(latencies: &mut [u64], baseline: u64) {
+    for latency in latencies.iter_mut() {
+        *latency = latency.saturating_sub(baseline);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subtract_baseline() {
+        let mut latencies = vec![5000, 1200, 1000, 800, 0];
+        subtract_baseline(&mut latencies, 1000);
+        assert_eq!(latencies, vec![4000, 200, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_loopback_rtt() {
+        let rtt = loopback_rtt("127.0.0.1", 100).unwrap();
+        assert!(rtt > 0);
+        assert!(rtt < cycles::cycles_per_second());
+    }
+}
@@ -13,8 +13,56 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+use super::cycles;
+use super::report::OutputFormat;
+
 use std::fs::File;
 use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LatencyUnit {
+    #[serde(rename = "cycles")]
+    Cycles,
+
+    #[serde(rename = "ns")]
+    Nanoseconds,
+
+    #[serde(rename = "us")]
+    Microseconds,
+}
+
+impl Default for LatencyUnit {
+    fn default() -> LatencyUnit {
+        LatencyUnit::Nanoseconds
+    }
+}
+
+impl LatencyUnit {
+    /// Converts the number of CPU cycles to this unit.
+    ///
+    /// # Arguments
+    /// *`cycles`: Number of CPU cycles.
+    ///
+    /// # Return
+    /// The time corresponding to the given CPU cycles in this unit.
+    pub fn convert(&self, cycles: u64) -> f64 {
+        match self {
+            LatencyUnit::Cycles => cycles as f64,
+            LatencyUnit::Nanoseconds => cycles::to_seconds(cycles) * 1e9,
+            LatencyUnit::Microseconds => cycles::to_seconds(cycles) * 1e6,
+        }
+    }
+
+    /// Return the name of this unit, as written in client.toml.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LatencyUnit::Cycles => "cycles",
+            LatencyUnit::Nanoseconds => "ns",
+            LatencyUnit::Microseconds => "us",
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ClientConfig {
@@ -33,8 +81,89 @@ pub struct ClientConfig {
     // The number of responses that the client must receive before terminating the process.
     pub num_resps: u64,
 
+    // The number of responses received before the latencies are measured.
+    pub warmup_resps: u64,
+
     // The req rate per second.
     pub req_rate: u64,
+
+    // If true, print the per-tenant send summary at the end of the run.
+    pub verbose: bool,
+
+    // The socket send buffer size in bytes; zero keeps the kernel default.
+    pub sndbuf: usize,
+
+    // The socket receive buffer size in bytes; zero keeps the kernel default.
+    pub rcvbuf: usize,
+
+    // The unit for the printed latencies; cycles, ns or us.
+    pub latency_unit: LatencyUnit,
+
+    // If set, the latency samples are written to this file instead of being kept in memory.
+    pub raw_latency_output: Option<PathBuf>,
+
+    // If true, record the gaps between consecutive sends and print a histogram at the end.
+    pub record_gaps: bool,
+
+    // The maximum number of outstanding requests per sender; zero disables the back-pressure.
+    pub max_in_flight: u64,
+
+    // The maximum number of outstanding requests per tenant; zero disables the per-tenant limit.
+    pub per_tenant_inflight: u64,
+
+    // The number of responses between two ACKs from the receiver to the sender.
+    pub ack_interval: u64,
+
+    // The receiver counts a response as lost if nothing arrives for this long; zero waits forever.
+    pub recv_timeout_ms: u64,
+
+    // The send rate ramps up linearly from zero to req_rate over this many milliseconds.
+    pub ramp_up_duration_ms: u64,
+
+    // If non-zero, keep a uniform random sample of at most this many latencies.
+    pub reservoir_size: usize,
+
+    // If non-zero, send this many requests back to back, then pause for burst_gap_ms.
+    pub burst_size: u64,
+
+    // The pause in milliseconds between two bursts.
+    pub burst_gap_ms: f64,
+
+    // If non-zero, pace the requests with a token bucket refilled at this many tokens per second,
+    // instead of sending one every 1/req_rate seconds.
+    pub bucket_rate: u64,
+
+    // The capacity of the token bucket; the longest burst of back to back requests.
+    pub bucket_burst: u64,
+
+    // The format of the stats printed at the end of the run; text, json or csv.
+    pub output_format: OutputFormat,
+
+    // The version of the packet format sent to the server; see packet::PROTO_VERSION.
+    pub proto_version: u8,
+
+    // The number of responses the server sends back for each request; 0 is treated as 1.
+    pub responses_per_request: u8,
+
+    // If true, send and receive on the main thread instead of a thread pair per two cores.
+    pub single_thread: bool,
+
+    // If true, measure the loopback round-trip time before the run and subtract it from the
+    // latencies.
+    pub subtract_loopback: bool,
+
+    // If set, the latency samples are sent to the StatsD server at this host and statsd_port.
+    pub statsd_host: Option<String>,
+
+    // The UDP port of the StatsD server.
+    pub statsd_port: u16,
+
+    // If set, each request is also sent to the server at this IP address, and the latencies of
+    // the two servers are compared.
+    pub mirror_server_ip: Option<String>,
+
+    // If set, the receivers append their stats to this file instead of printing them to stdout.
+    pub output_file: Option<PathBuf>,
 }
 
 impl ClientConfig {
@@ -51,4 +180,44 @@ impl ClientConfig {
             }
         }
     }
+
+    /// Return the number of responses expected for each request sent; the server sends
+    /// `responses_per_request` of them, and so does the mirror server if there is one.
+    pub fn responses_per_send(&self) -> u8 {
+        let servers = if self.mirror_server_ip.is_some() {
+            2
+        } else {
+            1
+        };
+        std::cmp::max(self.responses_per_request, 1) * servers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latency_unit() {
+        let cycles = cycles::cycles_per_second() / 1000;
+        assert_eq!(LatencyUnit::Cycles.convert(cycles), cycles as f64);
+        assert!((LatencyUnit::Nanoseconds.convert(cycles) - 1e6).abs() < 1.0);
+        assert!((LatencyUnit::Microseconds.convert(cycles) - 1e3).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_latency_unit_parse() {
+        #[derive(Deserialize)]
+        struct Unit {
+            latency_unit: LatencyUnit,
+        }
+
+        let parse = |unit: &str| -> LatencyUnit {
+            let config: Unit = toml::from_str(&format!("latency_unit = \"{}\"", unit)).unwrap();
+            config.latency_unit
+        };
+        assert_eq!(parse("cycles"), LatencyUnit::Cycles);
+        assert_eq!(parse("ns"), LatencyUnit::Nanoseconds);
+        assert_eq!(parse("us"), LatencyUnit::Microseconds);
+    }
 }
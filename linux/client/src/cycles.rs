@@ -13,16 +13,31 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+#[cfg(feature = "std")]
 use std::sync::Once;
+#[cfg(feature = "std")]
 use time::PreciseTime;
 
+#[cfg(feature = "std")]
 static mut CYCLES_PER_SECOND: u64 = 0;
+#[cfg(feature = "std")]
 static INIT: Once = Once::new();
 
+#[cfg(feature = "std")]
+static mut OVERHEAD: u64 = 0;
+#[cfg(feature = "std")]
+static OVERHEAD_INIT: Once = Once::new();
+
+/// The CPU cycles per second without std, where the clock frequency can't be calibrated against
+/// the wall clock; set this to the invariant TSC frequency of the target processor at compile time.
+#[cfg(not(feature = "std"))]
+pub const CYCLES_PER_SECOND: u64 = 2_400_000_000;
+
 /// Perform once-only overall initialization for the cycles module, such
 /// as calibrating the clock frequency.  This method is invoked automatically
 /// during initialization.
 /// Stolen from the RAMCloud code base. Thanks, John.
+#[cfg(feature = "std")]
 fn init() -> u64 {
     // Compute the frequency of the fine-grained CPU timer: to do this,
     // take parallel time readings using both rdtsc and PreciseTime.
@@ -59,6 +74,7 @@ fn init() -> u64 {
 /// # Return
 ///
 /// Number of CPU cycles per second.
+#[cfg(feature = "std")]
 pub fn cycles_per_second() -> u64 {
     unsafe {
         INIT.call_once(|| {
@@ -68,6 +84,24 @@ pub fn cycles_per_second() -> u64 {
     }
 }
 
+/// Return the CPU cycles per second for the executing processor; `CYCLES_PER_SECOND` without std.
+///
+/// # Return
+///
+/// Number of CPU cycles per second.
+#[cfg(not(feature = "std"))]
+pub fn cycles_per_second() -> u64 {
+    CYCLES_PER_SECOND
+}
+
+/// Return the calibrated clock frequency in MHz; for the human-readable output.
+///
+/// # Return
+/// Number of CPU cycles per micro-second, rounded down.
+pub fn frequency_mhz() -> u64 {
+    cycles_per_second() / 1_000_000
+}
+
 /// Return a 64-bit timestamp using the rdtsc instruction.
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 pub fn rdtsc() -> u64 {
@@ -79,6 +113,76 @@ pub fn rdtsc() -> u64 {
     }
 }
 
+/// The index of the fixed-function counter for the instructions retired; bit 30 of the RDPMC index
+/// selects the fixed-function counters.
+#[cfg(target_arch = "x86_64")]
+pub const PMC_INSTRUCTIONS: u32 = 1 << 30;
+
+/// The index of the first general-purpose counter, which `rdpmc_llc_misses()` expects to be
+/// programmed for the last level cache misses; e.g. by `perf stat -e LLC-misses`.
+#[cfg(target_arch = "x86_64")]
+pub const PMC_LLC_MISSES: u32 = 0;
+
+/// Return the value of a hardware performance counter using the rdpmc instruction. The instruction
+/// faults in user mode unless CR4.PCE is set; on Linux, `echo 2 >
+/// /sys/bus/event_source/devices/cpu/rdpmc` sets it for all the processes. The counter must also
+/// be enabled, e.g. by running the client under `perf stat`, or the value doesn't change.
+///
+/// # Arguments
+/// *`counter`: The counter index; see `PMC_INSTRUCTIONS` and `PMC_LLC_MISSES`.
+///
+/// # Return
+/// The value of the counter; up to 48 bits wide, depending on the processor.
+#[cfg(target_arch = "x86_64")]
+pub fn rdpmc(counter: u32) -> u64 {
+    unsafe {
+        let lo: u32;
+        let hi: u32;
+        core::arch::asm!(
+            "rdpmc",
+            in("ecx") counter,
+            out("eax") lo,
+            out("edx") hi,
+            options(nomem, nostack)
+        );
+        ((hi as u64) << 32) | lo as u64
+    }
+}
+
+/// Return the number of instructions retired on this core; see `rdpmc()`.
+#[cfg(target_arch = "x86_64")]
+pub fn rdpmc_instructions() -> u64 {
+    rdpmc(PMC_INSTRUCTIONS)
+}
+
+/// Return the number of last level cache misses on this core; see `rdpmc()` and `PMC_LLC_MISSES`.
+#[cfg(target_arch = "x86_64")]
+pub fn rdpmc_llc_misses() -> u64 {
+    rdpmc(PMC_LLC_MISSES)
+}
+
+/// Return the cost in cycles of measuring an interval with two back-to-back rdtsc() calls.
+/// The overhead is measured once, as the minimum over 1000 iterations, and cached.
+///
+/// # Return
+///
+/// Number of CPU cycles added to each measured interval by the measurement itself.
+#[cfg(feature = "std")]
+pub fn overhead() -> u64 {
+    unsafe {
+        OVERHEAD_INIT.call_once(|| {
+            let mut overhead = std::u64::MAX;
+            for _ in 0..1000 {
+                let start = rdtsc();
+                let stop = rdtsc();
+                overhead = std::cmp::min(overhead, stop - start);
+            }
+            OVERHEAD = overhead;
+        });
+        OVERHEAD
+    }
+}
+
 /// Converts the number of CPU cycles to seconds.
 ///
 /// # Arguments
@@ -90,6 +194,15 @@ pub fn to_seconds(cycles: u64) -> f64 {
     cycles as f64 / cycles_per_second() as f64
 }
 
+/// Busy-wait for the given number of CPU cycles; more precise than a thread sleep.
+///
+/// # Arguments
+/// *`cycles`: Number of CPU cycles to wait for.
+pub fn sleep_for(cycles: u64) {
+    let stop = rdtsc() + cycles;
+    while rdtsc() < stop {}
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -102,6 +215,20 @@ mod test {
         assert!(cycles_per_second() < 5000000000);
     }
 
+    #[test]
+    fn test_frequency_mhz() {
+        let mhz = frequency_mhz();
+        assert!(mhz >= 1000 && mhz <= 10000);
+        assert_eq!(mhz, cycles_per_second() / 1_000_000);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_overhead() {
+        assert!(overhead() < 200);
+        assert_eq!(overhead(), overhead());
+    }
+
     #[test]
     fn test_rdtsc_sanity() {
         let start = rdtsc();
@@ -109,4 +236,12 @@ mod test {
         let stop = rdtsc();
         assert!(to_seconds(stop - start) - 1.0 < 0.001);
     }
+
+    #[test]
+    fn test_sleep_for() {
+        let cycles = cycles_per_second() / 1000;
+        let start = rdtsc();
+        sleep_for(cycles);
+        assert!(rdtsc() - start >= cycles);
+    }
 }
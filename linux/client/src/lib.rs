@@ -1,13 +1,74 @@
 #![feature(llvm_asm, integer_atomics)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
+#[cfg(feature = "std")]
+extern crate libc;
+#[cfg(feature = "std")]
 extern crate serde;
+#[cfg(feature = "std")]
 extern crate serde_aux;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "std")]
 extern crate toml;
 
+/// Print a line of diagnostics like `println!`; to stderr if stdout only has the stats, so that
+/// the JSON or CSV output can be piped to other tools. See `report::STATS_ONLY`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! diag {
+    ($($arg:tt)*) => {
+        if $crate::report::STATS_ONLY.load(::std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// This module measures the client's own loopback latency, to subtract it from the samples.
+#[cfg(feature = "std")]
+pub mod calibrate;
+
 /// This module is used for parsing the client configuration file.
+#[cfg(feature = "std")]
 pub mod config;
 
 /// This module contains the CPU cycles related functionality; rdtsc() etc.
 pub mod cycles;
+
+/// This module contains the packet format shared by the requests and the responses.
+#[cfg(feature = "std")]
+pub mod packet;
+
+/// This module receives the responses, and measures and prints their latencies.
+#[cfg(feature = "std")]
+pub mod receiver;
+
+/// This module formats the stats printed at the end of the run; text, JSON or CSV.
+#[cfg(feature = "std")]
+pub mod report;
+
+/// This module is used to write the raw latency samples to a file, and read them back.
+#[cfg(feature = "std")]
+pub mod samples;
+
+/// This module sends the requests to the server at the configured rate.
+#[cfg(feature = "std")]
+pub mod sender;
+
+/// This module contains the socket options used by the client; buffer sizes etc.
+#[cfg(feature = "std")]
+pub mod socket;
+
+/// This module sends the latency samples to a StatsD server, for the real-time dashboards.
+#[cfg(feature = "std")]
+pub mod statsd;
+
+#[cfg(feature = "std")]
+pub use config::ClientConfig;
+#[cfg(feature = "std")]
+pub use receiver::Receiver;
+#[cfg(feature = "std")]
+pub use sender::Sender;
@@ -0,0 +1,107 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+/// The size of a request/response packet on the wire, in bytes.
+pub const PACKET_SIZE: usize = 23;
+
+/// The version of the packet format written by this client.
+pub const PROTO_VERSION: u8 = 4;
+
+/// The packet sent by the client, and echoed back by the server in the response.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ResponsePacket {
+    // The version of the packet format; the receiver drops the responses of another version.
+    pub version: u8,
+
+    // The time stamp in cycles at which the client sent the request.
+    pub timestamp: u64,
+
+    // The lower 64 bits of the request's trace id; used to follow a request across the systems.
+    pub trace_id: u64,
+
+    // The number of responses the server should send back for this request; 0 is treated as 1.
+    pub responses: u8,
+
+    // The number of times the server retried the request; the client always sends 0.
+    pub retry_count: u8,
+
+    // The sequence number of the request on its socket; the receiver counts the gaps in it.
+    pub seq: u32,
+}
+
+impl ResponsePacket {
+    pub fn new(version: u8, timestamp: u64, trace_id: u64, responses: u8) -> ResponsePacket {
+        ResponsePacket {
+            version: version,
+            timestamp: timestamp,
+            trace_id: trace_id,
+            responses: responses,
+            retry_count: 0,
+            seq: 0,
+        }
+    }
+
+    /// Serialize the packet into the little-endian wire format; the version byte, the time stamp,
+    /// the trace id, the number of responses, the retry count, then the sequence number.
+    pub fn to_bytes(&self) -> [u8; PACKET_SIZE] {
+        let mut buf = [0; PACKET_SIZE];
+        buf[0] = self.version;
+        buf[1..9].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[9..17].copy_from_slice(&self.trace_id.to_le_bytes());
+        buf[17] = self.responses;
+        buf[18] = self.retry_count;
+        buf[19..23].copy_from_slice(&self.seq.to_le_bytes());
+        buf
+    }
+
+    /// Deserialize the packet from the little-endian wire format.
+    pub fn from_bytes(buf: &[u8; PACKET_SIZE]) -> ResponsePacket {
+        let mut timestamp = [0; 8];
+        let mut trace_id = [0; 8];
+        let mut seq = [0; 4];
+        timestamp.copy_from_slice(&buf[1..9]);
+        trace_id.copy_from_slice(&buf[9..17]);
+        seq.copy_from_slice(&buf[19..23]);
+        ResponsePacket {
+            version: buf[0],
+            timestamp: u64::from_le_bytes(timestamp),
+            trace_id: u64::from_le_bytes(trace_id),
+            responses: buf[17],
+            retry_count: buf[18],
+            seq: u32::from_le_bytes(seq),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut packet =
+            ResponsePacket::new(PROTO_VERSION, 0x0102030405060708, 0x1112131415161718, 3);
+        packet.retry_count = 2;
+        packet.seq = 0x21222324;
+        let buf = packet.to_bytes();
+        assert_eq!(buf[0], PROTO_VERSION);
+        assert_eq!(buf[1..9], [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(buf[9..17], [0x18, 0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0x11]);
+        assert_eq!(buf[17], 3);
+        assert_eq!(buf[18], 2);
+        assert_eq!(buf[19..23], [0x24, 0x23, 0x22, 0x21]);
+        assert_eq!(ResponsePacket::from_bytes(&buf), packet);
+    }
+}
@@ -0,0 +1,1142 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::calibrate;
+use super::config::{ClientConfig, LatencyUnit};
+use super::cycles;
+use super::packet::{ResponsePacket, PACKET_SIZE};
+use super::report::{self, OutputFormat, RunStats};
+use super::samples::{self, LatencyWriter, Reservoir};
+use super::sender::{Sender, TENANT_ACK_SIZE};
+use super::statsd::StatsdSink;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::marker::PhantomData;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+
+// Makes sure the CSV header is printed once, before the first row.
+static CSV_HEADER: Once = Once::new();
+
+// Only every this many latency samples is sent to StatsD, to limit the network overhead.
+const STATSD_INTERVAL: u64 = 100;
+
+/// Pairs the responses of the primary and the secondary server to the same request by the
+/// sequence number, and keeps the latencies of each server and the difference between them.
+struct MirrorPairs {
+    // The IP address of the secondary server; the responses from any other address are the
+    // primary's.
+    secondary_ip: IpAddr,
+
+    // The latencies of the requests answered by only one of the servers so far, by sequence
+    // number; the primary's latency, then the secondary's.
+    pending: HashMap<u32, (Option<u64>, Option<u64>)>,
+
+    // The latencies of the primary server in cycles.
+    primary: Vec<u64>,
+
+    // The latencies of the secondary server in cycles.
+    secondary: Vec<u64>,
+
+    // The secondary's latency minus the primary's for each request answered by both, in cycles.
+    deltas: Vec<i64>,
+}
+
+impl MirrorPairs {
+    fn new(secondary_ip: IpAddr) -> MirrorPairs {
+        MirrorPairs {
+            secondary_ip: secondary_ip,
+            pending: HashMap::new(),
+            primary: Vec::new(),
+            secondary: Vec::new(),
+            deltas: Vec::new(),
+        }
+    }
+
+    /// Return true if the response came from the secondary server.
+    fn is_secondary(&self, src: &SocketAddr) -> bool {
+        src.ip() == self.secondary_ip
+    }
+
+    /// Record the latency of a server's response, and the difference to the other server's once
+    /// both answered the request. With many responses per request, only the first one of each
+    /// server is recorded.
+    ///
+    /// # Arguments
+    /// *`secondary`: True if the response came from the secondary server.
+    /// *`seq`: The sequence number of the request.
+    /// *`latency`: The latency in cycles.
+    fn record(&mut self, secondary: bool, seq: u32, latency: u64) {
+        let pair = self.pending.entry(seq).or_insert((None, None));
+        let side = if secondary { &mut pair.1 } else { &mut pair.0 };
+        if side.is_some() {
+            return;
+        }
+        *side = Some(latency);
+        if secondary {
+            self.secondary.push(latency);
+        } else {
+            self.primary.push(latency);
+        }
+
+        if let (Some(primary), Some(secondary)) = *pair {
+            self.deltas.push(secondary as i64 - primary as i64);
+            self.pending.remove(&seq);
+        }
+    }
+
+    /// Print the median and the 99th percentile latency of each server, and of the difference
+    /// between them.
+    ///
+    /// # Arguments
+    /// *`unit`: The unit for the printed latencies.
+    fn print(&mut self, unit: LatencyUnit) {
+        for (name, latencies) in [
+            ("Primary", &mut self.primary),
+            ("Secondary", &mut self.secondary),
+        ]
+        .iter_mut()
+        {
+            if !latencies.is_empty() {
+                let (m, t) = median_and_tail(latencies);
+                diag!(
+                    "Mirror {} Median({}) {:.2} Tail({}) {:.2}",
+                    name,
+                    unit.name(),
+                    unit.convert(m),
+                    unit.name(),
+                    unit.convert(t)
+                );
+            }
+        }
+
+        // The difference is negative when the secondary answered faster.
+        let signed = |delta: i64| delta.signum() as f64 * unit.convert(delta.abs() as u64);
+        if !self.deltas.is_empty() {
+            self.deltas.sort();
+            let n = self.deltas.len();
+            diag!(
+                "Mirror Delta Median({}) {:.2} Tail({}) {:.2} Pairs {} Unpaired {}",
+                unit.name(),
+                signed(self.deltas[n / 2]),
+                unit.name(),
+                signed(self.deltas[(n * 99) / 100]),
+                n,
+                self.pending.len()
+            );
+        }
+    }
+}
+
+/// Receives the responses on one socket, and measures their latencies.
+///
+/// A receiver is single-threaded by design: `recv()` updates `recvd`, `latencies` and `stop`
+/// without any locking, as each receiver thread owns its own receiver and socket. Sharing one
+/// across threads, e.g. in an `Arc<Mutex<_>>`, would interleave the counters of two threads, so
+/// the receiver is neither `Send` nor `Sync`, and must be created on the thread which uses it.
+///
+/// The stats are printed when the receiver is dropped; by the master receiver only, with the
+/// latencies.
+pub struct Receiver {
+    // The network socket required to receives response packets from the network.
+    socket: Arc<UdpSocket>,
+
+    // The number of response packets to wait for before printing out statistics.
+    responses: u64,
+
+    // The number of responses received before the latencies are measured.
+    warmup: u64,
+
+    // Time stamp in cycles at which measurement started. Required to calculate observed
+    // throughput of the Sandstorm server.
+    start: u64,
+
+    // The total number of responses received so far.
+    recvd: u64,
+
+    // Vector of sampled request latencies. Required to calculate distributions once all responses
+    // have been received. With more than one response per request, the latency to the first one.
+    latencies: Vec<u64>,
+
+    // A sorted copy of `latencies` for `percentile()`; sorted again only once more latencies have
+    // been measured, so that asking for several percentiles sorts the samples once.
+    sorted_latencies: RefCell<Vec<u64>>,
+
+    // The number of responses expected for each request; at least 1.
+    responses_per_request: u64,
+
+    // The number of responses expected for each request sent, from all the servers; the ACKs to
+    // the sender count the requests.
+    responses_per_send: u64,
+
+    // The number of responses seen so far for the requests still streaming, by trace id. Only
+    // used with more than one response per request.
+    streams: HashMap<u64, u64>,
+
+    // The latencies to the last response of each request, with more than one response per request.
+    // These are always kept in memory, even with a raw latency file or a reservoir.
+    last_latencies: Vec<u64>,
+
+    // The number of measured requests for each retry count echoed back by the server.
+    retry_counts: Vec<u64>,
+
+    // If true, this receiver will make latency measurements.
+    master: bool,
+
+    // Time stamp in cycles at which measurement stopped.
+    stop: u64,
+
+    // The unit for the printed latencies.
+    latency_unit: LatencyUnit,
+
+    // If set, the latency samples are written to this file instead of `latencies`.
+    raw_latency_output: Option<(PathBuf, LatencyWriter)>,
+
+    // If set, a bounded random sample of the latencies is kept instead of `latencies`.
+    reservoir: Option<Reservoir>,
+
+    // If set, the number of responses received is ACKed to the sender's control socket.
+    ack_addr: Option<SocketAddr>,
+
+    // The number of responses between two ACKs.
+    ack_interval: u64,
+
+    // If true, the number of requests answered for each tenant is also ACKed to the sender, as
+    // soon as each request is answered.
+    ack_tenants: bool,
+
+    // The number of responses received for each tenant, by the tenant's port.
+    tenant_recvd: HashMap<u16, u64>,
+
+    // The cost of the rdtsc() measurement in cycles; subtracted from each latency sample.
+    overhead: u64,
+
+    // The client's loopback round-trip time in cycles; subtracted from the latencies at the end.
+    baseline: u64,
+
+    // If set, the latency samples are also sent to a StatsD server.
+    statsd: Option<StatsdSink>,
+
+    // The number of responses considered lost, after the socket timed out waiting for them.
+    lost: u64,
+
+    // The format of the stats printed at the end of the run.
+    output_format: OutputFormat,
+
+    // The version of the packet format expected in the responses.
+    proto_version: u8,
+
+    // The number of responses with another version of the packet format; these aren't measured.
+    version_mismatches: u64,
+
+    // The number of responses shorter than a packet, e.g. error responses; these aren't measured.
+    truncated: u64,
+
+    // The sequence number expected in the next request's responses.
+    next_seq: u32,
+
+    // The number of gaps in the sequence numbers of the responses; each is one or more requests
+    // or responses lost on the way.
+    gap_count: u64,
+
+    // The number of sequence numbers skipped over by the gaps.
+    missed: u64,
+
+    // If set, the requests are also sent to a secondary server, and the responses of the two
+    // servers are paired up.
+    mirror: Option<MirrorPairs>,
+
+    // If set, the stats are appended to this file instead of printed to stdout. The flag is true
+    // if the file was empty when opened, and so still needs the CSV header.
+    output: Option<(BufWriter<File>, bool)>,
+
+    // Makes the receiver !Send and !Sync; see the documentation on the struct.
+    _single_threaded: PhantomData<*mut ()>,
+}
+
+impl Receiver {
+    /// Create a receiver for the responses in `config`; the clock for the throughput starts here.
+    ///
+    /// # Arguments
+    /// *`socket`: The socket the sender sends the requests on.
+    /// *`config`: The client configuration; the number of responses, the output and the timeout.
+    /// *`master`: If true, the latencies are measured and printed when the receiver is dropped.
+    /// *`ack_addr`: The address of the sender's control socket, for the flow control; see
+    ///              `Sender::new()`.
+    pub fn new(
+        socket: Arc<UdpSocket>,
+        config: &ClientConfig,
+        master: bool,
+        ack_addr: Option<SocketAddr>,
+    ) -> Receiver {
+        let mut raw_latency_output = None;
+        if let Some(ref path) = config.raw_latency_output {
+            if master {
+                let writer = LatencyWriter::create(path).expect("couldn't create the latency file");
+                raw_latency_output = Some((path.clone(), writer));
+            }
+        }
+
+        let mut reservoir = None;
+        if master && config.reservoir_size != 0 && raw_latency_output.is_none() {
+            reservoir = Some(Reservoir::new(config.reservoir_size));
+        }
+
+        // The latencies are read back from the file or the reservoir at the end, if either is used.
+        let mut capacity = config.num_resps as usize;
+        if raw_latency_output.is_some() || reservoir.is_some() {
+            capacity = 0;
+        }
+
+        let mut statsd = None;
+        if let (true, Some(ref host)) = (master, &config.statsd_host) {
+            let sink = StatsdSink::new(host, config.statsd_port, STATSD_INTERVAL)
+                .expect("couldn't create the StatsD socket");
+            statsd = Some(sink);
+        }
+
+        // Without a timeout, a lost response blocks the receiver forever.
+        if config.recv_timeout_ms != 0 {
+            let timeout = Duration::from_millis(config.recv_timeout_ms);
+            socket
+                .set_read_timeout(Some(timeout))
+                .expect("couldn't set the read timeout");
+        }
+
+        // Appending lets the receivers of all the threads share a file without clobbering it.
+        let mut output = None;
+        if let Some(ref path) = config.output_file {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .expect("couldn't open the output file");
+            let empty = file.metadata().map_or(false, |m| m.len() == 0);
+            output = Some((BufWriter::new(file), empty));
+        }
+
+        Receiver {
+            socket: socket,
+            responses: config.num_resps,
+            warmup: config.warmup_resps,
+            start: cycles::rdtsc(),
+            recvd: 0,
+            latencies: Vec::with_capacity(capacity),
+            sorted_latencies: RefCell::new(Vec::new()),
+            responses_per_request: std::cmp::max(config.responses_per_request, 1) as u64,
+            responses_per_send: config.responses_per_send() as u64,
+            streams: HashMap::new(),
+            last_latencies: Vec::new(),
+            retry_counts: vec![0; 256],
+            master: master,
+            stop: 0,
+            latency_unit: config.latency_unit,
+            raw_latency_output: raw_latency_output,
+            reservoir: reservoir,
+            ack_addr: ack_addr,
+            ack_interval: std::cmp::max(config.ack_interval, 1),
+            ack_tenants: config.per_tenant_inflight != 0,
+            tenant_recvd: HashMap::new(),
+            overhead: cycles::overhead(),
+            baseline: 0,
+            statsd: statsd,
+            lost: 0,
+            output_format: config.output_format,
+            proto_version: config.proto_version,
+            version_mismatches: 0,
+            truncated: 0,
+            next_seq: 0,
+            gap_count: 0,
+            missed: 0,
+            mirror: config
+                .mirror_server_ip
+                .as_ref()
+                .map(|ip| MirrorPairs::new(ip.parse().unwrap())),
+            output: output,
+            _single_threaded: PhantomData,
+        }
+    }
+
+    /// Return true once the required number of responses were received or lost.
+    pub fn done(&self) -> bool {
+        self.responses <= self.recvd + self.lost
+    }
+
+    /// Count a response, ACK it if needed, and measure its latency after the warmup. Only the
+    /// first `len` bytes of `buf` were received, from `src`.
+    fn handle_response(&mut self, buf: &[u8; PACKET_SIZE], len: usize, src: SocketAddr) {
+        self.recvd += 1;
+        if let Some(addr) = self.ack_addr {
+            if self.recvd % self.ack_interval == 0 {
+                // The sender counts requests, not responses.
+                let acked = self.recvd / self.responses_per_send;
+                let _ = self.socket.send_to(&acked.to_le_bytes(), addr);
+            }
+        }
+        if len < PACKET_SIZE {
+            self.truncated += 1;
+            return;
+        }
+        let packet = ResponsePacket::from_bytes(buf);
+
+        // Take latency measurement after warmup; say after 2M responses(warmup_resps).
+        if packet.version != self.proto_version {
+            self.version_mismatches += 1;
+            return;
+        }
+
+        // The secondary server's responses are only measured against the primary's.
+        let measure = self.recvd > self.warmup && self.master;
+        let latency = (cycles::rdtsc() - packet.timestamp).saturating_sub(self.overhead);
+        if let Some(ref mut mirror) = self.mirror {
+            let secondary = mirror.is_secondary(&src);
+            if measure {
+                mirror.record(secondary, packet.seq, latency);
+            }
+            if secondary {
+                return;
+            }
+        }
+
+        self.track_seq(packet.seq);
+        if measure {
+            if self.responses_per_request == 1 {
+                self.record(latency, packet.retry_count);
+            } else {
+                // Streaming request; the first response measures the first-byte latency, and the
+                // last one the last-byte latency.
+                let seen = {
+                    let seen = self.streams.entry(packet.trace_id).or_insert(0);
+                    *seen += 1;
+                    *seen
+                };
+                if seen == 1 {
+                    self.record(latency, packet.retry_count);
+                }
+                if seen >= self.responses_per_request {
+                    self.streams.remove(&packet.trace_id);
+                    self.last_latencies.push(latency);
+                }
+            }
+            if self.recvd % 1000000 == 0 {
+                diag!("Recvd {} responses", self.recvd);
+            }
+        }
+    }
+
+    /// ACK the number of requests of a tenant answered so far, once all the responses to a request
+    /// of the tenant were received; only if the per-tenant ACKs are enabled.
+    ///
+    /// # Arguments
+    /// *`tenant`: The port of the tenant; the server answers from the port the request was sent to.
+    fn ack_tenant(&mut self, tenant: u16) {
+        if let (true, Some(addr)) = (self.ack_tenants, self.ack_addr) {
+            let recvd = {
+                let recvd = self.tenant_recvd.entry(tenant).or_insert(0);
+                *recvd += 1;
+                *recvd
+            };
+            if recvd % self.responses_per_send == 0 {
+                let mut ack = [0; TENANT_ACK_SIZE];
+                ack[..2].copy_from_slice(&tenant.to_le_bytes());
+                ack[2..].copy_from_slice(&(recvd / self.responses_per_send).to_le_bytes());
+                let _ = self.socket.send_to(&ack, addr);
+            }
+        }
+    }
+
+    /// Count a gap if the sequence number skips over the next one expected. The sequence numbers
+    /// behind the next one expected are the later responses of a streaming request, or the
+    /// reordered responses; these are ignored, so a reordered response counts as missed.
+    ///
+    /// # Arguments
+    /// *`seq`: The sequence number of the request echoed back in the response.
+    fn track_seq(&mut self, seq: u32) {
+        // Wrapping differences over half the sequence space are behind the next one expected.
+        let ahead = seq.wrapping_sub(self.next_seq);
+        if ahead > u32::max_value() / 2 {
+            return;
+        }
+        if ahead > 0 {
+            self.gap_count += 1;
+            self.missed += ahead as u64;
+        }
+        self.next_seq = seq.wrapping_add(1);
+    }
+
+    /// Return the estimated number of requests lost so far, whose responses never arrived; counted
+    /// from the gaps in the sequence numbers, so it is known during the run.
+    fn missed_packet_count(&self) -> u64 {
+        self.missed
+    }
+
+    /// Record the latency of a request, to the first response if there are many.
+    ///
+    /// # Arguments
+    /// *`latency`: The latency in cycles.
+    /// *`retry_count`: The number of times the server retried the request.
+    fn record(&mut self, latency: u64, retry_count: u8) {
+        self.retry_counts[retry_count as usize] += 1;
+        match (&mut self.raw_latency_output, &mut self.reservoir) {
+            (Some((_, ref mut writer)), _) => writer
+                .write(latency)
+                .expect("couldn't write to the latency file"),
+            (None, Some(ref mut reservoir)) => reservoir.add(latency),
+            (None, None) => self.latencies.push(latency),
+        }
+        if let Some(ref mut statsd) = self.statsd {
+            statsd.record(LatencyUnit::Nanoseconds.convert(latency) as u64);
+        }
+    }
+
+    /// Receive the responses until `num_resps` of them were received or lost, blocking on the
+    /// socket; a response is lost when the socket times out after `recv_timeout_ms`.
+    pub fn recv(&mut self) {
+        let mut buf = [0; PACKET_SIZE];
+        loop {
+            // Receieved maximum number of packets, exit now.
+            if self.done() {
+                return;
+            }
+
+            // Check the responses; add latency to the vector.
+            match self.socket.recv_from(&mut buf) {
+                Ok((received, src)) => {
+                    self.handle_response(&buf, received, src);
+                    self.ack_tenant(src.port());
+                }
+                // Timed out; count the response as lost.
+                Err(ref e)
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                {
+                    self.lost += 1;
+                }
+                Err(e) => diag!("recv function failed: {:?}", e),
+            }
+
+            // Update the stop timestamp, if received the required number of responses.
+            if self.done() {
+                self.stop = cycles::rdtsc();
+            }
+        }
+    }
+
+    /// Read all the responses waiting on the socket, without blocking; the socket must be in the
+    /// non-blocking mode. Nothing is counted as lost here, as the responses may still be on the
+    /// way.
+    pub fn drain(&mut self) {
+        let mut buf = [0; PACKET_SIZE];
+        while !self.done() {
+            match self.socket.recv_from(&mut buf) {
+                Ok((received, src)) => {
+                    self.handle_response(&buf, received, src);
+                    self.ack_tenant(src.port());
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => diag!("recv function failed: {:?}", e),
+            }
+        }
+        self.stop = cycles::rdtsc();
+    }
+
+    /// Return the number of responses received so far.
+    pub fn recvd(&self) -> u64 {
+        self.recvd
+    }
+
+    /// Set the client's loopback round-trip time in cycles, to subtract from the latencies; see
+    /// `calibrate::loopback_rtt()`.
+    pub fn set_baseline(&mut self, baseline: u64) {
+        self.baseline = baseline;
+    }
+
+    /// Return the p-th percentile of the latencies measured so far in cycles; see `percentile()`.
+    fn percentile_cycles(&self, p: f64) -> Option<u64> {
+        let mut sorted = self.sorted_latencies.borrow_mut();
+        if sorted.len() != self.latencies.len() {
+            sorted.clone_from(&self.latencies);
+            samples::sort_latencies(&mut sorted);
+        }
+        sorted_percentile(&sorted, p)
+    }
+
+    /// Return the p-th percentile of the latencies measured so far, in nano-seconds; with more
+    /// than one response per request, of the latencies to the first response. The latencies are
+    /// only kept in memory without a raw latency file or a reservoir.
+    ///
+    /// # Arguments
+    /// *`p`: The percentile, as a fraction between 0 and 1; e.g. 0.99 for the tail latency.
+    ///
+    /// # Return
+    /// The percentile, or None if no latency was measured or `p` is out of [0, 1].
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        self.percentile_cycles(p)
+            .map(|cycles| LatencyUnit::Nanoseconds.convert(cycles))
+    }
+
+    /// Return the stats for the responses received so far, without the latencies. The goodput
+    /// only counts the valid responses; not the ones with another version of the packet format,
+    /// or shorter than a packet.
+    fn run_stats(&self) -> RunStats {
+        let elapsed = cycles::to_seconds(self.stop.saturating_sub(self.start));
+        let valid = self.recvd - self.version_mismatches - self.truncated;
+        RunStats {
+            throughput: self.recvd as f64 / elapsed,
+            goodput: valid as f64 / elapsed,
+            recvd: self.recvd,
+            lost: self.lost,
+            latency: None,
+            last_latency: None,
+            p99_retry_count: None,
+            latency_unit: self.latency_unit,
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        // Calculate & print the throughput for all client threads.
+        let mut stats = self.run_stats();
+        if self.version_mismatches != 0 {
+            diag!("Version mismatches {}", self.version_mismatches);
+        }
+        if self.truncated != 0 {
+            diag!("Truncated responses {}", self.truncated);
+        }
+        if self.gap_count != 0 {
+            diag!(
+                "Sequence gaps {} Missed requests {}",
+                self.gap_count,
+                self.missed_packet_count()
+            );
+        }
+
+        // Collect the latencies from the file or the reservoir, if either is used.
+        if let Some((ref path, ref mut writer)) = self.raw_latency_output {
+            writer.flush().expect("couldn't flush the latency file");
+            self.latencies = samples::read_latencies(path).expect("couldn't read the latency file");
+        }
+        if let Some(reservoir) = self.reservoir.take() {
+            self.latencies = reservoir.into_samples();
+        }
+        calibrate::subtract_baseline(&mut self.latencies, self.baseline);
+        calibrate::subtract_baseline(&mut self.last_latencies, self.baseline);
+
+        // Calculate & print median & tail latency only on the master thread.
+        if self.master && !self.latencies.is_empty() {
+            let (m, t) = median_and_tail(&mut self.latencies);
+            stats.latency = Some((self.latency_unit.convert(m), self.latency_unit.convert(t)));
+        }
+        if self.master && !self.last_latencies.is_empty() {
+            let (m, t) = median_and_tail(&mut self.last_latencies);
+            stats.last_latency = Some((self.latency_unit.convert(m), self.latency_unit.convert(t)));
+        }
+        if self.master {
+            stats.p99_retry_count = p99_retry_count(&self.retry_counts);
+        }
+        if let (true, Some(ref mut mirror)) = (self.master, &mut self.mirror) {
+            mirror.print(self.latency_unit);
+        }
+
+        let line = match self.output_format {
+            OutputFormat::Text => stats.to_text(),
+            OutputFormat::Json => stats.to_json(),
+            OutputFormat::Csv => stats.to_csv(),
+        };
+        match self.output {
+            Some((ref mut writer, ref mut empty)) => {
+                if self.output_format == OutputFormat::Csv && *empty {
+                    let _ = writeln!(writer, "{}", report::CSV_HEADER);
+                    *empty = false;
+                }
+                if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+                    diag!("Couldn't write the output file: {}", e);
+                }
+            }
+            None => {
+                if self.output_format == OutputFormat::Csv {
+                    CSV_HEADER.call_once(|| println!("{}", report::CSV_HEADER));
+                }
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+/// Sort the latencies, and return their median and 99th percentile.
+///
+/// # Arguments
+/// *`latencies`: The latency samples; must not be empty.
+fn median_and_tail(latencies: &mut Vec<u64>) -> (u64, u64) {
+    samples::sort_latencies(latencies);
+    (
+        sorted_percentile(latencies, 0.5).unwrap(),
+        sorted_percentile(latencies, 0.99).unwrap(),
+    )
+}
+
+/// Return the p-th percentile of the samples; the sample at index `p * n` of the `n` sorted
+/// samples, or the largest one for `p` = 1.
+///
+/// # Arguments
+/// *`sorted`: The samples, sorted in increasing order.
+/// *`p`: The percentile, as a fraction between 0 and 1.
+///
+/// # Return
+/// The percentile, or None if there are no samples or `p` is out of [0, 1].
+fn sorted_percentile(sorted: &[u64], p: f64) -> Option<u64> {
+    if sorted.is_empty() || !(p >= 0.0 && p <= 1.0) {
+        return None;
+    }
+    let index = std::cmp::min((sorted.len() as f64 * p) as usize, sorted.len() - 1);
+    Some(sorted[index])
+}
+
+/// Return the 99th percentile retry count, picked the same way as the latency percentiles.
+///
+/// # Arguments
+/// *`histogram`: The number of requests for each retry count.
+///
+/// # Return
+/// The retry count, or None if no request was counted.
+fn p99_retry_count(histogram: &[u64]) -> Option<u8> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    // The index of the percentile in the sorted retry counts.
+    let rank = (total * 99) / 100;
+    let mut seen = 0;
+    for (retries, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > rank {
+            return Some(retries as u8);
+        }
+    }
+    None
+}
+
+/// Return a warning if the number of responses received differs from the number of requests sent
+/// by more than 0.1%. The experiment then measured fewer requests than intended. Packets lost on
+/// the way to the server or back cause this divergence, and so does a `num_resps` different from
+/// `num_reqs` in client.toml.
+///
+/// # Arguments
+/// *`sent`: Number of requests sent out by the sender.
+/// *`recvd`: Number of responses received by the receiver on the same socket.
+/// *`responses_per_request`: Number of responses sent back for each request, by all the servers.
+pub fn delivery_warning(sent: u64, recvd: u64, responses_per_request: u8) -> Option<String> {
+    let expected = sent * std::cmp::max(responses_per_request, 1) as u64;
+    let diff = if expected > recvd {
+        expected - recvd
+    } else {
+        recvd - expected
+    };
+    if diff as f64 > expected as f64 * 0.001 {
+        Some(format!(
+            "[WARNING] Sent {} requests but received {} responses; check for packet loss or \
+             num_reqs and num_resps in client.toml",
+            sent, recvd
+        ))
+    } else {
+        None
+    }
+}
+
+/// Send the requests and receive the responses on a single thread; the receiver drains the
+/// socket after each attempt to send, so neither side blocks the other. Once all the requests are
+/// sent, the receiver waits for the remaining responses like a receiver thread, with the timeout
+/// from `recv_timeout_ms`.
+///
+/// # Arguments
+/// *`sender`: The sender for the socket.
+/// *`receiver`: The receiver for the same socket.
+pub fn run_interleaved(sender: &mut Sender, receiver: &mut Receiver) {
+    receiver
+        .socket
+        .set_nonblocking(true)
+        .expect("couldn't make the socket non-blocking");
+    while !sender.done() && !receiver.done() {
+        sender.poll_send();
+        receiver.drain();
+    }
+    receiver
+        .socket
+        .set_nonblocking(false)
+        .expect("couldn't make the socket blocking");
+    receiver.recv();
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::packet::PROTO_VERSION;
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_recv_timeout() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 3;
+        config.recv_timeout_ms = 10;
+
+        // Nothing is ever sent to this socket; each response times out and is counted as lost.
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let mut receiver = Receiver::new(socket, &config, false, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 0);
+        assert_eq!(receiver.lost, 3);
+    }
+
+    #[test]
+    fn test_delivery_warning() {
+        assert_eq!(delivery_warning(100000, 100000, 1), None);
+        assert_eq!(delivery_warning(100000, 99900, 1), None);
+        assert!(delivery_warning(100000, 99899, 1).is_some());
+        assert_eq!(delivery_warning(100000, 300000, 3), None);
+        assert!(delivery_warning(100000, 100000, 3).is_some());
+
+        // A lossy socket drops every tenth request; the receiver times out waiting for them.
+        let mut config = ClientConfig::default();
+        config.num_resps = 100;
+        config.recv_timeout_ms = 10;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+        let lossy = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sent = Arc::new(AtomicU64::new(0));
+        for i in 0..100 {
+            if i % 10 != 0 {
+                let buf = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), i, 1).to_bytes();
+                lossy.send_to(&buf, addr).unwrap();
+            }
+            sent.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut receiver = Receiver::new(socket, &config, false, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 90);
+        let warning = delivery_warning(sent.load(Ordering::Relaxed), receiver.recvd, 1).unwrap();
+        assert!(warning.contains("Sent 100 requests but received 90 responses"));
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 2;
+        config.proto_version = PROTO_VERSION;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        // A response from a server speaking another version of the packet format.
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for version in [PROTO_VERSION, PROTO_VERSION + 1].iter() {
+            let buf = ResponsePacket::new(*version, cycles::rdtsc(), 0, 1).to_bytes();
+            assert_eq!(buf[0], *version);
+            server.send_to(&buf, addr).unwrap();
+        }
+
+        let mut receiver = Receiver::new(socket, &config, false, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 2);
+        assert_eq!(receiver.version_mismatches, 1);
+    }
+
+    #[test]
+    fn test_streaming_responses() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 6;
+        config.responses_per_request = 3;
+        config.proto_version = PROTO_VERSION;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        // Two requests expecting 3 responses each, with their responses interleaved.
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let first = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), 1, 3).to_bytes();
+        let second = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), 2, 3).to_bytes();
+        for buf in [first, second, first, second, first, second].iter() {
+            server.send_to(buf, addr).unwrap();
+        }
+
+        let mut receiver = Receiver::new(socket, &config, true, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 6);
+        assert_eq!(receiver.latencies.len(), 2);
+        assert_eq!(receiver.last_latencies.len(), 2);
+        assert!(receiver.streams.is_empty());
+        assert!(receiver.last_latencies[0] >= receiver.latencies[0]);
+        assert!(receiver.last_latencies[1] >= receiver.latencies[1]);
+    }
+
+    #[test]
+    fn test_retry_counts() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 100;
+        config.proto_version = PROTO_VERSION;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        // 90 requests served at the first attempt, 8 retried once and 2 retried twice.
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for i in 0..100 {
+            let mut packet = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), i, 1);
+            packet.retry_count = match i {
+                0..=89 => 0,
+                90..=97 => 1,
+                _ => 2,
+            };
+            server.send_to(&packet.to_bytes(), addr).unwrap();
+        }
+
+        let mut receiver = Receiver::new(socket, &config, true, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 100);
+        assert_eq!(receiver.retry_counts[..4], [90, 8, 2, 0]);
+        assert_eq!(p99_retry_count(&receiver.retry_counts), Some(2));
+
+        // The percentile isn't always the maximum.
+        assert_eq!(p99_retry_count(&[149, 50, 0, 0, 0, 0, 0, 1]), Some(1));
+        assert_eq!(p99_retry_count(&[0; 256]), None);
+    }
+
+    #[test]
+    fn test_goodput() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 10;
+        config.proto_version = PROTO_VERSION;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        // 6 valid responses, 2 of another version and 2 error responses shorter than a packet.
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for i in 0..10 {
+            let version = if i % 5 == 1 {
+                PROTO_VERSION + 1
+            } else {
+                PROTO_VERSION
+            };
+            let buf = ResponsePacket::new(version, cycles::rdtsc(), i, 1).to_bytes();
+            let len = if i % 5 == 2 { 4 } else { PACKET_SIZE };
+            server.send_to(&buf[..len], addr).unwrap();
+        }
+
+        let mut receiver = Receiver::new(socket, &config, true, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 10);
+        assert_eq!(receiver.version_mismatches, 2);
+        assert_eq!(receiver.truncated, 2);
+        assert_eq!(receiver.latencies.len(), 6);
+
+        let stats = receiver.run_stats();
+        assert!((stats.goodput / stats.throughput - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sequence_gaps() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 6;
+        config.proto_version = PROTO_VERSION;
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let addr = socket.local_addr().unwrap();
+
+        // Requests 2, 5 and 6 are dropped, and request 4 arrives after request 7.
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for seq in [0, 1, 3, 7, 4, 8].iter() {
+            let mut packet = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), 0, 1);
+            packet.seq = *seq;
+            server.send_to(&packet.to_bytes(), addr).unwrap();
+        }
+
+        let mut receiver = Receiver::new(socket, &config, true, None);
+        receiver.recv();
+        assert_eq!(receiver.recvd, 6);
+        assert_eq!(receiver.gap_count, 2);
+        assert_eq!(receiver.missed_packet_count(), 4);
+
+        // The streamed responses of a request repeat its sequence number; these aren't gaps.
+        receiver.track_seq(8);
+        receiver.track_seq(9);
+        receiver.track_seq(9);
+        assert_eq!(receiver.gap_count, 2);
+
+        // The sequence numbers wrap around.
+        receiver.next_seq = u32::max_value();
+        receiver.track_seq(u32::max_value());
+        receiver.track_seq(1);
+        assert_eq!(receiver.gap_count, 3);
+        assert_eq!(receiver.missed_packet_count(), 5);
+    }
+
+    #[test]
+    fn test_interleaved_loop() {
+        // An echo server for the only tenant, on port 1024.
+        let server = UdpSocket::bind("127.0.0.1:1024").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let echo = thread::spawn(move || {
+            let mut buf = [0; PACKET_SIZE];
+            while let Ok((amt, src)) = server.recv_from(&mut buf) {
+                let _ = server.send_to(&buf[..amt], src);
+            }
+        });
+
+        let mut config = ClientConfig::default();
+        config.server_ip = "127.0.0.1".to_string();
+        config.num_tenants = 1;
+        config.num_reqs = 1000;
+        config.num_resps = 1000;
+        config.req_rate = 1000000;
+        config.recv_timeout_ms = 1000;
+        config.proto_version = PROTO_VERSION;
+
+        // At most 8 requests in flight; the sender waits for the ACKs from the receiver on the
+        // same thread, so the loop deadlocks unless it drains the responses while sending.
+        config.max_in_flight = 8;
+        config.ack_interval = 4;
+        let control = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ack_addr = control.local_addr().unwrap();
+
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let sent = Arc::new(AtomicU64::new(0));
+        let mut sender = Sender::new(Arc::clone(&socket), &config, Some(control), sent);
+        let mut receiver = Receiver::new(socket, &config, false, Some(ack_addr));
+        run_interleaved(&mut sender, &mut receiver);
+        assert_eq!(sender.stats().sent, 1000);
+        assert_eq!(receiver.recvd, 1000);
+        assert_eq!(receiver.lost, 0);
+        echo.join().unwrap();
+    }
+
+    #[test]
+    fn test_receiver_percentile() {
+        let config = ClientConfig::default();
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let mut receiver = Receiver::new(socket, &config, false, None);
+        receiver.stop = receiver.start + 1;
+        assert_eq!(receiver.percentile(0.5), None);
+
+        // 100 latencies of 1000 to 100000 cycles, out of order.
+        receiver.latencies = (1..101).rev().map(|i| i * 1000).collect();
+        let ns = |cycles| Some(LatencyUnit::Nanoseconds.convert(cycles));
+        assert_eq!(receiver.percentile(0.0), ns(1000));
+        assert_eq!(receiver.percentile(0.5), ns(51000));
+        assert_eq!(receiver.percentile(0.9), ns(91000));
+        assert_eq!(receiver.percentile(0.99), ns(100000));
+        assert_eq!(receiver.percentile(1.0), ns(100000));
+        assert_eq!(receiver.percentile(-0.1), None);
+        assert_eq!(receiver.percentile(1.5), None);
+        assert_eq!(receiver.latencies[0], 100000);
+    }
+
+    #[test]
+    fn test_receiver_output_file() {
+        // Two receiver groups, each with its own output file.
+        let dir = env::temp_dir();
+        let paths: Vec<_> = (0..2)
+            .map(|i| dir.join(format!("client-output-{}-{}.json", process::id(), i)))
+            .collect();
+        for (i, path) in paths.iter().enumerate() {
+            let _ = fs::remove_file(path);
+            let mut config = ClientConfig::default();
+            config.output_format = OutputFormat::Json;
+            config.output_file = Some(path.clone());
+            let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+            let mut receiver = Receiver::new(socket, &config, false, None);
+            receiver.recvd = 100 * (i as u64 + 1);
+            receiver.stop = receiver.start + cycles::cycles_per_second();
+        }
+
+        // Each file only has the stats of its own receiver.
+        for (i, path) in paths.iter().enumerate() {
+            let contents = fs::read_to_string(path).unwrap();
+            assert_eq!(contents.lines().count(), 1);
+            let recvd = format!("\"recvd\":{},", 100 * (i + 1));
+            assert!(contents.contains(&recvd));
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_mirror_sender() {
+        // Echo servers for the only tenant, on port 1024; the secondary answers 1ms later.
+        let delay = Duration::from_millis(1);
+        let mut servers = vec![];
+        for (ip, delay) in [("127.0.0.3", None), ("127.0.0.4", Some(delay))]
+            .iter()
+            .cloned()
+        {
+            let server = UdpSocket::bind((ip, 1024)).unwrap();
+            server
+                .set_read_timeout(Some(Duration::from_millis(100)))
+                .unwrap();
+            servers.push(thread::spawn(move || {
+                let mut buf = [0; PACKET_SIZE];
+                while let Ok((amt, src)) = server.recv_from(&mut buf) {
+                    if let Some(delay) = delay {
+                        thread::sleep(delay);
+                    }
+                    let _ = server.send_to(&buf[..amt], src);
+                }
+            }));
+        }
+
+        let mut config = ClientConfig::default();
+        config.server_ip = "127.0.0.3".to_string();
+        config.mirror_server_ip = Some("127.0.0.4".to_string());
+        config.num_tenants = 1;
+        config.num_reqs = 100;
+        config.num_resps = 200;
+        config.req_rate = 100000;
+        config.recv_timeout_ms = 1000;
+        config.proto_version = PROTO_VERSION;
+
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let sent = Arc::new(AtomicU64::new(0));
+        let mut sender = Sender::new(Arc::clone(&socket), &config, None, sent);
+        let mut receiver = Receiver::new(socket, &config, true, None);
+        run_interleaved(&mut sender, &mut receiver);
+        assert_eq!(sender.stats().sent, 100);
+        assert_eq!(receiver.recvd, 200);
+        assert_eq!(receiver.lost, 0);
+
+        // Each server's latencies are kept apart; the main stats only have the primary's.
+        assert_eq!(receiver.latencies.len(), 100);
+        assert_eq!(receiver.gap_count, 0);
+        let mirror = receiver.mirror.as_mut().unwrap();
+        assert_eq!(mirror.primary.len(), 100);
+        assert_eq!(mirror.secondary.len(), 100);
+        assert_eq!(mirror.deltas.len(), 100);
+        assert!(mirror.pending.is_empty());
+
+        // The secondary is slower by at least the delay in the median.
+        mirror.deltas.sort();
+        let delay_cycles = cycles::cycles_per_second() / 1000;
+        assert!(mirror.deltas[50] >= delay_cycles as i64);
+        drop(receiver);
+        for server in servers {
+            server.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,233 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::LatencyUnit;
+
+use std::sync::atomic::AtomicBool;
+
+/// True if stdout only has the stats at the end of the run; set for the json and csv formats. The
+/// diagnostics printed with `diag!` go to stderr then.
+pub static STATS_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// The format of the stats printed at the end of the run; picked with `--output-format`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    #[serde(rename = "text")]
+    Text,
+
+    #[serde(rename = "json")]
+    Json,
+
+    #[serde(rename = "csv")]
+    Csv,
+}
+
+impl Default for OutputFormat {
+    fn default() -> OutputFormat {
+        OutputFormat::Text
+    }
+}
+
+impl OutputFormat {
+    /// Parse the value of the `--output-format` flag; one of text, json or csv.
+    pub fn parse(format: &str) -> Option<OutputFormat> {
+        match format {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// The header row for the CSV format; the columns match `RunStats::to_csv()`.
+pub const CSV_HEADER: &str =
+    "throughput,recvd,lost,median,p99,latency_unit,last_median,last_p99,p99_retry_count,goodput";
+
+/// The stats printed by a receiver at the end of the run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunStats {
+    // The observed throughput in responses per second.
+    pub throughput: f64,
+
+    // The observed throughput in valid responses per second; without the error responses.
+    pub goodput: f64,
+
+    // The number of responses received.
+    pub recvd: u64,
+
+    // The number of responses considered lost.
+    pub lost: u64,
+
+    // The median and 99th percentile latency in `latency_unit`; only measured on the master.
+    // With more than one response per request, the latency to the first response.
+    pub latency: Option<(f64, f64)>,
+
+    // The median and 99th percentile latency to the last response of each request; only measured
+    // with more than one response per request.
+    pub last_latency: Option<(f64, f64)>,
+
+    // The 99th percentile of the retry counts echoed back by the server; measured along with the
+    // latencies.
+    pub p99_retry_count: Option<u8>,
+
+    // The unit for the latencies.
+    pub latency_unit: LatencyUnit,
+}
+
+impl RunStats {
+    /// Return the stats in the format printed before the `--output-format` flag; one per line.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("Throughput {}", self.throughput),
+            format!("Goodput {}", self.goodput),
+        ];
+        if self.lost != 0 {
+            lines.push(format!("Lost {} responses", self.lost));
+        }
+        if let Some((median, p99)) = self.latency {
+            lines.push(format!(">>> {} {}", median, p99));
+        }
+        if let Some((median, p99)) = self.last_latency {
+            lines.push(format!("Last >>> {} {}", median, p99));
+        }
+        if let Some(retries) = self.p99_retry_count {
+            lines.push(format!("Retries >>> {}", retries));
+        }
+        lines.join("\n")
+    }
+
+    /// Return the stats as a single JSON object; the latencies are null if not measured.
+    pub fn to_json(&self) -> String {
+        let (median, p99) = format_latency(self.latency, "null");
+        let (last_median, last_p99) = format_latency(self.last_latency, "null");
+        let retries = self
+            .p99_retry_count
+            .map_or("null".to_string(), |retries| retries.to_string());
+        format!(
+            "{{\"throughput\":{},\"goodput\":{},\"recvd\":{},\"lost\":{},\"median\":{},\"p99\":{},\"latency_unit\":\"{}\",\"last_median\":{},\"last_p99\":{},\"p99_retry_count\":{}}}",
+            self.throughput,
+            self.goodput,
+            self.recvd,
+            self.lost,
+            median,
+            p99,
+            self.latency_unit.name(),
+            last_median,
+            last_p99,
+            retries
+        )
+    }
+
+    /// Return the stats as a CSV row under `CSV_HEADER`; the latencies are empty if not measured.
+    pub fn to_csv(&self) -> String {
+        let (median, p99) = format_latency(self.latency, "");
+        let (last_median, last_p99) = format_latency(self.last_latency, "");
+        let retries = self
+            .p99_retry_count
+            .map_or(String::new(), |retries| retries.to_string());
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.throughput,
+            self.recvd,
+            self.lost,
+            median,
+            p99,
+            self.latency_unit.name(),
+            last_median,
+            last_p99,
+            retries,
+            self.goodput
+        )
+    }
+}
+
+/// Format a median and 99th percentile latency pair, or `missing` for both if not measured.
+fn format_latency(latency: Option<(f64, f64)>, missing: &str) -> (String, String) {
+    match latency {
+        Some((median, p99)) => (median.to_string(), p99.to_string()),
+        None => (missing.to_string(), missing.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats(latency: Option<(f64, f64)>) -> RunStats {
+        RunStats {
+            throughput: 1000.5,
+            goodput: 990.5,
+            recvd: 2000,
+            lost: 3,
+            latency: latency,
+            last_latency: None,
+            p99_retry_count: latency.map(|_| 1),
+            latency_unit: LatencyUnit::Microseconds,
+        }
+    }
+
+    #[test]
+    fn test_output_format_parse() {
+        assert_eq!(OutputFormat::parse("text"), Some(OutputFormat::Text));
+        assert_eq!(OutputFormat::parse("json"), Some(OutputFormat::Json));
+        assert_eq!(OutputFormat::parse("csv"), Some(OutputFormat::Csv));
+        assert_eq!(OutputFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn test_run_stats_formats() {
+        let master = stats(Some((12.5, 40.0)));
+        assert_eq!(
+            master.to_text(),
+            "Throughput 1000.5\nGoodput 990.5\nLost 3 responses\n>>> 12.5 40\nRetries >>> 1"
+        );
+        assert_eq!(
+            master.to_json(),
+            "{\"throughput\":1000.5,\"goodput\":990.5,\"recvd\":2000,\"lost\":3,\"median\":12.5,\"p99\":40,\"latency_unit\":\"us\",\"last_median\":null,\"last_p99\":null,\"p99_retry_count\":1}"
+        );
+        assert_eq!(master.to_csv(), "1000.5,2000,3,12.5,40,us,,,1,990.5");
+        assert_eq!(
+            master.to_csv().split(',').count(),
+            CSV_HEADER.split(',').count()
+        );
+
+        let other = stats(None);
+        assert_eq!(
+            other.to_text(),
+            "Throughput 1000.5\nGoodput 990.5\nLost 3 responses"
+        );
+        assert!(other.to_json().contains("\"median\":null,\"p99\":null"));
+        assert!(other.to_json().ends_with("\"p99_retry_count\":null}"));
+        assert_eq!(other.to_csv(), "1000.5,2000,3,,,us,,,,990.5");
+    }
+
+    #[test]
+    fn test_last_latency_formats() {
+        let mut streaming = stats(Some((12.5, 40.0)));
+        streaming.last_latency = Some((30.0, 90.5));
+        assert_eq!(
+            streaming.to_text(),
+            "Throughput 1000.5\nGoodput 990.5\nLost 3 responses\n>>> 12.5 40\nLast >>> 30 90.5\nRetries >>> 1"
+        );
+        assert!(streaming
+            .to_json()
+            .contains("\"last_median\":30,\"last_p99\":90.5"));
+        assert_eq!(
+            streaming.to_csv(),
+            "1000.5,2000,3,12.5,40,us,30,90.5,1,990.5"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+
+#[cfg(feature = "simd-sort")]
+use crumsort::ParCrumSort;
+
+/// Sort the latency samples in increasing order. The order of equal samples doesn't matter, so
+/// the unstable sort is used; it doesn't allocate, and is faster than `sort()`.
+#[cfg(not(feature = "simd-sort"))]
+pub fn sort_latencies(latencies: &mut [u64]) {
+    latencies.sort_unstable();
+}
+
+/// Sort the latency samples in increasing order with crumsort, on all the cores; faster than the
+/// std sorts on the tens of millions of samples of the long runs.
+#[cfg(feature = "simd-sort")]
+pub fn sort_latencies(latencies: &mut [u64]) {
+    latencies.par_crumsort();
+}
+
+/// Writes the raw latency samples, in cycles, to a binary file as little-endian u64s.
+pub struct LatencyWriter {
+    writer: BufWriter<File>,
+}
+
+impl LatencyWriter {
+    pub fn create(path: &Path) -> io::Result<LatencyWriter> {
+        Ok(LatencyWriter {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Append a latency sample to the file.
+    pub fn write(&mut self, latency: u64) -> io::Result<()> {
+        self.writer.write_all(&latency.to_le_bytes())
+    }
+
+    /// Flush the buffered samples to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Read back the latency samples written by a `LatencyWriter`.
+///
+/// # Arguments
+/// *`path`: The file with the raw latency samples.
+///
+/// # Return
+/// The latency samples in cycles, in the order they were written.
+pub fn read_latencies(path: &Path) -> io::Result<Vec<u64>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut latencies = Vec::new();
+    let mut buf = [0; 8];
+    loop {
+        match reader.read_exact(&mut buf) {
+            Ok(()) => latencies.push(u64::from_le_bytes(buf)),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(latencies),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Read the raw latency files written on several client machines and merge their samples.
+///
+/// # Arguments
+/// *`paths`: The files with the raw latency samples, one per client machine.
+///
+/// # Return
+/// All the latency samples in cycles, sorted in increasing order.
+pub fn merge_latencies<P: AsRef<Path>>(paths: &[P]) -> io::Result<Vec<u64>> {
+    let mut latencies = Vec::new();
+    for path in paths.iter() {
+        latencies.extend(read_latencies(path.as_ref())?);
+    }
+    sort_latencies(&mut latencies);
+    Ok(latencies)
+}
+
+/// The global percentiles of the latencies merged from several client machines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergedPercentiles {
+    // The number of samples merged.
+    pub count: usize,
+
+    // The median latency.
+    pub p50: u64,
+
+    // The 99th percentile latency.
+    pub p99: u64,
+
+    // The 99.9th percentile latency.
+    pub p999: u64,
+}
+
+impl MergedPercentiles {
+    /// Pick the percentiles from sorted latencies; the samples are indexed the same way as for the
+    /// tail latency of a single run.
+    ///
+    /// # Arguments
+    /// *`sorted`: The latency samples, sorted in increasing order.
+    ///
+    /// # Return
+    /// The percentiles, or None if there are no samples.
+    pub fn from_sorted(sorted: &[u64]) -> Option<MergedPercentiles> {
+        let n = sorted.len();
+        if n == 0 {
+            return None;
+        }
+        Some(MergedPercentiles {
+            count: n,
+            p50: sorted[n / 2],
+            p99: sorted[(n * 99) / 100],
+            p999: sorted[(n * 999) / 1000],
+        })
+    }
+}
+
+/// Keeps a uniform random sample of at most `capacity` latencies, however many are added; used to
+/// bound the memory of long runs. Implements reservoir sampling (Algorithm R).
+pub struct Reservoir {
+    // The maximum number of samples kept.
+    capacity: usize,
+
+    // The number of samples added so far.
+    seen: u64,
+
+    // The samples kept so far.
+    samples: Vec<u64>,
+
+    // Random number generator to pick the samples to replace.
+    rng: ThreadRng,
+}
+
+impl Reservoir {
+    pub fn new(capacity: usize) -> Reservoir {
+        Reservoir {
+            capacity: capacity,
+            seen: 0,
+            samples: Vec::with_capacity(capacity),
+            rng: thread_rng(),
+        }
+    }
+
+    /// Add a sample; once the reservoir is full, it replaces a random sample with probability
+    /// `capacity / seen`.
+    pub fn add(&mut self, sample: u64) {
+        self.seen += 1;
+        if self.samples.len() < self.capacity {
+            self.samples.push(sample);
+            return;
+        }
+
+        let index = self.rng.gen_range(0, self.seen);
+        if index < self.capacity as u64 {
+            self.samples[index as usize] = sample;
+        }
+    }
+
+    /// Return the number of samples added so far, including the ones which were dropped.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Consume the reservoir and return the samples kept.
+    pub fn into_samples(self) -> Vec<u64> {
+        self.samples
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::time::Instant;
+
+    #[test]
+    fn test_sort_latencies() {
+        let mut rng = thread_rng();
+        let mut latencies: Vec<u64> = (0..100000).map(|_| rng.gen_range(0, 1000)).collect();
+        latencies.extend(vec![std::u64::MAX, 0, 0]);
+        let mut expected = latencies.clone();
+        expected.sort();
+        sort_latencies(&mut latencies);
+        assert_eq!(latencies, expected);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_sort_latencies() {
+        // Run with and without `--features simd-sort` to compare; `cargo test --release --
+        // --ignored --nocapture`.
+        let mut rng = thread_rng();
+        let latencies: Vec<u64> = (0..10000000).map(|_| rng.gen::<u64>() >> 32).collect();
+        let mut sorted = latencies.clone();
+        let start = Instant::now();
+        sort_latencies(&mut sorted);
+        println!("sort_latencies: {:?}", start.elapsed());
+
+        let mut expected = latencies;
+        let start = Instant::now();
+        expected.sort();
+        println!("sort: {:?}", start.elapsed());
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_write_read_latencies() {
+        let path = env::temp_dir().join("client_test_latencies.bin");
+        let latencies = vec![0, 1, 4500, 123456789, std::u64::MAX];
+
+        let mut writer = LatencyWriter::create(&path).unwrap();
+        for latency in latencies.iter() {
+            writer.write(*latency).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            8 * latencies.len() as u64
+        );
+        assert_eq!(read_latencies(&path).unwrap(), latencies);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_latencies() {
+        let first = env::temp_dir().join("client_test_merge_first.bin");
+        let second = env::temp_dir().join("client_test_merge_second.bin");
+        for (path, latencies) in [(&first, 1..=600), (&second, 601..=1000)].iter().cloned() {
+            let mut writer = LatencyWriter::create(path).unwrap();
+            for latency in latencies.rev() {
+                writer.write(latency).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+
+        let merged = merge_latencies(&[&first, &second]).unwrap();
+        assert_eq!(merged, (1..=1000).collect::<Vec<u64>>());
+
+        let percentiles = MergedPercentiles::from_sorted(&merged).unwrap();
+        assert_eq!(percentiles.count, 1000);
+        assert_eq!(percentiles.p50, 501);
+        assert_eq!(percentiles.p99, 991);
+        assert_eq!(percentiles.p999, 1000);
+        assert_eq!(MergedPercentiles::from_sorted(&[]), None);
+
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn test_reservoir() {
+        let capacity = 10000;
+        let count = 1000000;
+        let mut reservoir = Reservoir::new(capacity);
+        for sample in 0..count {
+            reservoir.add(sample);
+            assert!(reservoir.samples.len() <= capacity);
+        }
+        assert_eq!(reservoir.seen(), count);
+
+        // Each decile of the samples added should hold about a tenth of the reservoir.
+        let samples = reservoir.into_samples();
+        assert_eq!(samples.len(), capacity);
+        let mut deciles = [0; 10];
+        for sample in samples {
+            deciles[(sample * 10 / count) as usize] += 1;
+        }
+        for decile in deciles.iter() {
+            assert!((*decile as i64 - 1000).abs() < 200, "deciles {:?}", deciles);
+        }
+    }
+}
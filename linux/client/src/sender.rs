@@ -0,0 +1,908 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::ClientConfig;
+use super::cycles;
+use super::packet::ResponsePacket;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::prelude::*;
+use rand::rngs::ThreadRng;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+// The size of a per-tenant ACK on the control socket; the tenant's port, then the number of its
+// requests answered. The ACKs for all the tenants are 8 bytes; the number of requests answered.
+pub const TENANT_ACK_SIZE: usize = 10;
+
+/// Sends the requests to the tenants on the server at the configured rate, or in bursts or through
+/// a token bucket, and optionally holds them back until the receiver on the same socket ACKs
+/// enough responses; see `Receiver`.
+pub struct Sender {
+    // Socket to send the packets.
+    socket: Arc<UdpSocket>,
+
+    // The server ip-address.
+    server_ip: String,
+
+    // Total number of requests to be sent out.
+    requests: u64,
+
+    // Number of requests that have been sent out so far.
+    sent: u64,
+
+    // The inverse of the rate at which requests are to be generated. Basically, the time interval
+    // between two request generations in cycles.
+    rate_inv: u64,
+
+    // The time stamp at which the workload started generating requests in cycles.
+    start: u64,
+
+    // The time stamp at which the next request must be issued in cycles.
+    next: u64,
+
+    // The tenant random number generator.
+    tenant_rng: Box<Uniform<u16>>,
+
+    // Random number generator.
+    rng: Box<ThreadRng>,
+
+    // Number of requests which couldn't be sent out due to a socket error.
+    dropped: u64,
+
+    // Per-tenant send summary; printed at the end of the run if `verbose` is set.
+    tenant_summary: TenantSummary,
+
+    // If true, print the per-tenant send summary once all the requests are sent.
+    verbose: bool,
+
+    // If set, the gaps between consecutive sends are recorded to check the pacing accuracy.
+    gaps: Option<GapHistogram>,
+
+    // If set, the sender pauses when too many requests are outstanding.
+    flow_control: Option<FlowControl>,
+
+    // The duration in cycles over which the send rate ramps up from zero to the full rate.
+    ramp_up: u64,
+
+    // The version of the packet format written in each request.
+    proto_version: u8,
+
+    // The number of responses the server should send back for each request.
+    responses_per_request: u8,
+
+    // If set, the requests are sent in bursts instead of at `rate_inv`.
+    burst: Option<BurstPacer>,
+
+    // If set, the requests are paced by the token bucket instead of at `rate_inv`.
+    bucket: Option<TokenBucket>,
+
+    // If set, a copy of each request is sent to a secondary server.
+    mirror: Option<MirrorSender>,
+
+    // True once the sender reached the full rate.
+    ramped_up: bool,
+
+    // The number of requests sent out so far, shared with the main thread to check the delivery.
+    shared_sent: Arc<AtomicU64>,
+}
+
+/// Return the offset in cycles from the start at which a request must be sent, when the send rate
+/// ramps up linearly from zero to the full rate over `ramp_up` cycles. During the ramp-up the
+/// gap between the sends is `rate_inv * ramp_up / elapsed`, so the first `ramp_up / (2 * rate_inv)`
+/// requests are spread over the ramp-up, and the rest are sent every `rate_inv` cycles after it.
+///
+/// # Arguments
+/// *`sent`: Number of requests sent out so far.
+/// *`rate_inv`: The gap between two sends in cycles at the full rate.
+/// *`ramp_up`: The duration of the ramp-up in cycles.
+fn send_offset(sent: u64, rate_inv: u64, ramp_up: u64) -> u64 {
+    let ramp_sends = ramp_up / (2 * rate_inv);
+    if sent < ramp_sends {
+        // In f64; the product is about ramp_up² near the end of the ramp-up, and overflows a u64
+        // for a ramp-up over a second or so.
+        (2.0 * ramp_up as f64 * rate_inv as f64 * sent as f64).sqrt() as u64
+    } else {
+        ramp_up + (sent - ramp_sends) * rate_inv
+    }
+}
+
+/// The clock used to pause the sender between bursts; the TSC in the client, and a simulated clock
+/// in the tests.
+trait Clock {
+    /// Wait for the given number of cycles.
+    fn sleep_for(&self, cycles: u64);
+}
+
+/// The clock based on the rdtsc instruction.
+struct TscClock;
+
+impl Clock for TscClock {
+    fn sleep_for(&self, cycles: u64) {
+        cycles::sleep_for(cycles)
+    }
+}
+
+/// Paces the sender in bursts; `burst_size` requests back to back, then a pause of `gap` cycles.
+struct BurstPacer {
+    // The number of requests in a burst.
+    burst_size: u64,
+
+    // The pause between two bursts in cycles.
+    gap: u64,
+
+    // The number of requests sent so far in the current burst.
+    in_burst: u64,
+}
+
+impl BurstPacer {
+    fn new(burst_size: u64, gap: u64) -> BurstPacer {
+        BurstPacer {
+            burst_size: burst_size,
+            gap: gap,
+            in_burst: 0,
+        }
+    }
+
+    /// Record a request sent; pauses on `clock` once the current burst is complete.
+    fn after_send<C: Clock>(&mut self, clock: &C) {
+        self.in_burst += 1;
+        if self.in_burst == self.burst_size {
+            clock.sleep_for(self.gap);
+            self.in_burst = 0;
+        }
+    }
+}
+
+/// Paces the sender with a token bucket; each request takes a token, and the tokens are refilled
+/// at a fixed rate up to the capacity of the bucket.
+struct TokenBucket {
+    // The time in cycles to refill one token.
+    refill_cycles: f64,
+
+    // The maximum number of tokens in the bucket.
+    capacity: f64,
+
+    // The number of tokens in the bucket at `last`.
+    tokens: f64,
+
+    // The time stamp in cycles at which the tokens were last refilled.
+    last: u64,
+}
+
+impl TokenBucket {
+    /// Create a full bucket.
+    ///
+    /// # Arguments
+    /// *`rate`: The number of tokens refilled per second.
+    /// *`capacity`: The maximum number of tokens in the bucket; at least 1.
+    /// *`now`: The current time stamp in cycles.
+    fn new(rate: u64, capacity: u64, now: u64) -> TokenBucket {
+        let capacity = std::cmp::max(capacity, 1) as f64;
+        TokenBucket {
+            refill_cycles: cycles::cycles_per_second() as f64 / rate as f64,
+            capacity: capacity,
+            tokens: capacity,
+            last: now,
+        }
+    }
+
+    /// Take a token if there is one, after refilling the tokens for the time elapsed.
+    ///
+    /// # Arguments
+    /// *`now`: The current time stamp in cycles.
+    ///
+    /// # Return
+    /// True if a token was taken, and a request can be sent.
+    fn try_take(&mut self, now: u64) -> bool {
+        let elapsed = now.saturating_sub(self.last);
+        self.tokens = (self.tokens + elapsed as f64 / self.refill_cycles).min(self.capacity);
+        self.last = std::cmp::max(self.last, now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Sends a copy of each request to a secondary server, to compare two server configurations on
+/// identical request streams; the "shadow traffic" pattern. Both servers answer on the client's
+/// socket, and the receiver pairs their responses by the sequence number; see `MirrorPairs`.
+struct MirrorSender {
+    // The IP address of the secondary server; the copy goes to the same tenant port on it.
+    secondary_ip: IpAddr,
+}
+
+impl MirrorSender {
+    fn new(secondary_ip: IpAddr) -> MirrorSender {
+        MirrorSender {
+            secondary_ip: secondary_ip,
+        }
+    }
+
+    /// Send the copy of a request to the secondary server.
+    ///
+    /// # Arguments
+    /// *`socket`: The socket the request was sent on; the responses come back to it.
+    /// *`buf`: The request, as sent to the primary server.
+    /// *`tenant`: The tenant's port the request was sent to.
+    fn send(&self, socket: &UdpSocket, buf: &[u8], tenant: u16) -> std::io::Result<usize> {
+        socket.send_to(buf, SocketAddr::new(self.secondary_ip, tenant))
+    }
+}
+
+/// Back-pressure for the sender, based on the number of responses ACKed by the receiver.
+struct FlowControl {
+    // Non-blocking control socket on which the receiver ACKs the number of responses received.
+    socket: UdpSocket,
+
+    // The maximum number of requests which can be outstanding at any time; zero for no limit.
+    max_in_flight: u64,
+
+    // The number of responses ACKed by the receiver so far.
+    acked: u64,
+
+    // The maximum number of requests of a tenant which can be outstanding at any time; zero for
+    // no limit.
+    per_tenant_inflight: u64,
+
+    // The number of requests sent to each tenant, by the tenant's port.
+    tenant_sent: HashMap<u16, u64>,
+
+    // The number of requests of each tenant ACKed by the receiver, by the tenant's port.
+    tenant_acked: HashMap<u16, u64>,
+}
+
+impl FlowControl {
+    fn new(socket: UdpSocket, max_in_flight: u64, per_tenant_inflight: u64) -> FlowControl {
+        socket
+            .set_nonblocking(true)
+            .expect("couldn't make the control socket non-blocking");
+        FlowControl {
+            socket: socket,
+            max_in_flight: max_in_flight,
+            acked: 0,
+            per_tenant_inflight: per_tenant_inflight,
+            tenant_sent: HashMap::new(),
+            tenant_acked: HashMap::new(),
+        }
+    }
+
+    /// Read all the pending ACKs from the control socket.
+    fn poll(&mut self) {
+        let mut buf = [0; TENANT_ACK_SIZE];
+        while let Ok(len) = self.socket.recv(&mut buf) {
+            let mut count = [0; 8];
+            if len == TENANT_ACK_SIZE {
+                let tenant = u16::from_le_bytes([buf[0], buf[1]]);
+                count.copy_from_slice(&buf[2..]);
+                let acked = self.tenant_acked.entry(tenant).or_insert(0);
+                *acked = std::cmp::max(*acked, u64::from_le_bytes(count));
+            } else {
+                count.copy_from_slice(&buf[..8]);
+                self.acked = std::cmp::max(self.acked, u64::from_le_bytes(count));
+            }
+        }
+    }
+
+    /// Return true if another request can be sent, given `sent` requests were sent so far.
+    fn can_send(&self, sent: u64) -> bool {
+        self.max_in_flight == 0 || sent - std::cmp::min(self.acked, sent) < self.max_in_flight
+    }
+
+    /// Return the number of requests of a tenant which are still outstanding.
+    ///
+    /// # Arguments
+    /// *`tenant`: The port of the tenant.
+    fn tenant_outstanding(&self, tenant: u16) -> u64 {
+        let sent = *self.tenant_sent.get(&tenant).unwrap_or(&0);
+        let acked = *self.tenant_acked.get(&tenant).unwrap_or(&0);
+        sent - std::cmp::min(acked, sent)
+    }
+
+    /// Return true if another request can be sent to a tenant.
+    ///
+    /// # Arguments
+    /// *`tenant`: The port of the tenant.
+    fn can_send_tenant(&self, tenant: u16) -> bool {
+        self.per_tenant_inflight == 0 || self.tenant_outstanding(tenant) < self.per_tenant_inflight
+    }
+
+    /// Count a request sent to a tenant.
+    ///
+    /// # Arguments
+    /// *`tenant`: The port of the tenant.
+    fn record_send(&mut self, tenant: u16) {
+        *self.tenant_sent.entry(tenant).or_insert(0) += 1;
+    }
+}
+
+/// Summary of a send run; returned by `Sender::stats()`.
+#[derive(Debug, Clone, Copy)]
+pub struct SenderStats {
+    // Number of requests sent out so far.
+    pub sent: u64,
+
+    // The time elapsed in cycles since the sender started.
+    pub elapsed_cycles: u64,
+
+    // The observed request rate in requests per second.
+    pub achieved_rate_rps: f64,
+
+    // Number of requests dropped due to a socket error.
+    pub dropped_sends: u64,
+}
+
+/// The first and last send timestamp, along with the number of sends, for a tenant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TenantSends {
+    // Time stamp in cycles at which the first request was sent to this tenant.
+    first: u64,
+
+    // Time stamp in cycles at which the last request was sent to this tenant.
+    last: u64,
+
+    // Number of requests sent to this tenant so far.
+    count: u64,
+}
+
+/// Keeps track of the requests sent to each tenant; used to debug uneven tenant coverage.
+struct TenantSummary {
+    tenants: HashMap<u16, TenantSends>,
+}
+
+impl TenantSummary {
+    fn new() -> TenantSummary {
+        TenantSummary {
+            tenants: HashMap::new(),
+        }
+    }
+
+    /// Record a request sent to `tenant` at time stamp `curr`.
+    fn record(&mut self, tenant: u16, curr: u64) {
+        let entry = self.tenants.entry(tenant).or_insert(TenantSends {
+            first: curr,
+            last: curr,
+            count: 0,
+        });
+        entry.last = curr;
+        entry.count += 1;
+    }
+
+    /// Return the summary for `tenant`, if any request was sent to it.
+    #[cfg(test)]
+    fn get(&self, tenant: u16) -> Option<&TenantSends> {
+        self.tenants.get(&tenant)
+    }
+
+    /// Print the summary for each tenant, sorted by the tenant id.
+    fn print(&self, start: u64) {
+        let mut tenants: Vec<&u16> = self.tenants.keys().collect();
+        tenants.sort();
+        for tenant in tenants {
+            let sends = &self.tenants[tenant];
+            diag!(
+                "Tenant {} Sent {} First(sec) {:.6} Last(sec) {:.6}",
+                tenant,
+                sends.count,
+                cycles::to_seconds(sends.first - start),
+                cycles::to_seconds(sends.last - start)
+            );
+        }
+    }
+}
+
+// Upper bounds for the histogram buckets, as a fraction of the expected gap `rate_inv`.
+const GAP_BUCKETS: [f64; 5] = [0.5, 0.9, 1.1, 1.5, 2.0];
+
+/// Histogram of the gaps between consecutive sends; used to diagnose the sender pacing accuracy.
+struct GapHistogram {
+    // The expected gap between two sends in cycles.
+    rate_inv: u64,
+
+    // Time stamp in cycles of the previous send.
+    prev: Option<u64>,
+
+    // Number of gaps recorded so far.
+    count: u64,
+
+    // Sum of all the gaps in cycles.
+    sum: u64,
+
+    // The smallest gap in cycles.
+    min: u64,
+
+    // The largest gap in cycles.
+    max: u64,
+
+    // Number of gaps in each bucket; the last bucket holds the gaps above `2 * rate_inv`.
+    buckets: [u64; GAP_BUCKETS.len() + 1],
+}
+
+impl GapHistogram {
+    fn new(rate_inv: u64) -> GapHistogram {
+        GapHistogram {
+            rate_inv: rate_inv,
+            prev: None,
+            count: 0,
+            sum: 0,
+            min: std::u64::MAX,
+            max: 0,
+            buckets: [0; GAP_BUCKETS.len() + 1],
+        }
+    }
+
+    /// Record a send at time stamp `curr`.
+    fn record(&mut self, curr: u64) {
+        if let Some(prev) = self.prev {
+            let gap = curr - prev;
+            let ratio = gap as f64 / self.rate_inv as f64;
+            let bucket = GAP_BUCKETS.iter().filter(|bound| **bound <= ratio).count();
+            self.buckets[bucket] += 1;
+            self.count += 1;
+            self.sum += gap;
+            self.min = std::cmp::min(self.min, gap);
+            self.max = std::cmp::max(self.max, gap);
+        }
+        self.prev = Some(curr);
+    }
+
+    /// Return the mean gap in cycles.
+    fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.count as f64
+    }
+
+    fn print(&self) {
+        diag!(
+            "Gaps: Expected(cycles) {} Mean(cycles) {:.2} Min(cycles) {} Max(cycles) {}",
+            self.rate_inv,
+            self.mean(),
+            self.min,
+            self.max
+        );
+        let mut low = 0.0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            let percent = *count as f64 * 100.0 / std::cmp::max(self.count, 1) as f64;
+            match GAP_BUCKETS.get(i) {
+                Some(high) => diag!("Gaps: [{:.1}x, {:.1}x) {:.2}%", low, high, percent),
+                None => diag!("Gaps: [{:.1}x, inf) {:.2}%", low, percent),
+            }
+            low = GAP_BUCKETS.get(i).cloned().unwrap_or(low);
+        }
+    }
+}
+
+impl Sender {
+    /// Create a sender for the requests in `config`; the clock for the send rate starts here.
+    ///
+    /// # Arguments
+    /// *`socket`: The socket to send the requests on; the responses come back on it too.
+    /// *`config`: The client configuration; the server, the rate and the number of requests.
+    /// *`control`: The socket on which the receiver ACKs the responses, for the flow control with
+    ///             `max_in_flight` or `per_tenant_inflight`; None sends at the rate regardless.
+    /// *`shared_sent`: Updated with the number of requests sent out, e.g. for another thread to
+    ///                 check the delivery once the receiver is done.
+    pub fn new(
+        socket: Arc<UdpSocket>,
+        config: &ClientConfig,
+        control: Option<UdpSocket>,
+        shared_sent: Arc<AtomicU64>,
+    ) -> Sender {
+        let rate_inv = cycles::cycles_per_second() / config.req_rate as u64;
+        let mut gaps = None;
+        if config.record_gaps {
+            gaps = Some(GapHistogram::new(rate_inv));
+        }
+        let mut burst = None;
+        if config.burst_size != 0 {
+            let gap = (config.burst_gap_ms * cycles::cycles_per_second() as f64 / 1000.0) as u64;
+            burst = Some(BurstPacer::new(config.burst_size, gap));
+        }
+        let mut bucket = None;
+        if config.bucket_rate != 0 {
+            let now = cycles::rdtsc();
+            bucket = Some(TokenBucket::new(
+                config.bucket_rate,
+                config.bucket_burst,
+                now,
+            ));
+        }
+
+        Sender {
+            socket: socket,
+            server_ip: config.server_ip.clone(),
+            requests: config.num_reqs,
+            sent: 0,
+            rate_inv: rate_inv,
+            start: cycles::rdtsc(),
+            next: 0,
+            tenant_rng: Box::new(Uniform::from(1024..(1024 + config.num_tenants as u16))),
+            rng: Box::new(thread_rng()),
+            dropped: 0,
+            tenant_summary: TenantSummary::new(),
+            verbose: config.verbose,
+            gaps: gaps,
+            flow_control: control.map(|socket| {
+                FlowControl::new(socket, config.max_in_flight, config.per_tenant_inflight)
+            }),
+            ramp_up: config.ramp_up_duration_ms * cycles::cycles_per_second() / 1000,
+            proto_version: config.proto_version,
+            responses_per_request: config.responses_per_request,
+            ramped_up: config.ramp_up_duration_ms == 0,
+            burst: burst,
+            bucket: bucket,
+            mirror: config
+                .mirror_server_ip
+                .as_ref()
+                .map(|ip| MirrorSender::new(ip.parse().unwrap())),
+            shared_sent: shared_sent,
+        }
+    }
+
+    /// Return the statistics for the requests sent out so far.
+    pub fn stats(&self) -> SenderStats {
+        let elapsed_cycles = cycles::rdtsc() - self.start;
+        SenderStats {
+            sent: self.sent,
+            elapsed_cycles: elapsed_cycles,
+            achieved_rate_rps: self.sent as f64 / cycles::to_seconds(elapsed_cycles),
+            dropped_sends: self.dropped,
+        }
+    }
+
+    /// Return true once all the requests have been sent.
+    pub fn done(&self) -> bool {
+        self.requests <= self.sent
+    }
+
+    /// Send the next request if it is due; returns at once otherwise.
+    pub fn poll_send(&mut self) {
+        let curr: u64 = cycles::rdtsc();
+        if self.bucket.is_some() || self.burst.is_some() || curr >= self.next || self.next == 0 {
+            // Pick a random port to send the request to a random tenant.
+            let tenant = self.tenant_rng.sample(&mut *self.rng);
+
+            // Hold the request back until the receiver ACKs enough responses; the tenant is
+            // picked again on the next attempt.
+            if let Some(ref mut flow_control) = self.flow_control {
+                flow_control.poll();
+                if !flow_control.can_send(self.sent) || !flow_control.can_send_tenant(tenant) {
+                    return;
+                }
+            }
+            if let Some(ref mut bucket) = self.bucket {
+                if !bucket.try_take(curr) {
+                    return;
+                }
+            }
+
+            // The sequence number wraps around after 2^32 requests; see Receiver::track_seq().
+            let mut packet = ResponsePacket::new(
+                self.proto_version,
+                curr,
+                self.rng.gen(),
+                self.responses_per_request,
+            );
+            packet.seq = self.sent as u32;
+            let buf = packet.to_bytes();
+
+            let ip_address = self.server_ip.parse().unwrap();
+            let addr = SocketAddr::new(ip_address, tenant);
+            if let Err(e) = self.socket.send_to(&buf, addr) {
+                diag!("send_to function failed: {:?}", e);
+                self.dropped += 1;
+            }
+            if let Some(ref mirror) = self.mirror {
+                if let Err(e) = mirror.send(&self.socket, &buf, tenant) {
+                    diag!("send_to function failed for the mirror: {:?}", e);
+                    self.dropped += 1;
+                }
+            }
+            self.tenant_summary.record(tenant, curr);
+            if let Some(ref mut flow_control) = self.flow_control {
+                flow_control.record_send(tenant);
+            }
+            if let Some(ref mut gaps) = self.gaps {
+                gaps.record(curr);
+            }
+
+            // Update the time stamp at which the next request should be generated, assuming that
+            // the first request was sent out at self.start.
+            self.sent += 1;
+            self.shared_sent.store(self.sent, Ordering::Relaxed);
+            let offset = send_offset(self.sent, self.rate_inv, self.ramp_up);
+            self.next = self.start + offset;
+            if !self.ramped_up && offset >= self.ramp_up {
+                diag!("[RAMP_UP COMPLETE] after {} requests", self.sent);
+                self.ramped_up = true;
+            }
+            if let Some(ref mut burst) = self.burst {
+                burst.after_send(&TscClock);
+            }
+        }
+    }
+
+    /// Send all the requests, busy-waiting between them; returns once the last one is sent. With
+    /// the flow control, this blocks until the receiver ACKs enough responses.
+    pub fn send(&mut self) {
+        while !self.done() {
+            self.poll_send();
+        }
+    }
+
+    /// Print the histogram of the gaps between the sends with `record_gaps`, and the per-tenant
+    /// send summary with `verbose`.
+    pub fn print_summary(&self) {
+        if let Some(ref gaps) = self.gaps {
+            gaps.print();
+        }
+        if self.verbose {
+            self.tenant_summary.print(self.start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::packet::PROTO_VERSION;
+    use super::super::receiver::Receiver;
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_send_offset_ramp_up() {
+        let rate_inv = 1000;
+        let ramp_up = 1000000;
+
+        // Without a ramp-up, the requests are sent at the full rate from the start.
+        assert_eq!(send_offset(10, rate_inv, 0), 10 * rate_inv);
+
+        // The gaps shrink during the ramp-up, and reach the full rate at its end.
+        let ramp_sends = ramp_up / (2 * rate_inv);
+        let mut prev_gap = std::u64::MAX;
+        for sent in 1..ramp_sends {
+            let gap =
+                send_offset(sent + 1, rate_inv, ramp_up) - send_offset(sent, rate_inv, ramp_up);
+            // Allow a cycle for truncating the offsets.
+            assert!(gap <= prev_gap.saturating_add(1));
+            assert!(gap >= rate_inv);
+            prev_gap = gap;
+        }
+        assert_eq!(send_offset(ramp_sends, rate_inv, ramp_up), ramp_up);
+        assert_eq!(
+            send_offset(ramp_sends + 5, rate_inv, ramp_up),
+            ramp_up + 5 * rate_inv
+        );
+    }
+
+    #[test]
+    fn test_send_offset_long_ramp_up() {
+        // A 5 second ramp-up on a 3 GHz TSC, up to 100K requests per second.
+        let rate_inv = 30000;
+        let ramp_up = 15_000_000_000;
+        let ramp_sends = ramp_up / (2 * rate_inv);
+
+        let mut prev = 0;
+        for sent in 1..ramp_sends {
+            let offset = send_offset(sent, rate_inv, ramp_up);
+            assert!(offset > prev && offset < ramp_up);
+            prev = offset;
+        }
+        assert!(ramp_up - prev <= 2 * rate_inv);
+        assert_eq!(send_offset(ramp_sends, rate_inv, ramp_up), ramp_up);
+    }
+
+    /// A clock which only moves when the test advances it.
+    struct SimClock {
+        now: std::cell::Cell<u64>,
+    }
+
+    impl SimClock {
+        fn now(&self) -> u64 {
+            self.now.get()
+        }
+    }
+
+    impl Clock for SimClock {
+        fn sleep_for(&self, cycles: u64) {
+            self.now.set(self.now.get() + cycles);
+        }
+    }
+
+    #[test]
+    fn test_burst_timing() {
+        let clock = SimClock {
+            now: std::cell::Cell::new(0),
+        };
+        let mut burst = BurstPacer::new(4, 1000);
+
+        // Each send takes 10 cycles; the bursts are 4 sends back to back, 1000 cycles apart.
+        let mut sends = Vec::new();
+        for _ in 0..8 {
+            sends.push(clock.now());
+            clock.sleep_for(10);
+            burst.after_send(&clock);
+        }
+        assert_eq!(sends, vec![0, 10, 20, 30, 1040, 1050, 1060, 1070]);
+        assert_eq!(clock.now(), 2080);
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        // A token every 1000 cycles, and up to 10 requests back to back.
+        let rate = cycles::cycles_per_second() / 1000;
+        let mut bucket = TokenBucket::new(rate, 10, 0);
+
+        // The sender polls every 10 cycles, and sends back to back while it gets tokens; the full
+        // bucket lets a burst of 10 out at once.
+        let mut sends = Vec::new();
+        for now in (0..1000000).step_by(10) {
+            while bucket.try_take(now) {
+                sends.push(now);
+            }
+        }
+        assert_eq!(sends.iter().filter(|now| **now == 0).count(), 10);
+
+        // In the long run, the rate is the refill rate.
+        assert!((1000..=1010).contains(&sends.len()));
+        let steady = &sends[20..];
+        for gap in steady.windows(2).map(|pair| pair[1] - pair[0]) {
+            assert!((990..=1010).contains(&gap));
+        }
+
+        // After an idle period, the burst is capped at the capacity of the bucket.
+        let idle = 10000000;
+        let burst = (0..20).filter(|_| bucket.try_take(idle)).count();
+        assert_eq!(burst, 10);
+    }
+
+    #[test]
+    fn test_flow_control() {
+        let control = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ack_addr = control.local_addr().unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut flow_control = FlowControl::new(control, 4, 0);
+
+        // The receiver ACKs all but the last request whenever the sender is held back.
+        let mut sent = 0;
+        while sent < 100 {
+            flow_control.poll();
+            if flow_control.can_send(sent) {
+                sent += 1;
+            } else {
+                receiver
+                    .send_to(&(sent - 1).to_le_bytes(), ack_addr)
+                    .unwrap();
+                thread::sleep(std::time::Duration::from_millis(1));
+            }
+            assert!(sent - flow_control.acked <= 4);
+        }
+    }
+
+    #[test]
+    fn test_per_tenant_inflight() {
+        let mut config = ClientConfig::default();
+        config.num_resps = 1000;
+        config.proto_version = PROTO_VERSION;
+        config.per_tenant_inflight = 2;
+        let control = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ack_addr = control.local_addr().unwrap();
+        let mut flow_control = FlowControl::new(control, 0, config.per_tenant_inflight);
+
+        // The receiver ACKs the responses from each tenant's port separately.
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+        socket.set_nonblocking(true).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut receiver = Receiver::new(socket, &config, false, Some(ack_addr));
+        let tenants: Vec<UdpSocket> = (0..2)
+            .map(|_| UdpSocket::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let ports: Vec<u16> = tenants
+            .iter()
+            .map(|tenant| tenant.local_addr().unwrap().port())
+            .collect();
+
+        // The first tenant gets most of the requests; a tenant at its limit answers one request
+        // whenever the sender is held back.
+        let mut sent = 0;
+        let mut held_back = 0;
+        while sent < 100 {
+            let index = if sent % 4 == 3 { 1 } else { 0 };
+            flow_control.poll();
+            if flow_control.can_send_tenant(ports[index]) {
+                flow_control.record_send(ports[index]);
+                sent += 1;
+            } else {
+                // The sender is only held back once the tenant is at its limit.
+                assert_eq!(flow_control.tenant_outstanding(ports[index]), 2);
+                held_back += 1;
+                let buf = ResponsePacket::new(PROTO_VERSION, cycles::rdtsc(), 0, 1).to_bytes();
+                tenants[index].send_to(&buf, addr).unwrap();
+                thread::sleep(std::time::Duration::from_millis(1));
+                receiver.drain();
+            }
+            for port in ports.iter() {
+                assert!(flow_control.tenant_outstanding(*port) <= 2);
+            }
+        }
+        // All but the last 2 requests of each tenant were answered, one per hold back.
+        assert!(held_back >= 100 - 2 * 2);
+        assert_eq!(flow_control.tenant_sent[&ports[0]], 75);
+        assert_eq!(flow_control.tenant_sent[&ports[1]], 25);
+    }
+
+    #[test]
+    fn test_gap_histogram() {
+        let mut gaps = GapHistogram::new(100);
+        for curr in [0, 100, 200, 350, 360, 600].iter() {
+            gaps.record(*curr);
+        }
+
+        assert_eq!(gaps.count, 5);
+        assert_eq!(gaps.min, 10);
+        assert_eq!(gaps.max, 240);
+        assert_eq!(gaps.mean(), 120.0);
+        assert_eq!(gaps.buckets, [1, 0, 2, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_tenant_summary() {
+        let mut summary = TenantSummary::new();
+        let picks: [(u16, u64); 6] = [
+            (1024, 10),
+            (1025, 20),
+            (1024, 30),
+            (1026, 40),
+            (1024, 50),
+            (1025, 60),
+        ];
+        for (tenant, curr) in picks.iter() {
+            summary.record(*tenant, *curr);
+        }
+
+        assert_eq!(
+            summary.get(1024),
+            Some(&TenantSends {
+                first: 10,
+                last: 50,
+                count: 3
+            })
+        );
+        assert_eq!(
+            summary.get(1025),
+            Some(&TenantSends {
+                first: 20,
+                last: 60,
+                count: 2
+            })
+        );
+        assert_eq!(
+            summary.get(1026),
+            Some(&TenantSends {
+                first: 40,
+                last: 40,
+                count: 1
+            })
+        );
+        assert_eq!(summary.get(1027), None);
+    }
+}
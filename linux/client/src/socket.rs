@@ -0,0 +1,104 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::io;
+use std::mem::size_of;
+use std::net::UdpSocket;
+use std::os::unix::io::AsRawFd;
+
+fn setsockopt(socket: &UdpSocket, option: libc::c_int, value: usize) -> io::Result<()> {
+    let value = value as libc::c_int;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            &value as *const libc::c_int as *const libc::c_void,
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn getsockopt(socket: &UdpSocket, option: libc::c_int) -> io::Result<usize> {
+    let mut value: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value as usize)
+}
+
+/// Set the kernel send and receive buffer sizes for a socket.
+///
+/// # Arguments
+/// *`socket`: The socket to update.
+/// *`sndbuf`: The send buffer size in bytes; zero keeps the kernel default.
+/// *`rcvbuf`: The receive buffer size in bytes; zero keeps the kernel default.
+pub fn set_buffer_sizes(socket: &UdpSocket, sndbuf: usize, rcvbuf: usize) -> io::Result<()> {
+    if sndbuf != 0 {
+        setsockopt(socket, libc::SO_SNDBUF, sndbuf)?;
+    }
+    if rcvbuf != 0 {
+        setsockopt(socket, libc::SO_RCVBUF, rcvbuf)?;
+    }
+    Ok(())
+}
+
+/// Return the kernel send and receive buffer sizes for a socket.
+///
+/// # Return
+/// The send and receive buffer sizes in bytes. Linux reports twice the configured size, as it
+/// reserves the extra space for the bookkeeping overhead.
+pub fn buffer_sizes(socket: &UdpSocket) -> io::Result<(usize, usize)> {
+    Ok((
+        getsockopt(socket, libc::SO_SNDBUF)?,
+        getsockopt(socket, libc::SO_RCVBUF)?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buffer_sizes() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        set_buffer_sizes(&socket, 65536, 98304).unwrap();
+        let (sndbuf, rcvbuf) = buffer_sizes(&socket).unwrap();
+        assert!(sndbuf >= 65536);
+        assert!(rcvbuf >= 98304);
+    }
+
+    #[test]
+    fn test_default_buffer_sizes() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let before = buffer_sizes(&socket).unwrap();
+        set_buffer_sizes(&socket, 0, 0).unwrap();
+        assert_eq!(buffer_sizes(&socket).unwrap(), before);
+    }
+}
@@ -0,0 +1,107 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// The name of the StatsD histogram for the response latencies.
+pub const LATENCY_METRIC: &str = "sandstorm.latency_ns";
+
+/// Sends the latency samples to a StatsD server as histogram metrics, one UDP packet per sample;
+/// the sends are best effort, like StatsD itself.
+pub struct StatsdSink {
+    // The socket to send the metrics from.
+    socket: UdpSocket,
+
+    // The address of the StatsD server.
+    addr: SocketAddr,
+
+    // Only every this many samples is sent, to limit the network overhead.
+    every: u64,
+
+    // The number of samples recorded so far.
+    recorded: u64,
+}
+
+impl StatsdSink {
+    /// Create a sink for the StatsD server at `host:port`.
+    ///
+    /// # Arguments
+    /// *`host`: The host name or the IP address of the StatsD server.
+    /// *`port`: The UDP port of the StatsD server.
+    /// *`every`: Send only every this many samples; 1 sends all of them.
+    pub fn new(host: &str, port: u16, every: u64) -> io::Result<StatsdSink> {
+        let addr = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "couldn't resolve the StatsD host")
+        })?;
+        let local: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        Ok(StatsdSink {
+            socket: UdpSocket::bind(local)?,
+            addr: addr,
+            every: std::cmp::max(every, 1),
+            recorded: 0,
+        })
+    }
+
+    /// Record a latency sample; sent to the server if it is one of every `every` samples.
+    pub fn record(&mut self, latency_ns: u64) {
+        self.recorded += 1;
+        if self.recorded % self.every == 0 {
+            let _ = self
+                .socket
+                .send_to(format(latency_ns).as_bytes(), self.addr);
+        }
+    }
+}
+
+/// Return the StatsD histogram metric for a latency in nano-seconds.
+pub fn format(latency_ns: u64) -> String {
+    format!("{}:{}|h", LATENCY_METRIC, latency_ns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_statsd_packets() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        // Every 100th sample is sent; the 100th and the 200th.
+        let mut sink = StatsdSink::new("127.0.0.1", port, 100).unwrap();
+        for latency in 1..251 {
+            sink.record(latency);
+        }
+
+        let mut buf = [0; 64];
+        let mut packets = Vec::new();
+        while let Ok(amt) = server.recv(&mut buf) {
+            packets.push(String::from_utf8(buf[..amt].to_vec()).unwrap());
+        }
+        assert_eq!(
+            packets,
+            vec!["sandstorm.latency_ns:100|h", "sandstorm.latency_ns:200|h"]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Runs the client binary against a mock server on the loopback, which echoes back each request.
+
+use std::env;
+use std::fs;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// The client sends the requests for tenant `t` to port `1024 + t`; the config has one tenant.
+const TENANT_PORT: u16 = 1024;
+
+/// A UDP server which echoes back each request as soon as it arrives, like the server in
+/// linux/server without the tenant processes.
+struct MockServer {
+    // Set to stop the server thread.
+    stop: Arc<AtomicBool>,
+
+    // The server thread.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    fn start(port: u16) -> MockServer {
+        let socket = UdpSocket::bind(("127.0.0.1", port)).expect("couldn't bind the mock server");
+        socket
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            let mut buf = [0; 64];
+            while !stop_clone.load(Ordering::Relaxed) {
+                if let Ok((amt, src)) = socket.recv_from(&mut buf) {
+                    let _ = socket.send_to(&buf[..amt], src);
+                }
+            }
+        });
+        MockServer {
+            stop: stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Write a client.toml for a short run into a new directory, and return the directory.
+fn config_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("client_test_{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    let config = r#"
+client_ip = "127.0.0.1"
+server_ip = "127.0.0.1"
+num_tenants = 1
+num_reqs = 1000
+num_resps = 1000
+warmup_resps = 0
+burst_size = 0
+burst_gap_ms = 1.0
+bucket_rate = 0
+bucket_burst = 32
+single_thread = true
+subtract_loopback = false
+statsd_port = 8125
+req_rate = 10000
+verbose = false
+sndbuf = 0
+rcvbuf = 0
+latency_unit = "ns"
+output_format = "text"
+proto_version = 4
+responses_per_request = 1
+record_gaps = false
+max_in_flight = 0
+ack_interval = 64
+per_tenant_inflight = 0
+recv_timeout_ms = 20
+ramp_up_duration_ms = 0
+reservoir_size = 0
+"#;
+    fs::write(dir.join("client.toml"), config).unwrap();
+    dir
+}
+
+/// Run the client binary in `dir`, which reads the client.toml in there.
+fn run_client(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_client"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("couldn't run the client")
+}
+
+#[test]
+fn test_client_against_mock_server() {
+    let _server = MockServer::start(TENANT_PORT);
+    let dir = config_dir("mock_server");
+
+    // The single-threaded mode runs on any core layout. The text format has a throughput line for
+    // the receiver, and the latencies.
+    let output = run_client(&dir, &[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line.starts_with("Throughput ")));
+    assert!(stdout.lines().any(|line| line.starts_with(">>> ")));
+
+    // The JSON format only prints the stats on stdout, as one object.
+    let output = run_client(&dir, &["--output-format", "json"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().count() > 0);
+    assert!(stdout
+        .lines()
+        .all(|line| line.starts_with("{\"throughput\":")));
+    assert!(stdout.lines().any(|line| !line.contains("\"median\":null")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
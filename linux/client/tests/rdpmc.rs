@@ -0,0 +1,35 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+//! Reads the hardware performance counters. These tests need CR4.PCE set and the counters enabled,
+//! so they are ignored by default; run them with
+//! `echo 2 | sudo tee /sys/bus/event_source/devices/cpu/rdpmc` and
+//! `perf stat -e instructions -- cargo test --test rdpmc -- --ignored`.
+
+use client::cycles;
+
+#[test]
+#[ignore]
+#[cfg(target_arch = "x86_64")]
+fn test_rdpmc_instructions() {
+    let start = cycles::rdpmc_instructions();
+    let mut sum: u64 = 0;
+    for i in 0..100000 {
+        sum = sum.wrapping_add(i);
+    }
+    assert!(sum > 0);
+    let stop = cycles::rdpmc_instructions();
+    assert!(stop > start);
+}
@@ -24,13 +24,25 @@ pub fn echo(ip_address: IpAddr, port: u16) {
     let addr = SocketAddr::new(ip_address, port);
     let socket = UdpSocket::bind(addr).expect("couldn't bind to address");
     // Receives a single datagram message on the socket. If `buf` is too small to hold
-    // the message, it will be cut off.
-    let mut buf = [0; 8];
+    // the message, it will be cut off. The client packet is 23 bytes; version, timestamp, trace
+    // id, the number of responses to send back, the retry count and the sequence number. This
+    // server never retries, so the retry count is echoed back as is.
+    let mut buf = [0; 23];
     loop {
-        let (_amt, src) = socket
+        let (amt, src) = socket
             .recv_from(&mut buf)
             .expect("couldn't bind to address");
 
-        socket.send_to(&buf, &src).expect("couldn't bind to address");
+        // Streaming requests expect more than one echo; the older 17 byte packets expect one.
+        let responses = if amt > 17 {
+            std::cmp::max(buf[17], 1)
+        } else {
+            1
+        };
+        for _ in 0..responses {
+            socket
+                .send_to(&buf, &src)
+                .expect("couldn't bind to address");
+        }
     }
 }
@@ -0,0 +1,321 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::cycles;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::collections::{HashMap, VecDeque};
+
+// The priority of the tasks admitted within their tenant's reservation; the others get 0.
+const CONFORMING: u8 = 1;
+
+/// What the `AdmissionScheduler` does with the requests above their tenant's reservation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    // The excess requests wait in the best-effort run-queue.
+    Deprioritize,
+
+    // The excess requests are dropped on arrival.
+    Drop,
+}
+
+/// The token bucket for the reserved rate of one tenant.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reservation {
+    // The number of tokens added per cycle; the reserved rate.
+    rate: f64,
+
+    // The maximum number of tokens; the largest burst admitted at once.
+    capacity: f64,
+
+    // The number of tokens available.
+    tokens: f64,
+
+    // The time in cycles at which the tokens were last refilled.
+    last: u64,
+}
+
+impl Reservation {
+    /// Create a full token bucket.
+    ///
+    /// # Arguments
+    /// `rate`: The reserved rate in requests per second.
+    /// `burst`: The largest number of requests admitted at once; at least 1.
+    pub fn new(rate: f64, burst: u64) -> Reservation {
+        let capacity = std::cmp::max(burst, 1) as f64;
+        Reservation {
+            rate: rate / cycles::cycles_per_second() as f64,
+            capacity: capacity,
+            tokens: capacity,
+            last: 0,
+        }
+    }
+
+    /// Refill the tokens up to `now`, and take one if there is any.
+    ///
+    /// # Return
+    /// True if the request is within the reservation.
+    fn admit(&mut self, now: u64) -> bool {
+        if now > self.last {
+            let refill = (now - self.last) as f64 * self.rate;
+            self.tokens = (self.tokens + refill).min(self.capacity);
+            self.last = now;
+        }
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        false
+    }
+}
+
+/// Admission control over the tenants' reserved rates. The requests of a tenant within its
+/// reservation go to the fast run-queue; the excess requests, and all the requests of the tenants
+/// without a reservation, go to the best-effort run-queue, which only runs once the fast one is
+/// empty, or are dropped. Each run-queue is first-come first-served, and a preempted task goes
+/// back to the run-queue it was admitted to.
+#[derive(Serialize, Deserialize)]
+pub struct AdmissionScheduler {
+    // The token bucket for each tenant with a reservation.
+    reservations: HashMap<u16, Reservation>,
+
+    // What to do with the requests above the reservations.
+    overflow: Overflow,
+
+    // Task runqueue for the requests within the reservations.
+    fast_rq: VecDeque<Box<Request>>,
+
+    // Task runqueue for the excess requests.
+    best_effort_rq: VecDeque<Box<Request>>,
+
+    // The sequence number for the next task added to a runqueue.
+    next_seq: u64,
+
+    // The number of requests above the reservations; deprioritized or dropped.
+    pub excess: u64,
+
+    // The number of requests dropped on arrival.
+    pub dropped: u64,
+}
+
+impl AdmissionScheduler {
+    /// Create an admission controller.
+    ///
+    /// # Arguments
+    /// `reservations`: The token bucket for each tenant with a reservation.
+    /// `overflow`: What to do with the requests above the reservations.
+    pub fn new(reservations: HashMap<u16, Reservation>, overflow: Overflow) -> AdmissionScheduler {
+        AdmissionScheduler {
+            reservations: reservations,
+            overflow: overflow,
+            fast_rq: VecDeque::new(),
+            best_effort_rq: VecDeque::new(),
+            next_seq: 0,
+            excess: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Return true if the task was admitted within its tenant's reservation.
+    pub fn is_conforming(req: &Request) -> bool {
+        req.priority() == CONFORMING
+    }
+}
+
+impl Scheduler for AdmissionScheduler {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let conforming = match self.reservations.get_mut(&tenant_id) {
+            Some(reservation) => reservation.admit(rdtsc),
+            None => false,
+        };
+        if !conforming {
+            self.excess += 1;
+            if self.overflow == Overflow::Drop {
+                self.dropped += 1;
+                return;
+            }
+        }
+
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        if conforming {
+            req.set_priority(CONFORMING);
+        }
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        self.fast_rq
+            .pop_front()
+            .or_else(|| self.best_effort_rq.pop_front())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.fast_rq
+            .front()
+            .or_else(|| self.best_effort_rq.front())
+            .map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        self.fast_rq
+            .iter()
+            .chain(self.best_effort_rq.iter())
+            .take(n)
+            .map(|req| req.as_ref())
+            .collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
+        if AdmissionScheduler::is_conforming(&req) {
+            self.fast_rq.push_back(req);
+        } else {
+            self.best_effort_rq.push_back(req);
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        let conforming = AdmissionScheduler::is_conforming(req);
+        if !conforming {
+            if let Some(waiting) = self.fast_rq.front() {
+                return Err(format!(
+                    "AdmissionScheduler picked excess task {} while conforming task {} was waiting",
+                    req.seq(),
+                    waiting.seq()
+                ));
+            }
+        }
+        let rq = if conforming {
+            &self.fast_rq
+        } else {
+            &self.best_effort_rq
+        };
+        match rq.iter().find(|waiting| waiting.seq() < req.seq()) {
+            Some(waiting) => Err(format!(
+                "AdmissionScheduler picked task {} before the earlier task {} of its run-queue",
+                req.seq(),
+                waiting.seq()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn debug_state(&self) -> String {
+        format!(
+            "fast_rq {} best_effort_rq {} excess {} dropped {}",
+            self.fast_rq.len(),
+            self.best_effort_rq.len(),
+            self.excess,
+            self.dropped
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reservations() -> HashMap<u16, Reservation> {
+        // Both tenants reserve 1000 requests per second, in bursts of up to 2.
+        let mut reservations = HashMap::new();
+        reservations.insert(1, Reservation::new(1000.0, 2));
+        reservations.insert(2, Reservation::new(1000.0, 2));
+        reservations
+    }
+
+    #[test]
+    fn test_excess_deprioritized() {
+        let mut sched = AdmissionScheduler::new(reservations(), Overflow::Deprioritize);
+
+        // Tenant 1 sends 5 requests at once, over its reservation; tenant 2 sends 2 within it.
+        for key in 0..5 {
+            sched.create_task(0, 1.0, 1, key);
+        }
+        sched.create_task(1, 1.0, 2, 5);
+        sched.create_task(1, 1.0, 2, 6);
+        assert_eq!(sched.excess, 3);
+
+        // Tenant 2's requests run before tenant 1's excess ones, which still run in the end.
+        let mut keys = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            keys.push(req.key_id());
+        }
+        assert_eq!(keys, vec![0, 1, 5, 6, 2, 3, 4]);
+
+        // The reservation refills at its rate; 1.5ms later, one more request is within it.
+        let later = cycles::from_microseconds(1500.0);
+        sched.create_task(later, 1.0, 1, 7);
+        sched.create_task(later, 1.0, 1, 8);
+        let req = sched.pick_next_task(CoreType::Small).unwrap();
+        assert!(AdmissionScheduler::is_conforming(&req));
+
+        // A preempted task goes back to its own run-queue.
+        sched.enqueue_task(req);
+        assert_eq!(
+            sched.debug_state(),
+            "fast_rq 1 best_effort_rq 1 excess 4 dropped 0"
+        );
+    }
+
+    #[test]
+    fn test_excess_dropped() {
+        let mut sched = AdmissionScheduler::new(reservations(), Overflow::Drop);
+        for key in 0..5 {
+            sched.create_task(0, 1.0, 1, key);
+        }
+        sched.create_task(0, 1.0, 2, 5);
+
+        // Tenants without a reservation are always over it.
+        sched.create_task(0, 1.0, 3, 6);
+        assert_eq!(sched.dropped, 4);
+        assert_eq!(sched.top_n_tasks(CoreType::Small, 10).len(), 3);
+        assert!(sched.best_effort_rq.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// The renewal process of the request arrivals; the gaps between consecutive arrivals are
+/// independent, with the mean set by the request rate and the variability set by the process.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ArrivalProcess {
+    // The requests arrive exactly every 1/req_rate seconds; no variability.
+    #[serde(rename = "deterministic")]
+    Deterministic,
+
+    // Poisson arrivals; the squared coefficient of variation of the gaps is 1.
+    #[serde(rename = "exponential")]
+    Exponential,
+
+    // The gaps are the sum of k exponential phases; the squared coefficient of variation is 1/k.
+    #[serde(rename = "erlang")]
+    Erlang { k: u32 },
+
+    // The gaps come from one of two exponentials with balanced means; the squared coefficient of
+    // variation is scv, which must be at least 1.
+    #[serde(rename = "hyperexponential")]
+    Hyperexponential { scv: f64 },
+}
+
+impl ArrivalProcess {
+    /// Return true if the parameters of the process are valid; Erlang needs at least one phase, and
+    /// the hyperexponential is at least as variable as the exponential.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            ArrivalProcess::Erlang { k } => k > 0,
+            ArrivalProcess::Hyperexponential { scv } => scv >= 1.0,
+            _ => true,
+        }
+    }
+
+    /// Return the variance of the gaps between the arrivals.
+    ///
+    /// # Arguments
+    /// *`mean`: The mean gap between two arrivals.
+    pub fn variance(&self, mean: f64) -> f64 {
+        match *self {
+            ArrivalProcess::Deterministic => 0.0,
+            ArrivalProcess::Exponential => mean * mean,
+            ArrivalProcess::Erlang { k } => mean * mean / k as f64,
+            ArrivalProcess::Hyperexponential { scv } => scv * mean * mean,
+        }
+    }
+
+    /// Sample the gap until the next arrival.
+    ///
+    /// # Arguments
+    /// *`mean`: The mean gap between two arrivals.
+    /// *`rng`: The random number generator for the sample.
+    ///
+    /// # Return
+    /// The gap, in the same unit as the mean.
+    pub fn sample<R: Rng>(&self, mean: f64, rng: &mut R) -> f64 {
+        // Inverse transform sampling; 1 - u is in (0, 1], so the log is finite.
+        let exponential = |mean: f64, rng: &mut R| -mean * (1.0 - rng.gen::<f64>()).ln();
+        match *self {
+            ArrivalProcess::Deterministic => mean,
+            ArrivalProcess::Exponential => exponential(mean, rng),
+            ArrivalProcess::Erlang { k } => (0..k).map(|_| exponential(mean / k as f64, rng)).sum(),
+            ArrivalProcess::Hyperexponential { scv } => {
+                // Each branch contributes half of the mean, and the branch probability sets the
+                // variance; p = (1 + sqrt((scv - 1) / (scv + 1))) / 2.
+                let p = (1.0 + ((scv - 1.0) / (scv + 1.0)).sqrt()) / 2.0;
+                if rng.gen::<f64>() < p {
+                    exponential(mean / (2.0 * p), rng)
+                } else {
+                    exponential(mean / (2.0 * (1.0 - p)), rng)
+                }
+            }
+        }
+    }
+}
+
+/// Generates the gaps between the request arrivals of a renewal process.
+pub struct ArrivalGen {
+    // The renewal process of the arrivals.
+    process: ArrivalProcess,
+
+    // The mean gap between two arrivals in cycles.
+    mean: f64,
+
+    // Random number generator for the gaps.
+    rng: StdRng,
+}
+
+impl ArrivalGen {
+    pub fn new(process: ArrivalProcess, mean: f64) -> ArrivalGen {
+        ArrivalGen {
+            process: process,
+            mean: mean,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Same as `new()`, but the gaps are reproducible across runs.
+    pub fn with_seed(process: ArrivalProcess, mean: f64, seed: u64) -> ArrivalGen {
+        ArrivalGen {
+            process: process,
+            mean: mean,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// This method returns the gap until the next arrival in cycles.
+    pub fn next_gap(&mut self) -> f64 {
+        self.process.sample(self.mean, &mut self.rng)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_moments() {
+        let mean = 1000.0;
+        let samples = 200000;
+        let processes = [
+            ArrivalProcess::Deterministic,
+            ArrivalProcess::Exponential,
+            ArrivalProcess::Erlang { k: 4 },
+            ArrivalProcess::Hyperexponential { scv: 4.0 },
+        ];
+        for process in processes.iter() {
+            let mut gen = ArrivalGen::with_seed(*process, mean, 42);
+            let gaps: Vec<f64> = (0..samples).map(|_| gen.next_gap()).collect();
+            let sample_mean = gaps.iter().sum::<f64>() / samples as f64;
+            let sample_var =
+                gaps.iter().map(|g| (g - sample_mean).powi(2)).sum::<f64>() / samples as f64;
+
+            // The sample variance of the hyperexponential converges the slowest; hence the 10%.
+            let variance = process.variance(mean);
+            assert!(
+                (sample_mean - mean).abs() < 0.02 * mean,
+                "{:?} mean {}",
+                process,
+                sample_mean
+            );
+            assert!(
+                (sample_var - variance).abs() <= 0.1 * variance,
+                "{:?} variance {} expected {}",
+                process,
+                sample_var,
+                variance
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(ArrivalProcess::Erlang { k: 1 }.is_valid());
+        assert!(!ArrivalProcess::Erlang { k: 0 }.is_valid());
+        assert!(ArrivalProcess::Hyperexponential { scv: 1.0 }.is_valid());
+        assert!(!ArrivalProcess::Hyperexponential { scv: 0.5 }.is_valid());
+    }
+}
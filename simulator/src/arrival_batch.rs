@@ -0,0 +1,152 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+/// A request which arrived at the server and waits for its batch to be dispatched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PendingRequest {
+    // The tenant of the request.
+    pub tenant: u16,
+
+    // The time stamp at which the request arrived, in cycles.
+    pub arrival: u64,
+
+    // The time the request needs on a core in micro-seconds, if it ran alone.
+    pub task_time: f64,
+
+    // The key accessed by the request.
+    pub key_id: u64,
+
+    // The size of the request in bytes.
+    pub size_bytes: usize,
+}
+
+/// Collects the arriving requests into batches on the server side. A batch is dispatched once
+/// `max_size` requests have arrived, or `window` cycles after its first request arrived. The
+/// requests in a batch share the per-request overhead, so a batch of K requests needs
+/// `K * task_time * efficiency` on a core instead of `K * task_time`.
+pub struct ArrivalBatcher {
+    // The time in cycles a batch waits for more requests after its first request arrived.
+    window: u64,
+
+    // The maximum number of requests in a batch.
+    max_size: usize,
+
+    // The task times in a batch are scaled by this factor.
+    efficiency: f64,
+
+    // The requests in the batch which is being collected.
+    pending: Vec<PendingRequest>,
+
+    // The number of batches dispatched so far.
+    pub batches: u64,
+}
+
+impl ArrivalBatcher {
+    /// Create a batcher.
+    ///
+    /// # Arguments
+    /// `window`: The time in cycles a batch waits for more requests after the first one.
+    /// `max_size`: The maximum number of requests in a batch.
+    /// `efficiency`: The task times in a batch are scaled by this factor; in (0, 1].
+    pub fn new(window: u64, max_size: usize, efficiency: f64) -> ArrivalBatcher {
+        ArrivalBatcher {
+            window: window,
+            max_size: max_size,
+            efficiency: efficiency,
+            pending: Vec::with_capacity(max_size),
+            batches: 0,
+        }
+    }
+
+    /// Add an arrival to the batch which is being collected.
+    pub fn add(&mut self, req: PendingRequest) {
+        self.pending.push(req);
+    }
+
+    /// Return the number of requests in the batch which is being collected.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Return the time stamp at which the batch which is being collected is dispatched, unless it
+    /// fills up before; none if there is no request waiting.
+    pub fn deadline(&self) -> Option<u64> {
+        self.pending.first().map(|req| req.arrival + self.window)
+    }
+
+    /// This method dispatches the batch which is being collected, if it is full or its window
+    /// has elapsed.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The current time stamp.
+    ///
+    /// # Return
+    /// The requests of the batch, in the order they arrived, with the task times scaled by the
+    /// efficiency; empty if the batch isn't due yet.
+    pub fn take_due(&mut self, rdtsc: u64) -> Vec<PendingRequest> {
+        let due = match self.deadline() {
+            Some(deadline) => self.pending.len() >= self.max_size || rdtsc >= deadline,
+            None => false,
+        };
+        if !due {
+            return Vec::new();
+        }
+
+        self.batches += 1;
+        let efficiency = self.efficiency;
+        let mut batch: Vec<PendingRequest> = self.pending.drain(..).collect();
+        for req in batch.iter_mut() {
+            req.task_time *= efficiency;
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn arrival(arrival: u64) -> PendingRequest {
+        PendingRequest {
+            tenant: 1,
+            arrival: arrival,
+            task_time: 2.0,
+            key_id: 0,
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_batch_window_and_size() {
+        let mut batcher = ArrivalBatcher::new(1000, 3, 0.5);
+        assert_eq!(batcher.deadline(), None);
+
+        // The window elapses before the batch fills up.
+        batcher.add(arrival(100));
+        batcher.add(arrival(500));
+        assert!(batcher.take_due(1099).is_empty());
+        let batch = batcher.take_due(1100);
+        assert_eq!(batch.len(), 2);
+        assert!(batch.iter().all(|req| req.task_time == 1.0));
+
+        // The batch fills up before the window elapses.
+        for time in [2000, 2001, 2002].iter() {
+            batcher.add(arrival(*time));
+        }
+        assert_eq!(batcher.take_due(2002).len(), 3);
+        assert_eq!(batcher.batches, 2);
+        assert_eq!(batcher.len(), 0);
+    }
+}
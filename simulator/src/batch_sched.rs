@@ -0,0 +1,253 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::collections::VecDeque;
+
+/// Groups consecutive short requests of the same tenant, so that a batch pays the dispatch
+/// overhead once instead of once per request.
+#[derive(Serialize, Deserialize)]
+pub struct BatchingScheduler {
+    // Task runqueue.
+    rq: VecDeque<Box<Request>>,
+
+    // The tasks left from the batch which is being dispatched.
+    batch: VecDeque<Box<Request>>,
+
+    // The maximum number of tasks in a batch.
+    max_batch: usize,
+
+    // The maximum total time in micro-seconds of the tasks in a batch; longer tasks run alone.
+    max_batch_time: f64,
+
+    // The sequence number for the next task added to the runqueue.
+    next_seq: u64,
+
+    // The number of batches dispatched so far; a task which runs alone is a batch of one.
+    pub dispatches: u64,
+}
+
+impl BatchingScheduler {
+    pub fn new(max_batch: usize, max_batch_time: f64) -> BatchingScheduler {
+        BatchingScheduler {
+            rq: VecDeque::with_capacity(32),
+            batch: VecDeque::with_capacity(max_batch),
+            max_batch: max_batch,
+            max_batch_time: max_batch_time,
+            next_seq: 0,
+            dispatches: 0,
+        }
+    }
+
+    /// This method takes the next batch from the head of the runqueue; up to `max_batch` tasks
+    /// of the same tenant, whose total time is within `max_batch_time`. A task which doesn't fit
+    /// in a batch by itself is returned alone.
+    ///
+    /// # Return
+    /// The tasks of the batch, in the order they were added to the runqueue.
+    pub fn pick_batch(&mut self) -> Vec<Box<Request>> {
+        let mut batch: Vec<Box<Request>> = Vec::with_capacity(self.max_batch);
+        let mut batch_time = 0.0;
+        while batch.len() < self.max_batch {
+            let fits = match self.rq.front() {
+                Some(next) => {
+                    let same_tenant = batch
+                        .first()
+                        .map_or(true, |first| first.get_tenant() == next.get_tenant());
+                    same_tenant && batch_time + next.remaining_time() <= self.max_batch_time
+                }
+                None => false,
+            };
+            if !fits {
+                break;
+            }
+            let req = self.rq.pop_front().unwrap();
+            batch_time += req.remaining_time();
+            batch.push(req);
+        }
+
+        // A long task runs alone.
+        if batch.is_empty() {
+            if let Some(req) = self.rq.pop_front() {
+                batch.push(req);
+            }
+        }
+        if !batch.is_empty() {
+            self.dispatches += 1;
+        }
+        batch
+    }
+}
+
+impl Scheduler for BatchingScheduler {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        if self.batch.is_empty() {
+            let batch = self.pick_batch();
+            self.batch.extend(batch);
+        }
+        self.batch.pop_front()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.batch
+            .front()
+            .or_else(|| self.rq.front())
+            .map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        // The batches are taken from the head of the runqueue, so the order doesn't change.
+        self.batch
+            .iter()
+            .chain(self.rq.iter())
+            .take(n)
+            .map(|req| req.as_ref())
+            .collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
+        self.rq.push_back(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        // Batches are taken from the head of the runqueue, so the tasks still run in order.
+        for waiting in self.batch.iter().chain(self.rq.iter()) {
+            if waiting.seq() < req.seq() {
+                return Err(format!(
+                    "BatchingScheduler picked task {} while task {} was waiting",
+                    req.seq(),
+                    waiting.seq()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::rr_sched::RoundRobin;
+    use super::*;
+
+    // The time in micro-seconds to run all the tasks, paying `overhead` for each dispatch.
+    fn makespan(dispatches: u64, work: f64, overhead: f64) -> f64 {
+        dispatches as f64 * overhead + work
+    }
+
+    #[test]
+    fn test_batching_throughput() {
+        let overhead = 2.0;
+        let mut batching = BatchingScheduler::new(8, 10.0);
+        let mut rr = RoundRobin::new(5.0);
+        for i in 0..1000 {
+            batching.create_task(i, 1.0, 1, 0);
+            rr.create_task(i, 1.0, 1, 0);
+        }
+
+        let mut work = 0.0;
+        while let Some(task) = batching.pick_next_task(CoreType::Small) {
+            assert!(batching.check_ordering(&task, CoreType::Small).is_ok());
+            work += task.remaining_time();
+        }
+        let mut rr_dispatches = 0;
+        while rr.pick_next_task(CoreType::Small).is_some() {
+            rr_dispatches += 1;
+        }
+
+        assert_eq!(batching.dispatches, 125);
+        let batching_rps = 1000.0 / makespan(batching.dispatches, work, overhead);
+        let single_rps = 1000.0 / makespan(rr_dispatches, work, overhead);
+        assert!(batching_rps > 2.0 * single_rps);
+    }
+
+    #[test]
+    fn test_top_n_tasks() {
+        let mut batching = BatchingScheduler::new(2, 10.0);
+        for i in 0..5 {
+            batching.create_task(i, 1.0, 1, i);
+        }
+
+        // One task left in the current batch, and the rest in the runqueue.
+        assert_eq!(
+            batching.pick_next_task(CoreType::Small).unwrap().key_id(),
+            0
+        );
+        let top: Vec<u64> = batching
+            .top_n_tasks(CoreType::Small, 3)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        assert_eq!(top, vec![1, 2, 3]);
+        for key in top {
+            assert_eq!(
+                batching.pick_next_task(CoreType::Small).unwrap().key_id(),
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_long_task_runs_alone() {
+        let mut batching = BatchingScheduler::new(8, 10.0);
+        batching.create_task(0, 1.0, 1, 0);
+        batching.create_task(1, 50.0, 1, 0);
+        batching.create_task(2, 1.0, 1, 0);
+
+        assert_eq!(batching.pick_batch().len(), 1);
+        assert_eq!(batching.pick_batch().len(), 1);
+        assert_eq!(batching.pick_batch().len(), 1);
+        assert_eq!(batching.dispatches, 3);
+    }
+}
@@ -15,9 +15,153 @@
 
 extern crate simulator;
 
+use simulator::config::Config;
 use simulator::cores::Simulator;
+use simulator::cycles;
+use simulator::fairness;
+use simulator::sched_bench;
+use simulator::snapshot;
+use simulator::sweep;
+
+use std::env;
+use std::path::Path;
+use std::process;
 
 fn main() {
     env_logger::init();
-    Simulator::new().start();
+    let args: Vec<String> = env::args().collect();
+    let mut config = match Config::from_cli_args(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(errors) = config.validate() {
+        for e in errors {
+            eprintln!("Invalid config: {}", e);
+        }
+        process::exit(1);
+    }
+    if env::args().any(|arg| arg == "--check-ordering") {
+        config.check_ordering = true;
+    }
+    if env::args().any(|arg| arg == "--verbose") {
+        config.verbose = true;
+    }
+    if env::args().any(|arg| arg == "--client-output") {
+        config.client_output = true;
+    }
+    if env::args().any(|arg| arg == "--fairness-report") {
+        for tenant in fairness::colocation_report(&config) {
+            println!(
+                "Fairness: Tenant {} Isolated(us) {:.2} Colocated(us) {:.2} Inflation {:.2}",
+                tenant.tenant,
+                cycles::to_microseconds(tenant.isolated_latency as u64),
+                cycles::to_microseconds(tenant.colocated_latency as u64),
+                tenant.inflation
+            );
+        }
+        return;
+    }
+
+    // Measure the scheduler of the configured policy alone, without running the simulation.
+    if env::args().any(|arg| arg == "--bench-sched") {
+        let result = sched_bench::run(
+            sched_bench::scheduler_for(&config),
+            sched_bench::DEFAULT_ROUNDS,
+        );
+        println!("Sched-Bench: Policy {:?} {}", config.policy, result);
+        return;
+    }
+
+    // A sweep runs once for each value following --sweep, e.g. --sweep arrival_rate=1e5,2e5, and
+    // writes one line of results per run to the file following --sweep-output.
+    if let Some(sweep) = args
+        .iter()
+        .position(|arg| arg == "--sweep")
+        .and_then(|i| args.get(i + 1))
+    {
+        let output = match args
+            .iter()
+            .position(|arg| arg == "--sweep-output")
+            .and_then(|i| args.get(i + 1))
+        {
+            Some(output) => output,
+            None => {
+                eprintln!("--sweep needs a --sweep-output file");
+                process::exit(1);
+            }
+        };
+        let mut pair = sweep.splitn(2, '=');
+        let parameter = pair.next().unwrap_or("");
+        let values: Result<Vec<f64>, _> = pair
+            .next()
+            .unwrap_or("")
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect();
+        let values = match values {
+            Ok(values) => values,
+            Err(_) => {
+                eprintln!("--sweep {} must be parameter=value,value,...", sweep);
+                process::exit(1);
+            }
+        };
+        let results = match sweep::run_sweep(&config, parameter, &values) {
+            Ok(results) => results,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+        if let Err(e) = sweep::write_csv(Path::new(output), parameter, &values, &results) {
+            eprintln!("Failure writing sweep results to {}: {}", output, e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // The snapshots are written to the file following --snapshot-output.
+    let snapshot_output = args
+        .iter()
+        .position(|arg| arg == "--snapshot-output")
+        .and_then(|i| args.get(i + 1));
+    if snapshot_output.is_some() && config.snapshot_interval_ns == 0 {
+        eprintln!("--snapshot-output needs a non-zero snapshot_interval_ns in config.toml");
+        process::exit(1);
+    }
+
+    // The timeline of each request is written to the file following --trace-log.
+    let trace_log = args
+        .iter()
+        .position(|arg| arg == "--trace-log")
+        .and_then(|i| args.get(i + 1));
+
+    // Each scheduling decision is written to the file following --decision-log.
+    let decision_log = args
+        .iter()
+        .position(|arg| arg == "--decision-log")
+        .and_then(|i| args.get(i + 1));
+
+    let mut simulator = Simulator::with_config(config);
+    if let Some(path) = trace_log {
+        if let Err(e) = simulator.set_trace_log(Path::new(path)) {
+            eprintln!("Failure creating trace log {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = decision_log {
+        if let Err(e) = simulator.set_decision_log(Path::new(path)) {
+            eprintln!("Failure creating decision log {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    let result = simulator.start();
+    if let Some(path) = snapshot_output {
+        if let Err(e) = snapshot::write_csv(Path::new(path), &result.snapshots) {
+            eprintln!("Failure writing snapshots to {}: {}", path, e);
+            process::exit(1);
+        }
+    }
 }
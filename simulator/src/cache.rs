@@ -0,0 +1,159 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+pub struct CacheSimulator {
+    // The maximum number of distinct keys the cache can hold.
+    cache_size: usize,
+
+    // Map from a cached key to the logical time of its last access.
+    keys: HashMap<u64, u64>,
+
+    // Map from the logical time of last access to the key; the first entry is the LRU key.
+    lru: BTreeMap<u64, u64>,
+
+    // Logical clock, incremented on each access.
+    tick: u64,
+
+    // Number of accesses which found the key in the cache.
+    pub hits: u64,
+
+    // Number of accesses which didn't find the key in the cache.
+    pub misses: u64,
+}
+
+impl CacheSimulator {
+    pub fn new(cache_size: usize) -> CacheSimulator {
+        CacheSimulator {
+            cache_size: cache_size,
+            keys: HashMap::with_capacity(cache_size),
+            lru: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// This method looks up a key in the cache and inserts it on a miss, evicting the least
+    /// recently used key if the cache is full.
+    ///
+    /// # Arguments
+    /// `key_id`: The key accessed by the request.
+    ///
+    /// # Return
+    /// True if the key was found in the cache, false otherwise.
+    pub fn access(&mut self, key_id: u64) -> bool {
+        if self.cache_size == 0 {
+            self.misses += 1;
+            return false;
+        }
+
+        self.tick += 1;
+        if let Some(last) = self.keys.insert(key_id, self.tick) {
+            self.lru.remove(&last);
+            self.lru.insert(self.tick, key_id);
+            self.hits += 1;
+            return true;
+        }
+
+        self.lru.insert(self.tick, key_id);
+        if self.keys.len() > self.cache_size {
+            let (&oldest, &victim) = self.lru.iter().next().unwrap();
+            self.lru.remove(&oldest);
+            self.keys.remove(&victim);
+        }
+        self.misses += 1;
+        false
+    }
+
+    /// Return the fraction of accesses which hit in the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::prelude::*;
+    use zipf::ZipfDistribution;
+
+    // Che's approximation for the hit rate of an LRU cache under independent Zipf accesses.
+    fn che_hit_rate(num_keys: usize, skew: f64, cache_size: usize) -> f64 {
+        let norm: f64 = (1..num_keys + 1).map(|i| 1.0 / (i as f64).powf(skew)).sum();
+        let probs: Vec<f64> = (1..num_keys + 1)
+            .map(|i| 1.0 / (i as f64).powf(skew) / norm)
+            .collect();
+        let occupancy = |t: f64| -> f64 { probs.iter().map(|p| 1.0 - (-p * t).exp()).sum() };
+
+        // Find the characteristic time at which the expected occupancy equals the cache size.
+        let (mut low, mut high) = (0.0, 1.0);
+        while occupancy(high) < cache_size as f64 {
+            high *= 2.0;
+        }
+        for _ in 0..100 {
+            let mid = (low + high) / 2.0;
+            if occupancy(mid) < cache_size as f64 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        probs.iter().map(|p| p * (1.0 - (-p * high).exp())).sum()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = CacheSimulator::new(2);
+        assert_eq!(cache.access(1), false);
+        assert_eq!(cache.access(2), false);
+        assert_eq!(cache.access(1), true);
+        // Key 2 is the least recently used key and gets evicted.
+        assert_eq!(cache.access(3), false);
+        assert_eq!(cache.access(1), true);
+        assert_eq!(cache.access(2), false);
+        assert_eq!(cache.hits, 2);
+        assert_eq!(cache.misses, 4);
+    }
+
+    #[test]
+    fn test_zero_sized_cache() {
+        let mut cache = CacheSimulator::new(0);
+        assert_eq!(cache.access(1), false);
+        assert_eq!(cache.access(1), false);
+        assert_eq!(cache.hit_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_zipf_hit_rate() {
+        let (num_keys, skew) = (1000, 0.9);
+        let mut rng = StdRng::seed_from_u64(42);
+        let zipf = ZipfDistribution::new(num_keys, skew).unwrap();
+
+        for cache_size in [10, 100, 500].iter() {
+            let mut cache = CacheSimulator::new(*cache_size);
+            for _ in 0..200000 {
+                cache.access(zipf.sample(&mut rng) as u64);
+            }
+            let expected = che_hit_rate(num_keys, skew, *cache_size);
+            assert!((cache.hit_rate() - expected).abs() < 0.02);
+        }
+    }
+}
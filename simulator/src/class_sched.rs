@@ -0,0 +1,321 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::classes::assign_classes;
+use super::config::TenantClass;
+use super::cores::CoreType;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// The class of a request; the `class_id` of its tenant's class in the configuration.
+pub type RequestClass = u8;
+
+/// Strict priority between the request classes, each with its own scheduler. A class is only
+/// served when all the classes of a higher priority have no task waiting; the order of the tasks
+/// within a class is up to the scheduler of that class.
+pub struct ClassBasedScheduler {
+    // The scheduler for the tasks of each class.
+    schedulers: HashMap<RequestClass, Box<dyn Scheduler>>,
+
+    // The classes from the highest to the lowest priority; classes of an equal priority are in
+    // the configuration order.
+    order: Vec<RequestClass>,
+
+    // The class of each tenant; tenant `t` is at `t - 1`.
+    tenant_classes: Vec<RequestClass>,
+}
+
+/// The state of a `ClassBasedScheduler` in a checkpoint; the state of each class's scheduler is
+/// serialized by that scheduler.
+#[derive(Serialize, Deserialize)]
+struct ClassState {
+    // The serialized state of the scheduler for each class.
+    schedulers: HashMap<RequestClass, Vec<u8>>,
+
+    // The classes from the highest to the lowest priority.
+    order: Vec<RequestClass>,
+
+    // The class of each tenant; tenant `t` is at `t - 1`.
+    tenant_classes: Vec<RequestClass>,
+}
+
+impl ClassBasedScheduler {
+    /// This method creates a scheduler over the tenant classes from the configuration.
+    ///
+    /// # Arguments
+    /// `classes`: The tenant classes from the configuration; the tenants are assigned to them by
+    ///            `classes::assign_classes()`.
+    /// `schedulers`: The scheduler for each class; there must be one for each `class_id`.
+    ///
+    /// # Return
+    /// The scheduler, with empty run-queues.
+    pub fn new(
+        classes: &[TenantClass],
+        schedulers: HashMap<RequestClass, Box<dyn Scheduler>>,
+    ) -> ClassBasedScheduler {
+        for class in classes.iter() {
+            assert!(
+                schedulers.contains_key(&class.class_id),
+                "No scheduler for class {}",
+                class.class_id
+            );
+        }
+
+        let mut sorted: Vec<&TenantClass> = classes.iter().collect();
+        sorted.sort_by_key(|class| Reverse(class.priority));
+        let tenant_classes = assign_classes(classes)
+            .iter()
+            .map(|index| classes[*index].class_id)
+            .collect();
+
+        ClassBasedScheduler {
+            schedulers: schedulers,
+            order: sorted.iter().map(|class| class.class_id).collect(),
+            tenant_classes: tenant_classes,
+        }
+    }
+
+    /// Return the class of the tenant; the lowest priority class if the tenant isn't assigned to
+    /// any class.
+    pub fn class(&self, tenant: u16) -> RequestClass {
+        match (tenant as usize).checked_sub(1) {
+            Some(index) if index < self.tenant_classes.len() => self.tenant_classes[index],
+            _ => *self.order.last().expect("No request classes"),
+        }
+    }
+
+    /// Return the schedulers from the highest to the lowest priority class.
+    fn by_priority(&self) -> impl Iterator<Item = (RequestClass, &dyn Scheduler)> {
+        self.order
+            .iter()
+            .map(move |class| (*class, self.schedulers[class].as_ref()))
+    }
+
+    /// Return the highest priority class with a task waiting.
+    fn top_class(&self, coretype: CoreType) -> Option<RequestClass> {
+        self.by_priority()
+            .find(|(_, sched)| sched.top_task(coretype).is_some())
+            .map(|(class, _)| class)
+    }
+}
+
+impl Scheduler for ClassBasedScheduler {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let class = self.class(tenant_id);
+        self.schedulers
+            .get_mut(&class)
+            .unwrap()
+            .create_sized_task(rdtsc, task_time, tenant_id, key_id, size_bytes);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, coretype: CoreType) -> Option<Box<Request>> {
+        let class = self.top_class(coretype)?;
+        self.schedulers
+            .get_mut(&class)
+            .unwrap()
+            .pick_next_task(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, coretype: CoreType) -> Option<&Request> {
+        self.by_priority()
+            .filter_map(|(_, sched)| sched.top_task(coretype))
+            .next()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, coretype: CoreType, n: usize) -> Vec<&Request> {
+        let mut tasks = Vec::new();
+        for (_, sched) in self.by_priority() {
+            if tasks.len() == n {
+                break;
+            }
+            tasks.extend(sched.top_n_tasks(coretype, n - tasks.len()));
+        }
+        tasks
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        match self.top_class(coretype) {
+            Some(class) => self.schedulers[&class].quantum(coretype),
+            None => default_quantum(coretype),
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, req: Box<Request>) {
+        let class = self.class(req.get_tenant());
+        self.schedulers.get_mut(&class).unwrap().enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(&ClassState {
+            schedulers: self
+                .schedulers
+                .iter()
+                .map(|(class, sched)| (*class, sched.serialize_state()))
+                .collect(),
+            order: self.order.clone(),
+            tenant_classes: self.tenant_classes.clone(),
+        })
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let state: ClassState = decode_state(data)?;
+        if state.schedulers.len() != self.schedulers.len()
+            || state
+                .schedulers
+                .keys()
+                .any(|class| !self.schedulers.contains_key(class))
+        {
+            return Err("the scheduler state has different request classes".to_string());
+        }
+
+        // Put back the schedulers restored so far if one of them rejects its state.
+        let backup = self.serialize_state();
+        for (class, data) in state.schedulers.iter() {
+            let sched = self.schedulers.get_mut(class).unwrap();
+            if let Err(e) = sched.restore_state(data) {
+                self.restore_state(&backup)
+                    .expect("couldn't restore the previous scheduler state");
+                return Err(format!("class {}: {}", class, e));
+            }
+        }
+        self.order = state.order;
+        self.tenant_classes = state.tenant_classes;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, coretype: CoreType) -> Result<(), String> {
+        let class = self.class(req.get_tenant());
+        for (higher, sched) in self.by_priority().take_while(|(other, _)| *other != class) {
+            if let Some(waiting) = sched.top_task(coretype) {
+                return Err(format!(
+                    "ClassBasedScheduler picked task {} of class {} while task {} of class {} was waiting",
+                    req.seq(),
+                    class,
+                    waiting.seq(),
+                    higher
+                ));
+            }
+        }
+        self.schedulers[&class].check_ordering(req, coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn debug_state(&self) -> String {
+        self.by_priority()
+            .map(|(class, sched)| format!("class {} [{}]", class, sched.debug_state()))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::two_class_sched::TwoClassScheduler;
+    use super::*;
+
+    fn class(class_id: u8, priority: u8, num_tenants: u32) -> TenantClass {
+        TenantClass {
+            class_id: class_id,
+            priority: priority,
+            arrival_rate: 1.0,
+            service_time_mean: 1.0,
+            num_tenants: num_tenants,
+        }
+    }
+
+    // A FIFO scheduler for each class; the tenants of a class are all equal.
+    fn fifo() -> Box<dyn Scheduler> {
+        Box::new(TwoClassScheduler::new(HashMap::new()))
+    }
+
+    #[test]
+    fn test_strict_class_priority() {
+        // Tenants 1 and 3 are in class 0 and tenants 2 and 4 in class 1; class 1 goes first.
+        let classes = vec![class(0, 1, 2), class(1, 2, 2)];
+        let mut schedulers = HashMap::new();
+        schedulers.insert(0, fifo());
+        schedulers.insert(1, fifo());
+        let mut sched = ClassBasedScheduler::new(&classes, schedulers);
+        assert_eq!(
+            (1..5)
+                .map(|tenant| sched.class(tenant))
+                .collect::<Vec<u8>>(),
+            vec![0, 1, 0, 1]
+        );
+
+        // The low priority tasks arrive first, but only run after all the high priority ones.
+        sched.create_task(0, 1.0, 1, 0);
+        sched.create_task(1, 1.0, 3, 1);
+        sched.create_task(2, 1.0, 2, 2);
+        sched.create_task(3, 1.0, 4, 3);
+        sched.create_task(4, 1.0, 2, 4);
+
+        let top: Vec<u64> = sched
+            .top_n_tasks(CoreType::Small, 5)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        let mut keys = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            keys.push(req.key_id());
+
+            // A high priority arrival runs ahead of the waiting low priority task.
+            if req.key_id() == 0 {
+                sched.create_task(5, 1.0, 4, 5);
+            }
+        }
+        assert_eq!(top, vec![2, 3, 4, 0, 1]);
+        assert_eq!(keys, vec![2, 3, 4, 0, 5, 1]);
+    }
+
+    #[test]
+    fn test_check_ordering() {
+        let classes = vec![class(0, 1, 1), class(1, 2, 1)];
+        let mut schedulers = HashMap::new();
+        schedulers.insert(0, fifo());
+        schedulers.insert(1, fifo());
+        let mut sched = ClassBasedScheduler::new(&classes, schedulers);
+
+        // Tenant 1 is in the low priority class 0.
+        let mut low = Box::new(Request::new(1, 0, 0, 1.0));
+        low.set_seq(0);
+        sched.create_task(0, 1.0, 2, 1);
+        assert!(sched.check_ordering(&low, CoreType::Small).is_err());
+        assert_eq!(
+            sched.debug_state(),
+            "class 1 [critical_rq 0 best_effort_rq 1] class 0 [critical_rq 0 best_effort_rq 0]"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::{IntraClassPolicy, TenantClass};
+use super::cores::CoreType;
+use super::tenant::Tenant;
+
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use rand::Rng;
+
+/// This method assigns the tenants to the classes in turn, so that the tenants on each core are
+/// spread over the classes.
+///
+/// # Arguments
+/// `classes`: The tenant classes from the configuration.
+///
+/// # Return
+/// The index of the class for each tenant; tenant `t` is at `t - 1`.
+pub fn assign_classes(classes: &[TenantClass]) -> Vec<usize> {
+    let mut left: Vec<u32> = classes.iter().map(|class| class.num_tenants).collect();
+    let mut assigned = Vec::new();
+    while left.iter().any(|tenants| *tenants > 0) {
+        for (class, tenants) in left.iter_mut().enumerate() {
+            if *tenants > 0 {
+                *tenants -= 1;
+                assigned.push(class);
+            }
+        }
+    }
+    assigned
+}
+
+/// The tenants of a class on a core.
+pub struct ClassQueue {
+    // The class from the configuration.
+    pub class: TenantClass,
+
+    // The tenants of this class; the indexes into the tenants of the core.
+    pub tenants: Vec<usize>,
+
+    // The position in `tenants` from which the next round-robin pick starts.
+    next: usize,
+}
+
+impl ClassQueue {
+    pub fn new(class: TenantClass) -> ClassQueue {
+        ClassQueue {
+            class: class,
+            tenants: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Return a service time in micro-seconds for a request of this class.
+    pub fn service_time<R: Rng>(&self, rng: &mut R) -> f64 {
+        // Exponential by inverse transform; 1 - u is in (0, 1], so the log is finite.
+        -self.class.service_time_mean * (1.0 - rng.gen::<f64>()).ln()
+    }
+
+    /// This method picks the tenant of this class to run next.
+    ///
+    /// # Arguments
+    /// `tenants`: The tenants of the core.
+    /// `coretype`: The type of the core.
+    /// `policy`: The order in which the tenants of the class are picked.
+    ///
+    /// # Return
+    /// The index into `tenants` of the picked tenant, if any tenant of this class has a task
+    /// waiting.
+    pub fn pick(
+        &mut self,
+        tenants: &[Arc<RefCell<Tenant>>],
+        coretype: CoreType,
+        policy: IntraClassPolicy,
+    ) -> Option<usize> {
+        let len = self.tenants.len();
+        match policy {
+            IntraClassPolicy::RoundRobin => {
+                for i in 0..len {
+                    let pos = (self.next + i) % len;
+                    let index = self.tenants[pos];
+                    if tenants[index].borrow().peek_task_time(coretype).is_some() {
+                        self.next = (pos + 1) % len;
+                        return Some(index);
+                    }
+                }
+                None
+            }
+
+            IntraClassPolicy::ShortestJobFirst => {
+                let mut picked: Option<(usize, f64)> = None;
+                for index in self.tenants.iter() {
+                    if let Some(time) = tenants[*index].borrow().peek_task_time(coretype) {
+                        match picked {
+                            Some((_, shortest)) if shortest <= time => {}
+                            _ => picked = Some((*index, time)),
+                        }
+                    }
+                }
+                picked.map(|(index, _)| index)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_assign_classes() {
+        let class = |class_id: u8, num_tenants: u32| TenantClass {
+            class_id: class_id,
+            priority: 0,
+            arrival_rate: 1.0,
+            service_time_mean: 1.0,
+            num_tenants: num_tenants,
+        };
+        let classes = vec![class(0, 3), class(1, 1)];
+        assert_eq!(assign_classes(&classes), vec![0, 1, 0, 0]);
+    }
+}
@@ -13,6 +13,12 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+use super::arrival::ArrivalProcess;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
 
@@ -36,7 +42,45 @@ pub enum Policy {
     Minos,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The distribution of the service times of the requests.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ServiceTimeDistribution {
+    // The task times from consts::TASK_DISTRIBUTION_TIME.
+    #[serde(rename = "fixed")]
+    Fixed,
+
+    // Exponential with a mean of service_time_mean_us.
+    #[serde(rename = "exponential")]
+    Exponential,
+}
+
+/// The order in which a core picks the tenants within the same tenant class.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum IntraClassPolicy {
+    RoundRobin,
+    ShortestJobFirst,
+}
+
+/// A group of tenants with the same priority and load.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TenantClass {
+    // The id of the class.
+    pub class_id: u8,
+
+    // The tenants of a class with a higher priority run before the tenants of a lower priority.
+    pub priority: u8,
+
+    // The share of the requests sent to the tenants of this class, relative to the other classes.
+    pub arrival_rate: f64,
+
+    // The mean service time in micro-seconds of the requests for this class; exponential.
+    pub service_time_mean: f64,
+
+    // The number of tenants in this class.
+    pub num_tenants: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
     // The maximum number of small cores used for the simultaion.
     pub small_cores: u64,
@@ -56,6 +100,10 @@ pub struct Config {
     // The number of responses that the client must receive before terminating the process.
     pub num_resps: u64,
 
+    // The total number of completions across all the cores after which the simulation stops;
+    // zero falls back to `num_resps` on each small core.
+    pub num_requests: u64,
+
     // The req rate per second.
     pub req_rate: u64,
 
@@ -70,6 +118,155 @@ pub struct Config {
 
     // This is to decide the scheduling policy; Round Robin or minos like core partitioning.
     pub policy: Policy,
+
+    // The number of distinct keys accessed by the requests.
+    pub num_keys: u64,
+
+    // Skew in picking the key for new request.
+    pub key_skew: f64,
+
+    // The number of distinct keys the per-core cache can hold; zero disables the cache.
+    pub cache_size: u64,
+
+    // The task time is multiplied by this factor if the request hits in the cache.
+    pub cache_speedup: f64,
+
+    // Verify each scheduling decision against the policy; expensive, debugging only.
+    pub check_ordering: bool,
+
+    // Print the progress of the run periodically.
+    pub verbose: bool,
+
+    // Print only the throughput and latency lines, in the same format as the client.
+    pub client_output: bool,
+
+    // Measure the wall-clock time spent in the scheduler calls, and print the mean per call.
+    pub measure_sched_overhead: bool,
+
+    // The number of completed requests between two progress lines; zero for one line every
+    // simulated second.
+    pub progress_interval: u64,
+
+    // If set, the client cancels each request which didn't complete this many micro-seconds
+    // after it arrived.
+    pub client_timeout_us: Option<f64>,
+
+    // The shortest task time in micro-seconds; the shorter generated or replayed task times are
+    // clamped up to it. Zero disables the floor.
+    pub min_task_time_us: f64,
+
+    // A task preempted this many times runs to completion the next time it is picked; zero
+    // doesn't limit the preemptions.
+    pub max_preemptions: u32,
+
+    // If set, all the random number generators are seeded from this, and the run is reproducible.
+    pub seed: Option<u64>,
+
+    // If set, replay the request arrivals from this pcap file instead of generating them.
+    pub trace_file: Option<String>,
+
+    // The factor by which the replayed trace is sped up.
+    pub trace_speedup: f64,
+
+    // The task time in micro-seconds for a request from a text trace is
+    // `trace_base_us + trace_us_per_byte * size_bytes`.
+    pub trace_base_us: f64,
+    pub trace_us_per_byte: f64,
+
+    // The size in bytes of the generated requests; the replayed requests have their own size.
+    pub request_size_bytes: u64,
+
+    // A core spends `dispatch_base_us + dispatch_us_per_byte * size_bytes` micro-seconds to set up
+    // a request before it first runs, on top of its task time.
+    pub dispatch_base_us: f64,
+    pub dispatch_us_per_byte: f64,
+
+    // The task time is inflated by this factor when the SMT sibling core is busy too.
+    pub smt_slowdown: f64,
+
+    // The speed of each small core, by the core id, relative to the others; the task time is
+    // divided by it. The cores without an entry have a speed of 1.
+    #[serde(default)]
+    pub core_speeds: Vec<f64>,
+
+    // The latency-critical tenants; with `core_speeds`, the cores serving the most of them are
+    // given the fastest speeds.
+    #[serde(default)]
+    pub critical_tenants: Vec<u16>,
+
+    // The latency SLA in microseconds for each tenant; used to detect the noisy neighbors.
+    pub sla_us: f64,
+
+    // The time slice in micro-seconds after which the round-robin scheduler preempts a task.
+    pub rr_quantum_us: f64,
+
+    // Coalesce the waiting requests of a tenant for the same key with the RoundRobin policy; the
+    // first one runs and the others complete with it.
+    pub coalesce_requests: bool,
+
+    // The queue depth on a core above which the core may be overloaded; zero disables the
+    // overload detection.
+    pub overload_high_water: u64,
+
+    // The time in micro-seconds the queue must stay above the high-water mark to be an overload.
+    pub overload_window_us: f64,
+
+    // The fraction of the arrivals shed while a core is overloaded.
+    pub shed_fraction: f64,
+
+    // The tenant which emits a burst of `burst_size` requests at `burst_at_us`; zero disables the
+    // burst.
+    pub burst_tenant: u64,
+
+    // The simulated time in micro-seconds at which the burst arrives.
+    pub burst_at_us: f64,
+
+    // The number of requests in the burst.
+    pub burst_size: u64,
+
+    // The server collects the arrivals into batches for this many nano-seconds after the first
+    // request of a batch; zero disables the server-side batching.
+    pub batch_window_ns: f64,
+
+    // The maximum number of requests in a server-side batch.
+    pub max_batch_size: u64,
+
+    // A batch of K requests needs K times the task time times this factor on a core.
+    pub batch_efficiency: f64,
+
+    // Stop the run once the p99 latency changes by less than this fraction between two estimates;
+    // zero disables the early stop.
+    pub convergence_epsilon: f64,
+
+    // The number of completed requests between two estimates of the p99 latency.
+    pub convergence_window: u64,
+
+    // The number of server worker threads shared by all the cores; zero means one per core.
+    pub max_concurrent_tasks: u64,
+
+    // Record a snapshot of the simulation every this many nanoseconds of simulated time; zero
+    // disables the snapshots.
+    pub snapshot_interval_ns: u64,
+
+    // The distribution of the service times; fixed or exponential.
+    pub service_time_distribution: ServiceTimeDistribution,
+
+    // The mean service time in micro-seconds, for the exponential distribution.
+    pub service_time_mean_us: f64,
+
+    // The renewal process of the request arrivals, at req_rate on average.
+    pub arrival_process: ArrivalProcess,
+
+    // The tenant classes; if empty, all the tenants are treated the same.
+    #[serde(default)]
+    pub tenant_classes: Vec<TenantClass>,
+
+    // The order in which the tenants within a class are picked.
+    pub intra_class_policy: IntraClassPolicy,
+
+    // The weight of each tenant's p99 latency in the objective; if empty, all the weights are 1.
+    #[serde(default)]
+    pub tenant_weights: Vec<f64>,
 }
 
 impl Config {
@@ -86,4 +283,844 @@ impl Config {
             }
         }
     }
+
+    /// This method loads the configuration file passed with `--config <path>`, or config.toml if
+    /// there is none, and then applies each `--set key=value` override on top of it; the
+    /// overrides let a script sweep a parameter without writing a config file for each run.
+    /// `--replay-speed <f64>` is a shorthand for `--set trace_speedup=<f64>`.
+    ///
+    /// # Arguments
+    /// `args`: The command-line arguments; the arguments other than these three are ignored.
+    ///
+    /// # Return
+    /// The configuration, or an error if the file or one of the overrides is invalid.
+    pub fn from_cli_args(args: &[String]) -> Result<Config, String> {
+        let mut filename: &str = "config.toml";
+        let mut overrides = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            if args[i] == "--config" || args[i] == "--set" || args[i] == "--replay-speed" {
+                let value = args
+                    .get(i + 1)
+                    .ok_or_else(|| format!("{} needs a value", args[i]))?;
+                if args[i] == "--config" {
+                    filename = value.as_str();
+                } else if args[i] == "--replay-speed" {
+                    value
+                        .parse::<f64>()
+                        .map_err(|_| format!("--replay-speed {} must be a number", value))?;
+                    overrides.push(("trace_speedup", value.trim()));
+                } else {
+                    let mut pair = value.splitn(2, '=');
+                    match (pair.next(), pair.next()) {
+                        (Some(key), Some(value)) if !key.is_empty() => {
+                            overrides.push((key.trim(), value.trim()))
+                        }
+                        _ => return Err(format!("--set {} must be key=value", value)),
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+        }
+
+        let mut contents = String::new();
+        File::open(filename)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("Failure reading config file {}: {}", filename, e))?;
+        let mut table: toml::value::Table = toml::from_str(&contents)
+            .map_err(|e| format!("Failure parsing config file {}: {}", filename, e))?;
+
+        // A value which isn't valid TOML on its own is taken as a string; e.g. policy=Minos.
+        for (key, value) in overrides.iter() {
+            let parsed = toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+                .ok()
+                .and_then(|mut t| t.remove("value"))
+                .unwrap_or_else(|| toml::Value::String(value.to_string()));
+            table.insert(key.to_string(), parsed);
+        }
+
+        let config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Invalid override for config file {}: {}", filename, e))?;
+
+        // The unknown keys are dropped by the deserialization, so they are missing from the
+        // serialized configuration.
+        let known = toml::Value::try_from(&config).map_err(|e| e.to_string())?;
+        for (key, _) in overrides.iter() {
+            if known.get(*key).is_none() {
+                return Err(format!("--set {}: unknown configuration key", key));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Return the seed for one of the random number generators of the run, if the run is seeded.
+    /// Each generator has its own stream, so that e.g. the cores don't draw the same numbers.
+    ///
+    /// # Arguments
+    /// `stream`: The number of the generator; unique within the run.
+    pub fn stream_seed(&self, stream: u64) -> Option<u64> {
+        self.seed
+            .map(|seed| seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    /// Return a random number generator for one stream of the run; seeded by `stream_seed()`,
+    /// or from the OS without a seed.
+    pub fn rng(&self, stream: u64) -> StdRng {
+        match self.stream_seed(stream) {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// This method checks the configuration for invalid values.
+    ///
+    /// # Return
+    /// All the errors found in the configuration, not just the first one.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if self.small_cores + self.large_cores != 32 {
+            errors.push(ConfigError::InvalidCoreCount(
+                self.small_cores,
+                self.large_cores,
+            ));
+        }
+
+        if self.num_tenants == 0 {
+            errors.push(ConfigError::ZeroTenants);
+        } else if self.num_tenants < self.small_cores || self.num_tenants < self.large_cores {
+            errors.push(ConfigError::TooFewTenants(self.num_tenants));
+        } else if self.num_tenants >= std::u16::MAX as u64 {
+            errors.push(ConfigError::TooManyTenants(self.num_tenants));
+        }
+
+        if !(self.tenant_skew > 0.0) {
+            errors.push(ConfigError::InvalidTenantSkew(self.tenant_skew));
+        }
+
+        if self.num_reqs == 0 {
+            errors.push(ConfigError::ZeroRequests);
+        }
+
+        if self.req_rate == 0 {
+            errors.push(ConfigError::ZeroRequestRate);
+        }
+
+        if self.large_cores != 0 && self.policy != Policy::Minos {
+            errors.push(ConfigError::PolicyMismatch(self.policy.clone()));
+        } else if self.large_cores == 0 && self.policy != Policy::RoundRobin {
+            errors.push(ConfigError::PolicyMismatch(self.policy.clone()));
+        }
+
+        if self.num_keys == 0 {
+            errors.push(ConfigError::ZeroKeys);
+        }
+
+        if !(self.key_skew > 0.0) {
+            errors.push(ConfigError::InvalidKeySkew(self.key_skew));
+        }
+
+        if !(self.cache_speedup > 0.0 && self.cache_speedup <= 1.0) {
+            errors.push(ConfigError::InvalidCacheSpeedup(self.cache_speedup));
+        }
+
+        if self.cache_size != 0 && self.large_cores != 0 {
+            errors.push(ConfigError::CacheWithLargeCores);
+        }
+
+        if !(self.trace_speedup > 0.0) {
+            errors.push(ConfigError::InvalidTraceSpeedup(self.trace_speedup));
+        }
+
+        if !(self.smt_slowdown >= 1.0) {
+            errors.push(ConfigError::InvalidSmtSlowdown(self.smt_slowdown));
+        }
+
+        for speed in self.core_speeds.iter() {
+            if !(*speed > 0.0) {
+                errors.push(ConfigError::InvalidCoreSpeed(*speed));
+            }
+        }
+
+        if !(self.sla_us > 0.0) {
+            errors.push(ConfigError::InvalidSla(self.sla_us));
+        }
+
+        if !(self.rr_quantum_us > 0.0) {
+            errors.push(ConfigError::InvalidQuantum(self.rr_quantum_us));
+        }
+
+        if !(self.shed_fraction >= 0.0 && self.shed_fraction <= 1.0) {
+            errors.push(ConfigError::InvalidShedFraction(self.shed_fraction));
+        }
+
+        if self.burst_tenant > self.num_tenants {
+            errors.push(ConfigError::InvalidBurstTenant(self.burst_tenant));
+        }
+
+        if !(self.burst_at_us >= 0.0) {
+            errors.push(ConfigError::InvalidBurstTime(self.burst_at_us));
+        }
+
+        if !(self.batch_window_ns >= 0.0) {
+            errors.push(ConfigError::InvalidBatchWindow(self.batch_window_ns));
+        } else if self.batch_window_ns > 0.0 {
+            if self.max_batch_size == 0 {
+                errors.push(ConfigError::ZeroMaxBatchSize);
+            }
+            if !(self.batch_efficiency > 0.0 && self.batch_efficiency <= 1.0) {
+                errors.push(ConfigError::InvalidBatchEfficiency(self.batch_efficiency));
+            }
+        }
+
+        if !(self.convergence_epsilon >= 0.0) {
+            errors.push(ConfigError::InvalidConvergenceEpsilon(
+                self.convergence_epsilon,
+            ));
+        } else if self.convergence_epsilon > 0.0 && self.convergence_window == 0 {
+            errors.push(ConfigError::ZeroConvergenceWindow);
+        }
+
+        if self.service_time_distribution == ServiceTimeDistribution::Exponential {
+            if !(self.service_time_mean_us > 0.0) {
+                errors.push(ConfigError::InvalidServiceTimeMean(
+                    self.service_time_mean_us,
+                ));
+            }
+            if self.large_cores != 0 {
+                errors.push(ConfigError::ExponentialWithLargeCores);
+            }
+        }
+
+        if !(self.min_task_time_us >= 0.0) {
+            errors.push(ConfigError::InvalidMinTaskTime(self.min_task_time_us));
+        }
+
+        if let Some(timeout) = self.client_timeout_us {
+            if !(timeout >= 0.0) {
+                errors.push(ConfigError::InvalidClientTimeout(timeout));
+            }
+        }
+
+        if !self.arrival_process.is_valid() {
+            errors.push(ConfigError::InvalidArrivalProcess(self.arrival_process));
+        }
+
+        if !self.tenant_classes.is_empty() {
+            let class_tenants: u64 = self
+                .tenant_classes
+                .iter()
+                .map(|c| c.num_tenants as u64)
+                .sum();
+            if class_tenants != self.num_tenants {
+                errors.push(ConfigError::ClassTenantMismatch(
+                    class_tenants,
+                    self.num_tenants,
+                ));
+            }
+            if self.large_cores != 0 {
+                errors.push(ConfigError::ClassesWithLargeCores);
+            }
+            for class in self.tenant_classes.iter() {
+                if !(class.arrival_rate > 0.0) || !(class.service_time_mean > 0.0) {
+                    errors.push(ConfigError::InvalidTenantClass(class.class_id));
+                }
+            }
+        }
+
+        if !self.tenant_weights.is_empty() {
+            let valid = self.tenant_weights.iter().all(|weight| *weight >= 0.0)
+                && self.tenant_weights.iter().any(|weight| *weight > 0.0);
+            if self.tenant_weights.len() as u64 != self.num_tenants || !valid {
+                errors.push(ConfigError::InvalidTenantWeights);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The errors returned by `Config::validate()`; one variant for each kind of invalid field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    // The simulation needs exactly 32 cores; small cores and large cores.
+    InvalidCoreCount(u64, u64),
+
+    // No tenants to generate the requests for.
+    ZeroTenants,
+
+    // Each core needs at least one tenant.
+    TooFewTenants(u64),
+
+    // The tenant ids must fit in a u16.
+    TooManyTenants(u64),
+
+    // The zipf skew must be a positive number.
+    InvalidTenantSkew(f64),
+
+    // No requests to generate.
+    ZeroRequests,
+
+    // The request rate must be positive.
+    ZeroRequestRate,
+
+    // Large cores need the Minos policy, and RoundRobin is used otherwise.
+    PolicyMismatch(Policy),
+
+    // No keys for the requests to access.
+    ZeroKeys,
+
+    // The zipf skew must be a positive number.
+    InvalidKeySkew(f64),
+
+    // The cache speedup must be in (0, 1].
+    InvalidCacheSpeedup(f64),
+
+    // Minos classifies the tasks by the task time, which the cache would change.
+    CacheWithLargeCores,
+
+    // The trace speedup must be a positive number.
+    InvalidTraceSpeedup(f64),
+
+    // The SMT slowdown can't speed up the tasks.
+    InvalidSmtSlowdown(f64),
+
+    // Each core speed must be positive.
+    InvalidCoreSpeed(f64),
+
+    // The latency SLA must be a positive number.
+    InvalidSla(f64),
+
+    // The round-robin quantum must be a positive number.
+    InvalidQuantum(f64),
+
+    // The shed fraction must be between 0 and 1.
+    InvalidShedFraction(f64),
+
+    // The burst tenant must be one of the tenants.
+    InvalidBurstTenant(u64),
+
+    // The burst can't arrive before the start of the simulation.
+    InvalidBurstTime(f64),
+
+    // The batch window can't be negative.
+    InvalidBatchWindow(f64),
+
+    // A batch needs room for at least one request.
+    ZeroMaxBatchSize,
+
+    // Batching can't make the requests slower; the efficiency must be in (0, 1].
+    InvalidBatchEfficiency(f64),
+
+    // The convergence epsilon can't be negative.
+    InvalidConvergenceEpsilon(f64),
+
+    // The p99 needs a window of requests between two estimates.
+    ZeroConvergenceWindow,
+
+    // The mean of the exponential service times must be a positive number.
+    InvalidServiceTimeMean(f64),
+
+    // Minos classifies the tasks by the fixed task times.
+    ExponentialWithLargeCores,
+
+    // The client timeout can't be negative.
+    InvalidClientTimeout(f64),
+
+    // The task time floor can't be negative.
+    InvalidMinTaskTime(f64),
+
+    // Erlang arrivals need at least one phase, and hyperexponential ones a scv of at least 1.
+    InvalidArrivalProcess(ArrivalProcess),
+
+    // The tenant classes must add up to num_tenants.
+    ClassTenantMismatch(u64, u64),
+
+    // Minos decides the core for each task by the task time, not by the tenant class.
+    ClassesWithLargeCores,
+
+    // The arrival rate and the service time of a class must be positive numbers.
+    InvalidTenantClass(u8),
+
+    // There must be a non-negative weight for each tenant, and not all of them zero.
+    InvalidTenantWeights,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::InvalidCoreCount(small, large) => write!(
+                f,
+                "small_cores ({}) + large_cores ({}) must be equal to 32",
+                small, large
+            ),
+            ConfigError::ZeroTenants => write!(f, "num_tenants must be greater than 0"),
+            ConfigError::TooFewTenants(tenants) => write!(
+                f,
+                "num_tenants ({}) must be at least the number of cores of each type",
+                tenants
+            ),
+            ConfigError::TooManyTenants(tenants) => {
+                write!(
+                    f,
+                    "num_tenants ({}) must be less than {}",
+                    tenants,
+                    std::u16::MAX
+                )
+            }
+            ConfigError::InvalidTenantSkew(skew) => {
+                write!(f, "tenant_skew ({}) must be greater than 0", skew)
+            }
+            ConfigError::ZeroRequests => write!(f, "num_reqs must be greater than 0"),
+            ConfigError::ZeroRequestRate => write!(f, "req_rate must be greater than 0"),
+            ConfigError::PolicyMismatch(policy) => write!(
+                f,
+                "policy {:?} can't be used; large_cores need Minos, RoundRobin otherwise",
+                policy
+            ),
+            ConfigError::ZeroKeys => write!(f, "num_keys must be greater than 0"),
+            ConfigError::InvalidKeySkew(skew) => {
+                write!(f, "key_skew ({}) must be greater than 0", skew)
+            }
+            ConfigError::InvalidCacheSpeedup(speedup) => {
+                write!(f, "cache_speedup ({}) must be in (0, 1]", speedup)
+            }
+            ConfigError::CacheWithLargeCores => {
+                write!(f, "cache_size must be 0 if large_cores are used")
+            }
+            ConfigError::InvalidTraceSpeedup(speedup) => {
+                write!(f, "trace_speedup ({}) must be greater than 0", speedup)
+            }
+            ConfigError::InvalidCoreSpeed(speed) => {
+                write!(f, "core_speeds ({}) must be greater than 0", speed)
+            }
+            ConfigError::InvalidSmtSlowdown(slowdown) => {
+                write!(f, "smt_slowdown ({}) must be at least 1", slowdown)
+            }
+            ConfigError::InvalidSla(sla) => write!(f, "sla_us ({}) must be greater than 0", sla),
+            ConfigError::InvalidQuantum(quantum) => {
+                write!(f, "rr_quantum_us ({}) must be greater than 0", quantum)
+            }
+            ConfigError::InvalidShedFraction(fraction) => {
+                write!(f, "shed_fraction ({}) must be between 0 and 1", fraction)
+            }
+            ConfigError::InvalidBurstTenant(tenant) => {
+                write!(f, "burst_tenant ({}) must be at most num_tenants", tenant)
+            }
+            ConfigError::InvalidBurstTime(time) => {
+                write!(f, "burst_at_us ({}) must be at least 0", time)
+            }
+            ConfigError::InvalidBatchWindow(window) => {
+                write!(f, "batch_window_ns ({}) must be at least 0", window)
+            }
+            ConfigError::ZeroMaxBatchSize => write!(
+                f,
+                "max_batch_size must be greater than 0 if batch_window_ns is set"
+            ),
+            ConfigError::InvalidBatchEfficiency(efficiency) => {
+                write!(f, "batch_efficiency ({}) must be in (0, 1]", efficiency)
+            }
+            ConfigError::InvalidConvergenceEpsilon(epsilon) => {
+                write!(f, "convergence_epsilon ({}) must be at least 0", epsilon)
+            }
+            ConfigError::ZeroConvergenceWindow => write!(
+                f,
+                "convergence_window must be greater than 0 if convergence_epsilon is set"
+            ),
+            ConfigError::InvalidServiceTimeMean(mean) => {
+                write!(f, "service_time_mean_us ({}) must be greater than 0", mean)
+            }
+            ConfigError::ExponentialWithLargeCores => write!(
+                f,
+                "service_time_distribution must be fixed if large_cores are used"
+            ),
+            ConfigError::InvalidMinTaskTime(time) => {
+                write!(f, "min_task_time_us ({}) must be at least 0", time)
+            }
+            ConfigError::InvalidClientTimeout(timeout) => {
+                write!(f, "client_timeout_us ({}) must be at least 0", timeout)
+            }
+            ConfigError::InvalidArrivalProcess(process) => write!(
+                f,
+                "arrival_process ({:?}) needs k > 0 for erlang and scv >= 1 for hyperexponential",
+                process
+            ),
+            ConfigError::ClassTenantMismatch(class_tenants, tenants) => write!(
+                f,
+                "tenant_classes have {} tenants, but num_tenants is {}",
+                class_tenants, tenants
+            ),
+            ConfigError::ClassesWithLargeCores => {
+                write!(f, "tenant_classes can't be used with large_cores")
+            }
+            ConfigError::InvalidTenantClass(class) => write!(
+                f,
+                "tenant class {} must have a positive arrival_rate and service_time_mean",
+                class
+            ),
+            ConfigError::InvalidTenantWeights => write!(
+                f,
+                "tenant_weights must have a non-negative weight for each tenant, not all zero"
+            ),
+        }
+    }
+}
+
+/// Return a small configuration, which the unit tests can run in a few milliseconds.
+#[cfg(test)]
+pub fn test_config() -> Config {
+    Config {
+        small_cores: 32,
+        large_cores: 0,
+        num_tenants: 32,
+        tenant_skew: 0.9,
+        num_reqs: 1000,
+        num_resps: 1000,
+        num_requests: 0,
+        req_rate: 100000,
+        batching: true,
+        isolation: Isolation::PageTableIsolation,
+        distribution: Distribution::Uniform,
+        policy: Policy::RoundRobin,
+        num_keys: 1000,
+        key_skew: 0.99,
+        cache_size: 0,
+        cache_speedup: 0.1,
+        check_ordering: false,
+        verbose: false,
+        client_output: false,
+        measure_sched_overhead: false,
+        progress_interval: 0,
+        client_timeout_us: None,
+        min_task_time_us: 0.0,
+        max_preemptions: 0,
+        seed: None,
+        trace_file: None,
+        trace_speedup: 1.0,
+        trace_base_us: 1.0,
+        trace_us_per_byte: 0.0,
+        request_size_bytes: 0,
+        dispatch_base_us: 0.0,
+        dispatch_us_per_byte: 0.0,
+        smt_slowdown: 1.0,
+        core_speeds: Vec::new(),
+        critical_tenants: Vec::new(),
+        sla_us: 100.0,
+        rr_quantum_us: 5.0,
+        coalesce_requests: false,
+        overload_high_water: 0,
+        overload_window_us: 100.0,
+        shed_fraction: 0.5,
+        burst_tenant: 0,
+        burst_at_us: 0.0,
+        burst_size: 0,
+        batch_window_ns: 0.0,
+        max_batch_size: 8,
+        batch_efficiency: 0.8,
+        convergence_epsilon: 0.0,
+        convergence_window: 10000,
+        max_concurrent_tasks: 0,
+        snapshot_interval_ns: 0,
+        service_time_distribution: ServiceTimeDistribution::Fixed,
+        service_time_mean_us: 1.0,
+        arrival_process: ArrivalProcess::Deterministic,
+        tenant_classes: Vec::new(),
+        intra_class_policy: IntraClassPolicy::RoundRobin,
+        tenant_weights: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn errors(config: &Config) -> Vec<ConfigError> {
+        config.validate().err().unwrap_or(Vec::new())
+    }
+
+    #[test]
+    fn test_valid_config() {
+        assert_eq!(test_config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_cli_overrides() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        let base = Config::from_cli_args(&args(&["simulator", "--config", "config.toml"])).unwrap();
+        assert_eq!(base.policy, Policy::RoundRobin);
+
+        // The overrides take precedence over the values in the file.
+        let config = Config::from_cli_args(&args(&[
+            "simulator",
+            "--set",
+            "req_rate=50000",
+            "--config",
+            "config.toml",
+            "--set",
+            "policy=Minos",
+            "--check-ordering",
+        ]))
+        .unwrap();
+        assert_ne!(base.req_rate, 50000);
+        assert_eq!(config.req_rate, 50000);
+        assert_eq!(config.policy, Policy::Minos);
+        assert_eq!(config.num_tenants, base.num_tenants);
+
+        assert!(Config::from_cli_args(&args(&["simulator", "--set", "req_rate"])).is_err());
+        assert!(Config::from_cli_args(&args(&["simulator", "--set", "arrival_rate=1"])).is_err());
+        assert!(Config::from_cli_args(&args(&["simulator", "--set", "req_rate=fast"])).is_err());
+
+        let config = Config::from_cli_args(&args(&["simulator", "--replay-speed", "0.5"])).unwrap();
+        assert_eq!(config.trace_speedup, 0.5);
+        assert!(Config::from_cli_args(&args(&["simulator", "--replay-speed", "2x"])).is_err());
+    }
+
+    #[test]
+    fn test_core_count() {
+        let mut config = test_config();
+        config.small_cores = 16;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidCoreCount(16, 0)]);
+    }
+
+    #[test]
+    fn test_tenants() {
+        let mut config = test_config();
+        config.num_tenants = 0;
+        assert_eq!(errors(&config), vec![ConfigError::ZeroTenants]);
+
+        config.num_tenants = 31;
+        assert_eq!(errors(&config), vec![ConfigError::TooFewTenants(31)]);
+
+        config.num_tenants = 65535;
+        assert_eq!(errors(&config), vec![ConfigError::TooManyTenants(65535)]);
+    }
+
+    #[test]
+    fn test_tenant_skew() {
+        let mut config = test_config();
+        config.tenant_skew = 0.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidTenantSkew(0.0)]);
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut config = test_config();
+        config.num_reqs = 0;
+        assert_eq!(errors(&config), vec![ConfigError::ZeroRequests]);
+    }
+
+    #[test]
+    fn test_request_rate() {
+        let mut config = test_config();
+        config.req_rate = 0;
+        assert_eq!(errors(&config), vec![ConfigError::ZeroRequestRate]);
+    }
+
+    #[test]
+    fn test_policy() {
+        let mut config = test_config();
+        config.policy = Policy::Minos;
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::PolicyMismatch(Policy::Minos)]
+        );
+
+        config.small_cores = 31;
+        config.large_cores = 1;
+        config.policy = Policy::RoundRobin;
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::PolicyMismatch(Policy::RoundRobin)]
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut config = test_config();
+        config.num_keys = 0;
+        config.key_skew = -1.0;
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::ZeroKeys, ConfigError::InvalidKeySkew(-1.0)]
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut config = test_config();
+        config.cache_speedup = 1.5;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidCacheSpeedup(1.5)]);
+
+        config.cache_speedup = 0.1;
+        config.cache_size = 100;
+        config.small_cores = 31;
+        config.large_cores = 1;
+        config.policy = Policy::Minos;
+        assert_eq!(errors(&config), vec![ConfigError::CacheWithLargeCores]);
+    }
+
+    #[test]
+    fn test_trace_speedup() {
+        let mut config = test_config();
+        config.trace_speedup = 0.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidTraceSpeedup(0.0)]);
+    }
+
+    #[test]
+    fn test_smt_slowdown() {
+        let mut config = test_config();
+        config.smt_slowdown = 0.5;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidSmtSlowdown(0.5)]);
+    }
+
+    #[test]
+    fn test_sla() {
+        let mut config = test_config();
+        config.sla_us = 0.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidSla(0.0)]);
+    }
+
+    #[test]
+    fn test_rr_quantum() {
+        let mut config = test_config();
+        config.rr_quantum_us = -1.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidQuantum(-1.0)]);
+    }
+
+    #[test]
+    fn test_shed_fraction() {
+        let mut config = test_config();
+        config.shed_fraction = 1.5;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidShedFraction(1.5)]);
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut config = test_config();
+        config.burst_tenant = 33;
+        config.burst_at_us = -1.0;
+        assert_eq!(
+            errors(&config),
+            vec![
+                ConfigError::InvalidBurstTenant(33),
+                ConfigError::InvalidBurstTime(-1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch_window() {
+        let mut config = test_config();
+        config.batch_window_ns = 1000.0;
+        config.max_batch_size = 0;
+        config.batch_efficiency = 1.5;
+        assert_eq!(
+            errors(&config),
+            vec![
+                ConfigError::ZeroMaxBatchSize,
+                ConfigError::InvalidBatchEfficiency(1.5)
+            ]
+        );
+
+        config.batch_window_ns = -1.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidBatchWindow(-1.0)]);
+    }
+
+    #[test]
+    fn test_convergence() {
+        let mut config = test_config();
+        config.convergence_epsilon = -0.1;
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::InvalidConvergenceEpsilon(-0.1)]
+        );
+
+        config.convergence_epsilon = 0.01;
+        config.convergence_window = 0;
+        assert_eq!(errors(&config), vec![ConfigError::ZeroConvergenceWindow]);
+    }
+
+    #[test]
+    fn test_service_time_mean() {
+        let mut config = test_config();
+        config.service_time_distribution = ServiceTimeDistribution::Exponential;
+        config.service_time_mean_us = 0.0;
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::InvalidServiceTimeMean(0.0)]
+        );
+    }
+
+    #[test]
+    fn test_arrival_process() {
+        let mut config = test_config();
+        config.arrival_process = ArrivalProcess::Erlang { k: 0 };
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::InvalidArrivalProcess(config.arrival_process)]
+        );
+
+        config.arrival_process = ArrivalProcess::Hyperexponential { scv: 4.0 };
+        assert_eq!(errors(&config), vec![]);
+    }
+
+    #[test]
+    fn test_tenant_classes() {
+        let class = |class_id: u8, num_tenants: u32| TenantClass {
+            class_id: class_id,
+            priority: class_id,
+            arrival_rate: 1.0,
+            service_time_mean: 1.0,
+            num_tenants: num_tenants,
+        };
+
+        let mut config = test_config();
+        config.tenant_classes = vec![class(0, 16), class(1, 16)];
+        assert!(config.validate().is_ok());
+
+        config.tenant_classes = vec![class(0, 16), class(1, 8)];
+        assert_eq!(
+            errors(&config),
+            vec![ConfigError::ClassTenantMismatch(24, 32)]
+        );
+
+        config.tenant_classes = vec![class(0, 16), class(1, 16)];
+        config.tenant_classes[1].service_time_mean = 0.0;
+        assert_eq!(errors(&config), vec![ConfigError::InvalidTenantClass(1)]);
+    }
+
+    #[test]
+    fn test_tenant_weights() {
+        let mut config = test_config();
+        config.tenant_weights = vec![1.0; 32];
+        assert!(config.validate().is_ok());
+
+        config.tenant_weights = vec![1.0; 16];
+        assert_eq!(errors(&config), vec![ConfigError::InvalidTenantWeights]);
+
+        config.tenant_weights = vec![0.0; 32];
+        assert_eq!(errors(&config), vec![ConfigError::InvalidTenantWeights]);
+    }
+
+    #[test]
+    fn test_all_errors_reported() {
+        let mut config = test_config();
+        config.num_tenants = 0;
+        config.req_rate = 0;
+        config.num_reqs = 0;
+        assert_eq!(
+            errors(&config),
+            vec![
+                ConfigError::ZeroTenants,
+                ConfigError::ZeroRequests,
+                ConfigError::ZeroRequestRate
+            ]
+        );
+    }
 }
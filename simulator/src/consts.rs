@@ -54,3 +54,6 @@ pub const LARGE_QUNATA_TIME: f64 = 50.0;
 // Time distribution for short-running and long-running tasks.
 // Short-running tasks take 1 us and long running tasks take 1 ms.
 pub const TASK_DISTRIBUTION_TIME: [f64; 2] = [1.0, 1.0];
+
+// The number of waiting tasks printed along with the scheduler state on an ordering violation.
+pub const DEBUG_NEXT_TASKS: usize = 8;
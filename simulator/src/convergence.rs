@@ -0,0 +1,103 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+/// Decides when the p99 latency of a run has converged, so that the run can stop early. The p99
+/// over all the latencies so far is computed once every `window` completed requests, and the run
+/// has converged once it changes by less than `epsilon`, relative to the previous estimate.
+pub struct ConvergenceDetector {
+    // The largest relative change in the p99 between two estimates for it to have converged.
+    epsilon: f64,
+
+    // The number of completed requests between two estimates.
+    window: u64,
+
+    // The number of completed requests at which the next estimate is due.
+    next_check: u64,
+
+    // The previous estimate of the p99 in cycles.
+    last_p99: Option<u64>,
+
+    // The number of completed requests at which the p99 converged, if it did.
+    pub converged_at: Option<u64>,
+}
+
+impl ConvergenceDetector {
+    /// Create a detector.
+    ///
+    /// # Arguments
+    /// `epsilon`: The largest relative change in the p99 between two estimates to converge.
+    /// `window`: The number of completed requests between two estimates.
+    pub fn new(epsilon: f64, window: u64) -> ConvergenceDetector {
+        ConvergenceDetector {
+            epsilon: epsilon,
+            window: window,
+            next_check: window,
+            last_p99: None,
+            converged_at: None,
+        }
+    }
+
+    /// This method updates the p99 estimate, if one is due.
+    ///
+    /// # Arguments
+    /// `latencies`: All the latencies recorded so far, in cycles.
+    ///
+    /// # Return
+    /// True once the p99 has converged.
+    pub fn update(&mut self, latencies: &[u64]) -> bool {
+        if self.converged_at.is_some() {
+            return true;
+        }
+        let completed = latencies.len() as u64;
+        if completed < self.next_check {
+            return false;
+        }
+        self.next_check = completed + self.window;
+
+        let mut sorted = latencies.to_vec();
+        let index = (sorted.len() * 99) / 100;
+        let p99 = *sorted.select_nth_unstable(index).1;
+        if let Some(last) = self.last_p99 {
+            let change = (p99 as f64 - last as f64).abs() / std::cmp::max(last, 1) as f64;
+            if change < self.epsilon {
+                self.converged_at = Some(completed);
+            }
+        }
+        self.last_p99 = Some(p99);
+        self.converged_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_converges_on_stable_p99() {
+        let mut detector = ConvergenceDetector::new(0.01, 1000);
+        let mut latencies = Vec::new();
+
+        // The p99 moves while the latencies keep growing, and settles once they repeat.
+        for i in 0..3000 {
+            latencies.push(i);
+            assert!(!detector.update(&latencies));
+        }
+        while !detector.update(&latencies) {
+            latencies.push(latencies.len() as u64 % 3000);
+        }
+        assert_eq!(detector.converged_at, Some(4000));
+        assert!(detector.update(&latencies));
+    }
+}
@@ -13,25 +13,45 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
-use super::config::{Config, Distribution as Dist, Isolation, Policy};
+use super::arrival_batch::{ArrivalBatcher, PendingRequest};
+use super::cache::CacheSimulator;
+use super::classes::{self, ClassQueue};
+use super::config::{
+    Config, Distribution as Dist, IntraClassPolicy, Isolation, Policy, ServiceTimeDistribution,
+};
 use super::consts;
+use super::convergence::ConvergenceDetector;
 use super::cycles;
-use super::dispatcher::Dispatch;
+use super::decisionlog::{DecisionLog, DecisionReason, DecisionRecord};
+use super::dispatcher::{Dispatch, STREAMS_PER_CORE};
+use super::fairness::{self, NoisyNeighborDetector};
+use super::faults::TenantBurst;
 use super::minos_sched::Minos;
-use super::request::{Request, TaskState};
+use super::overload::OverloadDetector;
+use super::progress::ProgressReporter;
+use super::replay::{Arrival, CapturedWorkload, PcapReplay, TaskTimeModel};
+use super::request::{DispatchCost, Request, TaskState};
 use super::rr_sched::RoundRobin;
-use super::tenant::Tenant;
+use super::snapshot::{IntervalSample, SimulationSnapshot};
+use super::taskgen::{ExponentialTaskGen, TaskGenerator};
+use super::tenant::{SchedOverhead, Tenant};
+use super::tracelog::{TraceLog, TraceRecord};
+use super::workers::WorkerPool;
 
 use std::cell::RefCell;
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
 use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use rand::distributions::weighted::alias_method::WeightedIndex;
 use rand::distributions::Distribution;
 use rand::prelude::*;
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
+use zipf::ZipfDistribution;
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum CoreType {
@@ -39,27 +59,248 @@ pub enum CoreType {
     Large = 0x2,
 }
 
+/// The aggregated results of a simulation run; returned by `Simulator::start()`.
+#[derive(Clone, Debug, Default)]
+pub struct SimulationResult {
+    // Total number of requests processed across all the cores.
+    pub requests_processed: u64,
+
+    // Total number of requests which hit in the per-core caches.
+    pub cache_hits: u64,
+
+    // Total number of requests which missed in the per-core caches.
+    pub cache_misses: u64,
+
+    // The fraction of requests which hit in the per-core caches.
+    pub cache_hit_rate: f64,
+
+    // Total time in cycles the completed requests spent waiting before the first dispatch.
+    pub queueing_cycles: u64,
+
+    // Total time in cycles the completed requests spent from the first dispatch to completion.
+    pub service_cycles: u64,
+
+    // The latency statistics for each tenant.
+    pub tenant_stats: HashMap<u16, TenantStats>,
+
+    // The latency statistics for each tenant in each second of simulated time; keyed by the
+    // tenant and the interval.
+    pub interval_stats: HashMap<(u16, u64), TenantStats>,
+
+    // The tenants which missed their SLA only while another tenant ran; (victim, culprit).
+    pub noisy_neighbor_report: Vec<(u16, u16)>,
+
+    // The state of the simulation in each snapshot interval, in the order of the simulated time.
+    pub snapshots: Vec<SimulationSnapshot>,
+
+    // The 99th percentile latency in cycles for each tenant class.
+    pub class_p99: HashMap<u8, u64>,
+
+    // The latency percentiles for all the completed requests.
+    pub latency: LatencyPercentiles,
+
+    // The latency percentiles for the requests with each label.
+    pub label_percentiles: HashMap<u32, LatencyPercentiles>,
+
+    // The latency percentiles for the requests completed on each core.
+    pub core_percentiles: HashMap<u8, LatencyPercentiles>,
+
+    // The 99th percentile latency in cycles for each tenant.
+    pub tenant_p99: HashMap<u16, u64>,
+
+    // The mean of the tenants' p99 latencies in micro-seconds, weighted by the tenant_weights.
+    pub weighted_p99: f64,
+
+    // Total number of arrivals shed by the overloaded cores.
+    pub requests_shed: u64,
+
+    // Total number of requests cancelled by the client before they completed.
+    pub cancelled_count: u64,
+
+    // Total number of task times clamped up to min_task_time_us.
+    pub clamped_tasks: u64,
+
+    // The wall-clock time spent in the scheduler calls of all the tenants; only measured with
+    // measure_sched_overhead.
+    pub sched_overhead: SchedOverhead,
+
+    // Total time in cycles the cores spent in overload, summed over the cores.
+    pub overload_cycles: u64,
+
+    // The deepest queue seen on any core; only tracked with the overload detection.
+    pub max_queue_depth: usize,
+
+    // The number of requests injected by the tenant burst.
+    pub burst_requests: u64,
+
+    // The number of server-side batches dispatched.
+    pub batches: u64,
+
+    // The number of completed requests after which the p99 latency converged and the run
+    // stopped; none if it didn't converge.
+    pub converged_after: Option<u64>,
+
+    // The simulated time in cycles until the last core stopped.
+    pub elapsed_cycles: u64,
+
+    // The time-averaged number of requests in the system(L), summed over the cores.
+    pub mean_queue_depth: f64,
+
+    // The arrival rate times the mean time in the system(lambda * W), summed over the cores;
+    // equal to `mean_queue_depth` by Little's Law.
+    pub littles_law_depth: f64,
+}
+
+impl SimulationResult {
+    /// Return the relative difference between the two sides of Little's Law, L = lambda * W; a
+    /// large difference means the simulation didn't reach a steady state, e.g. a load above 1.
+    pub fn littles_law_error(&self) -> f64 {
+        if self.mean_queue_depth == 0.0 {
+            return 0.0;
+        }
+        (self.mean_queue_depth - self.littles_law_depth).abs() / self.mean_queue_depth
+    }
+
+    /// Return the stats in the client's text format, so that the same scripts parse the results
+    /// of both; the throughput in requests per second, then the median and 99th percentile
+    /// latency in nano-seconds.
+    pub fn to_client_text(&self) -> String {
+        let mut throughput = 0.0;
+        if self.elapsed_cycles != 0 {
+            throughput = self.requests_processed as f64 / cycles::to_seconds(self.elapsed_cycles);
+        }
+        format!(
+            "Throughput {}\n>>> {} {}",
+            throughput,
+            cycles::to_microseconds(self.latency.median) * 1e3,
+            cycles::to_microseconds(self.latency.p99) * 1e3
+        )
+    }
+
+    /// Return the mean time in micro-seconds from the arrival to the completion of a request.
+    pub fn mean_latency_us(&self) -> f64 {
+        if self.requests_processed == 0 {
+            return 0.0;
+        }
+        cycles::to_microseconds(self.queueing_cycles + self.service_cycles)
+            / self.requests_processed as f64
+    }
+}
+
+/// The median and tail latency of a group of requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LatencyPercentiles {
+    // The number of requests in the group.
+    pub count: u64,
+
+    // The median latency in cycles.
+    pub median: u64,
+
+    // The 99th percentile latency in cycles.
+    pub p99: u64,
+}
+
+impl LatencyPercentiles {
+    /// Return the percentiles for the given latencies; sorts them in place.
+    pub fn new(latencies: &mut [u64]) -> LatencyPercentiles {
+        if latencies.is_empty() {
+            return LatencyPercentiles::default();
+        }
+
+        latencies.sort();
+        let len = latencies.len();
+        LatencyPercentiles {
+            count: len as u64,
+            median: latencies[len / 2],
+            p99: latencies[(len * 99) / 100],
+        }
+    }
+}
+
+/// The latency statistics for the requests completed for a tenant.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TenantStats {
+    // The number of requests completed for this tenant.
+    pub completed: u64,
+
+    // The sum of the latencies in cycles of the completed requests.
+    pub total_latency: u64,
+}
+
+impl TenantStats {
+    pub fn record(&mut self, latency: u64) {
+        self.completed += 1;
+        self.total_latency += latency;
+    }
+
+    pub fn merge(&mut self, other: &TenantStats) {
+        self.completed += other.completed;
+        self.total_latency += other.total_latency;
+    }
+
+    /// Return the mean latency in cycles.
+    pub fn mean_latency(&self) -> f64 {
+        if self.completed == 0 {
+            return 0.0;
+        }
+        self.total_latency as f64 / self.completed as f64
+    }
+}
+
 pub struct Simulator {
     config: Config,
     cores: Vec<Core>,
     latencies: Vec<u64>,
     tenants: HashMap<u64, Arc<RefCell<Tenant>>>,
+    replay: Option<PcapReplay>,
+
+    // If set, the arrivals for all the other tenants are dropped.
+    isolated_tenant: Option<u16>,
+
+    // If true, the arrivals on each core are kept for `captured_workload()`.
+    capture_workload: bool,
+
+    // If set, each core replays its arrivals from this workload instead of generating them.
+    workload: Option<CapturedWorkload>,
+
+    // If set, the tasks on all the cores share this many worker threads.
+    worker_pool: Option<Rc<RefCell<WorkerPool>>>,
+
+    // If set, the timeline of each completed request is written to this log.
+    trace_log: Option<TraceLog>,
+
+    // If set, each task picked by a core is written to this log.
+    decision_log: Option<DecisionLog>,
+
+    // If set, the run stops once the p99 latency converges.
+    convergence: Option<ConvergenceDetector>,
+
+    // If set, the progress of the run is printed periodically.
+    progress: Option<ProgressReporter>,
 }
 
 impl Simulator {
     pub fn new() -> Simulator {
-        let config = Config::load();
+        Simulator::with_config(Config::load())
+    }
+
+    pub fn with_config(config: Config) -> Simulator {
         info!("Starting the Simulator with config {:?}\n", config);
+        info!(
+            "Simulated clock frequency {} MHz\n",
+            cycles::frequency_mhz()
+        );
         let mut tenants = HashMap::with_capacity(config.num_tenants as usize);
         for i in 1..config.num_tenants + 1 {
             match config.policy {
                 Policy::RoundRobin => {
+                    let mut rr = RoundRobin::new(config.rr_quantum_us);
+                    if config.coalesce_requests {
+                        rr.coalesce_by_key();
+                    }
                     tenants.insert(
                         i,
-                        Arc::new(RefCell::new(Tenant::new(
-                            i as u16,
-                            Box::new(RoundRobin::new()),
-                        ))),
+                        Arc::new(RefCell::new(Tenant::new(i as u16, Box::new(rr)))),
                     );
                 }
                 Policy::Minos => {
@@ -70,14 +311,160 @@ impl Simulator {
                 }
             }
         }
+        if config.measure_sched_overhead {
+            for tenant in tenants.values() {
+                tenant.borrow_mut().measure_overhead();
+            }
+        }
         let max_cores = config.small_cores as usize;
         let num_reqs = config.num_reqs as usize;
 
+        let mut replay = None;
+        if let Some(ref trace_file) = config.trace_file {
+            // Anything other than a pcap file is read as a `timestamp_us,size_bytes` text trace.
+            let trace = if trace_file.ends_with(".pcap") {
+                PcapReplay::open(trace_file, config.trace_speedup, config.num_tenants)
+                    .map_err(|e| e.to_string())
+            } else {
+                let model = TaskTimeModel {
+                    base_us: config.trace_base_us,
+                    us_per_byte: config.trace_us_per_byte,
+                };
+                PcapReplay::open_text(trace_file, config.trace_speedup, config.num_tenants, model)
+                    .map_err(|e| e.to_string())
+            };
+            match trace {
+                Ok(trace) => {
+                    info!("Replaying {} requests from {}\n", trace.len(), trace_file);
+                    replay = Some(trace);
+                }
+                Err(e) => panic!("Failure reading trace file {}: {}", trace_file, e),
+            }
+        }
+
+        let mut worker_pool = None;
+        if config.max_concurrent_tasks != 0 {
+            worker_pool = Some(Rc::new(RefCell::new(WorkerPool::new(
+                config.max_concurrent_tasks,
+            ))));
+        }
+
+        let mut convergence = None;
+        if config.convergence_epsilon > 0.0 {
+            convergence = Some(ConvergenceDetector::new(
+                config.convergence_epsilon,
+                config.convergence_window,
+            ));
+        }
+
+        let mut progress = None;
+        if config.verbose {
+            progress = Some(ProgressReporter::new(config.progress_interval));
+        }
+
         Simulator {
             config: config,
             cores: Vec::with_capacity(max_cores),
             latencies: Vec::with_capacity(max_cores * num_reqs),
             tenants: tenants,
+            replay: replay,
+            isolated_tenant: None,
+            worker_pool: worker_pool,
+            capture_workload: false,
+            workload: None,
+            trace_log: None,
+            decision_log: None,
+            convergence: convergence,
+            progress: progress,
+        }
+    }
+
+    /// Run only the given tenant; the arrivals for all the other tenants are dropped. Used to
+    /// measure the baseline latency for a tenant without any neighbors.
+    pub fn isolate_tenant(&mut self, tenant: u16) {
+        self.isolated_tenant = Some(tenant);
+    }
+
+    /// Keep the arrivals generated on each core during the run; see `captured_workload()`.
+    pub fn capture_workload(&mut self) {
+        self.capture_workload = true;
+    }
+
+    /// Return the arrivals on each core during the run, if they were captured.
+    pub fn captured_workload(&self) -> Option<CapturedWorkload> {
+        if !self.capture_workload {
+            return None;
+        }
+        let cores = self
+            .cores
+            .iter()
+            .map(|core| core.captured.clone().unwrap_or_default())
+            .collect();
+        Some(CapturedWorkload { cores: cores })
+    }
+
+    /// Replay the arrivals captured in an earlier run with the same number of cores, instead of
+    /// generating them; all the runs with the workload see the identical arrivals, tenants and
+    /// task times. Takes precedence over the trace_file.
+    pub fn replay_workload(&mut self, workload: CapturedWorkload) {
+        self.workload = Some(workload);
+    }
+
+    /// Write a line of JSON to the file at `path` for each completed request; see tracelog.rs.
+    pub fn set_trace_log(&mut self, path: &Path) -> io::Result<()> {
+        self.trace_log = Some(TraceLog::create(path)?);
+        Ok(())
+    }
+
+    /// Write a line of JSON to the file at `path` for each task picked by a core; see
+    /// decisionlog.rs.
+    pub fn set_decision_log(&mut self, path: &Path) -> io::Result<()> {
+        self.decision_log = Some(DecisionLog::create(path)?);
+        Ok(())
+    }
+
+    /// Return the speed of each small core, by the core id; from the `core_speeds`, or 1. With
+    /// `critical_tenants`, the speeds are sorted, and the fastest go to the cores which serve the
+    /// most latency-critical tenants.
+    pub fn core_speeds(config: &Config) -> Vec<f64> {
+        let num_cores = config.small_cores as usize;
+        let mut speeds: Vec<f64> = (0..num_cores)
+            .map(|id| config.core_speeds.get(id).cloned().unwrap_or(1.0))
+            .collect();
+        if config.critical_tenants.is_empty() {
+            return speeds;
+        }
+
+        let critical: Vec<usize> = (0..num_cores)
+            .map(|id| {
+                let (low, high) =
+                    Core::tenant_range(id as u8, config.num_tenants, config.small_cores);
+                config
+                    .critical_tenants
+                    .iter()
+                    .filter(|tenant| **tenant >= low && **tenant < high)
+                    .count()
+            })
+            .collect();
+        let mut cores: Vec<usize> = (0..num_cores).collect();
+        cores.sort_by_key(|id| std::cmp::Reverse(critical[*id]));
+        let mut sorted = speeds.clone();
+        sorted.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        for (id, speed) in cores.into_iter().zip(sorted.into_iter()) {
+            speeds[id] = speed;
+        }
+        speeds
+    }
+
+    /// Set up the capture or the replay of the workload for the next core added to the simulator.
+    fn init_workload(&self, core: &mut Core) {
+        if self.capture_workload {
+            core.captured = Some(Vec::new());
+        }
+        if let Some(ref workload) = self.workload {
+            let arrivals = workload.cores[self.cores.len()].clone();
+            core.replay = Some(PcapReplay::from_arrivals(arrivals));
+            core.replay_exact = true;
         }
     }
 
@@ -99,51 +486,297 @@ impl Simulator {
             assert_eq!(self.config.policy, Policy::RoundRobin);
         }
 
+        // Minos classifies the tasks by the task time, which the cache would change.
+        if self.config.cache_size != 0 {
+            assert_eq!(self.config.large_cores, 0);
+        }
+
+        let speeds = Simulator::core_speeds(&self.config);
         for i in 0..self.config.small_cores {
-            self.cores.push(Core::new(
+            let mut core = Core::new(
                 i as u8,
                 &self.config,
                 self.config.small_cores,
                 &self.tenants,
                 CoreType::Small,
-            ));
+            );
+
+            core.speed = speeds[i as usize];
+
+            // The trace is replayed only on the small cores; large cores keep their dispatcher.
+            if let Some(ref replay) = self.replay {
+                core.set_replay(replay.clone());
+            }
+            core.isolated_tenant = self.isolated_tenant;
+            core.worker_pool = self.worker_pool.clone();
+            if self.trace_log.is_some() {
+                core.trace_records = Some(Vec::new());
+            }
+            if self.decision_log.is_some() {
+                core.decision_records = Some(Vec::new());
+            }
+            self.init_workload(&mut core);
+            self.cores.push(core);
         }
 
         // Start from zero as the partition is relative and won't work if started from absolute core-id.
         for i in 0..self.config.large_cores {
-            self.cores.push(Core::new(
+            let mut core = Core::new(
                 i as u8,
                 &self.config,
                 self.config.large_cores,
                 &self.tenants,
                 CoreType::Large,
-            ));
+            );
+            core.isolated_tenant = self.isolated_tenant;
+            core.worker_pool = self.worker_pool.clone();
+            if self.trace_log.is_some() {
+                core.trace_records = Some(Vec::new());
+            }
+            if self.decision_log.is_some() {
+                core.decision_records = Some(Vec::new());
+            }
+            self.init_workload(&mut core);
+            self.cores.push(core);
         }
     }
 
-    pub fn start(&mut self) {
+    pub fn start(&mut self) -> SimulationResult {
         self.core_init();
+        let mut processed = 0;
         loop {
             // Run each core one by one.
             for c in 0..(self.config.small_cores + self.config.large_cores) {
-                self.cores[c as usize].run();
+                let mut budget = std::u64::MAX;
+                if self.config.num_requests != 0 {
+                    budget = self.config.num_requests - processed;
+                }
+                let sibling_busy = match Simulator::sibling(c as usize, self.cores.len()) {
+                    Some(sibling) => self.cores[sibling].is_busy(),
+                    None => false,
+                };
+                self.cores[c as usize].sibling_busy = sibling_busy;
+                processed += self.cores[c as usize].run(budget);
                 let mut latency: Vec<u64> = self.cores[c as usize].latencies.drain(..).collect();
                 self.latencies.append(&mut latency);
+                if let Some(ref mut log) = self.trace_log {
+                    if let Some(ref mut records) = self.cores[c as usize].trace_records {
+                        for record in records.drain(..) {
+                            log.write(&record).expect("Failure writing the trace log");
+                        }
+                    }
+                }
+                if let Some(ref mut log) = self.decision_log {
+                    if let Some(ref mut records) = self.cores[c as usize].decision_records {
+                        for record in records.drain(..) {
+                            log.write(&record)
+                                .expect("Failure writing the decision log");
+                        }
+                    }
+                }
+            }
+
+            if let Some(ref mut progress) = self.progress {
+                let completed = self.cores.iter().map(|core| core.request_processed).sum();
+                let now = self
+                    .cores
+                    .iter()
+                    .map(|core| core.rdtsc())
+                    .max()
+                    .unwrap_or(0);
+                let depth = self.cores.iter().map(|core| core.outstanding as u64).sum();
+                if let Some(line) = progress.report(completed, now, depth) {
+                    println!("{}", line);
+                }
             }
 
             // Check exit condition after each iteration.
             let mut exit = true;
-            for c in 0..self.config.small_cores {
-                if self.config.num_resps > self.cores[c as usize].request_processed {
-                    exit = false;
+            if self.config.num_requests != 0 {
+                exit = processed >= self.config.num_requests;
+            } else {
+                for c in 0..self.config.small_cores {
+                    if self.config.num_resps > self.cores[c as usize].request_processed {
+                        exit = false;
+                    }
+                }
+            }
+
+            // Stop if no core can make any more progress.
+            if self.cores.iter().all(|core| core.is_idle()) {
+                exit = true;
+            }
+
+            // Stop once the p99 latency has converged.
+            if let Some(ref mut convergence) = self.convergence {
+                if convergence.update(&self.latencies) {
+                    exit = true;
                 }
             }
+
             if exit == true {
                 info!("Request generation completed !!!\n");
-                return;
+                if let Some(ref mut log) = self.trace_log {
+                    log.flush().expect("Failure writing the trace log");
+                }
+                if let Some(ref mut log) = self.decision_log {
+                    log.flush().expect("Failure writing the decision log");
+                }
+                return self.result();
             }
         }
     }
+
+    /// Return the SMT sibling for a core; cores 2n and 2n+1 share a physical core.
+    pub fn sibling(core: usize, num_cores: usize) -> Option<usize> {
+        let sibling = core ^ 1;
+        if sibling < num_cores {
+            Some(sibling)
+        } else {
+            None
+        }
+    }
+
+    /// Aggregate the per-core statistics into the result for this run.
+    pub fn result(&self) -> SimulationResult {
+        let mut result = SimulationResult::default();
+        for core in &self.cores {
+            result.requests_processed += core.request_processed;
+            result.cache_hits += core.cache.hits;
+            result.cache_misses += core.cache.misses;
+            result.queueing_cycles += core.queueing_cycles;
+            result.service_cycles += core.service_cycles;
+            if let Some(ref overload) = core.overload {
+                result.requests_shed += overload.shed;
+                result.overload_cycles += overload.overload_cycles(core.rdtsc());
+                result.max_queue_depth = std::cmp::max(result.max_queue_depth, overload.max_queue);
+            }
+            result.burst_requests += core.burst_requests;
+            result.cancelled_count += core.cancelled_count;
+            result.clamped_tasks += core.clamped_tasks;
+            result.elapsed_cycles = std::cmp::max(result.elapsed_cycles, core.rdtsc());
+            if let Some(ref batcher) = core.batcher {
+                result.batches += batcher.batches;
+            }
+            result.converged_after = self.convergence.as_ref().and_then(|c| c.converged_at);
+            if core.rdtsc() != 0 && core.request_processed != 0 {
+                let duration = core.rdtsc() as f64;
+                let wait = (core.queueing_cycles + core.service_cycles) as f64
+                    / core.request_processed as f64;
+                result.mean_queue_depth += core.queue_area(core.rdtsc()) as f64 / duration;
+                result.littles_law_depth += core.arrivals as f64 / duration * wait;
+            }
+            for (tenant, stats) in core.tenant_stats.iter() {
+                result
+                    .tenant_stats
+                    .entry(*tenant)
+                    .or_insert_with(TenantStats::default)
+                    .merge(stats);
+            }
+            for (key, stats) in core.interval_stats.iter() {
+                result
+                    .interval_stats
+                    .entry(*key)
+                    .or_insert_with(TenantStats::default)
+                    .merge(stats);
+            }
+        }
+        for tenant in self.tenants.values() {
+            if let Some(ref overhead) = tenant.borrow().overhead {
+                result.sched_overhead.merge(overhead);
+            }
+        }
+        let mut intervals: BTreeMap<u64, IntervalSample> = BTreeMap::new();
+        for core in &self.cores {
+            for (interval, sample) in core.snapshots.iter() {
+                intervals
+                    .entry(*interval)
+                    .or_insert_with(IntervalSample::default)
+                    .merge(sample);
+            }
+        }
+        for (interval, sample) in intervals.iter_mut() {
+            result.snapshots.push(SimulationSnapshot::new(
+                *interval,
+                self.config.snapshot_interval_ns,
+                sample,
+            ));
+        }
+
+        let mut class_latencies: HashMap<u8, Vec<u64>> = HashMap::new();
+        for core in &self.cores {
+            for (class, latencies) in core.class_latencies.iter() {
+                class_latencies
+                    .entry(*class)
+                    .or_insert_with(Vec::new)
+                    .extend_from_slice(latencies);
+            }
+        }
+        for (class, latencies) in class_latencies.iter_mut() {
+            latencies.sort();
+            result
+                .class_p99
+                .insert(*class, latencies[(latencies.len() * 99) / 100]);
+        }
+
+        let mut label_latencies: HashMap<u32, Vec<u64>> = HashMap::new();
+        for core in &self.cores {
+            for (label, latencies) in core.label_latencies.iter() {
+                label_latencies
+                    .entry(*label)
+                    .or_insert_with(Vec::new)
+                    .extend_from_slice(latencies);
+            }
+        }
+        for (label, latencies) in label_latencies.iter_mut() {
+            result
+                .label_percentiles
+                .insert(*label, LatencyPercentiles::new(latencies));
+        }
+
+        let mut core_latencies: HashMap<u8, Vec<u64>> = HashMap::new();
+        for core in &self.cores {
+            for (core_id, latencies) in core.core_latencies.iter() {
+                core_latencies
+                    .entry(*core_id)
+                    .or_insert_with(Vec::new)
+                    .extend_from_slice(latencies);
+            }
+        }
+        for (core_id, latencies) in core_latencies.iter_mut() {
+            result
+                .core_percentiles
+                .insert(*core_id, LatencyPercentiles::new(latencies));
+        }
+
+        let mut tenant_latencies: HashMap<u16, Vec<u64>> = HashMap::new();
+        for core in &self.cores {
+            for (tenant, latencies) in core.tenant_latencies.iter() {
+                tenant_latencies
+                    .entry(*tenant)
+                    .or_insert_with(Vec::new)
+                    .extend_from_slice(latencies);
+            }
+        }
+        let mut all_latencies: Vec<u64> = tenant_latencies.values().flatten().cloned().collect();
+        result.latency = LatencyPercentiles::new(&mut all_latencies);
+        for (tenant, latencies) in tenant_latencies.iter_mut() {
+            result
+                .tenant_p99
+                .insert(*tenant, LatencyPercentiles::new(latencies).p99);
+        }
+        result.weighted_p99 =
+            fairness::weighted_p99(&result.tenant_p99, &self.config.tenant_weights);
+
+        let sla = cycles::from_microseconds(self.config.sla_us);
+        result.noisy_neighbor_report =
+            NoisyNeighborDetector::new(sla).detect(&result.interval_stats);
+        if result.cache_hits + result.cache_misses > 0 {
+            result.cache_hit_rate =
+                result.cache_hits as f64 / (result.cache_hits + result.cache_misses) as f64;
+        }
+        result
+    }
 }
 
 pub struct Core {
@@ -183,6 +816,9 @@ pub struct Core {
     // Total number of preemptions per core.
     pub num_preemptions: u64,
 
+    // Total number of preempted tasks which resumed on this core after running on another core.
+    pub num_migrations: u64,
+
     // Isolation mechanism amoung domains on a core.
     pub isolation: Isolation,
 
@@ -204,11 +840,20 @@ pub struct Core {
     // Outstanding tasks in the queue.
     outstanding: usize,
 
+    // The number of requests which entered the queue, after the isolation and the shedding.
+    pub arrivals: u64,
+
+    // The integral of `outstanding` over the simulated time in cycles, up to `depth_changed`.
+    queue_area: u128,
+
+    // The time stamp of the last change to `outstanding`.
+    depth_changed: u64,
+
     // Distribution of short-running and long-running tasks.
     pub task_distribution: WeightedIndex<f64>,
 
     // Random number generator.
-    rng: Box<ThreadRng>,
+    rng: Box<StdRng>,
 
     // The last completed or preempted in the middle.
     last_task_state: TaskState,
@@ -218,6 +863,132 @@ pub struct Core {
 
     // If the cores are partitioned between large and small cores.
     is_core_partitioned: bool,
+
+    // The key zipf number generator.
+    key_rng_zipf: Box<ZipfDistribution>,
+
+    // Server-side cache; a hit reduces the task time by `cache_speedup`.
+    pub cache: CacheSimulator,
+
+    // The task time is multiplied by this factor if the request hits in the cache.
+    cache_speedup: f64,
+
+    // The cost model for the time to set up a request before it first runs.
+    dispatch_cost: DispatchCost,
+
+    // The size in bytes of the generated requests.
+    request_size_bytes: usize,
+
+    // If set, the client cancels the requests which didn't complete this many cycles after they
+    // arrived.
+    client_timeout: Option<u64>,
+
+    // The number of requests dropped on this core after the client cancelled them.
+    pub cancelled_count: u64,
+
+    // The shortest task time in micro-seconds; see `clamp_task_time()`.
+    min_task_time: f64,
+
+    // If true, the per-core summary isn't printed; see `Config::client_output`.
+    client_output: bool,
+
+    // The number of preemptions after which a task runs to completion; zero for no limit.
+    max_preemptions: u32,
+
+    // The number of task times clamped up to `min_task_time` on this core.
+    pub clamped_tasks: u64,
+
+    // If true, verify each task picked by the scheduler against the scheduling policy.
+    check_ordering: bool,
+
+    // Total time in cycles the completed requests spent waiting before the first dispatch.
+    pub queueing_cycles: u64,
+
+    // Total time in cycles the completed requests spent from the first dispatch to completion,
+    // including the time spent in the run-queue after preemption.
+    pub service_cycles: u64,
+
+    // If set, the requests arrive according to this trace instead of the dispatcher.
+    replay: Option<PcapReplay>,
+
+    // If true, the replayed arrivals come from a `CapturedWorkload`, and their tenants and task
+    // times are final.
+    replay_exact: bool,
+
+    // If set, each arrival on this core is kept here; see `Simulator::capture_workload()`.
+    pub captured: Option<Vec<Arrival>>,
+
+    // True if the SMT sibling of this core has outstanding tasks.
+    pub sibling_busy: bool,
+
+    // The task time is inflated by this factor when the SMT sibling is busy.
+    smt_slowdown: f64,
+
+    // The task time is divided by this factor; see `Simulator::core_speeds()`.
+    pub speed: f64,
+
+    // The latency statistics for each tenant on this core.
+    pub tenant_stats: HashMap<u16, TenantStats>,
+
+    // The latency statistics for each tenant in each second of simulated time on this core.
+    pub interval_stats: HashMap<(u16, u64), TenantStats>,
+
+    // If set, the arrivals for all the other tenants are dropped.
+    pub isolated_tenant: Option<u16>,
+
+    // If set, a task executes only while it holds one of the shared worker threads.
+    pub worker_pool: Option<Rc<RefCell<WorkerPool>>>,
+
+    // The length of each snapshot interval in cycles; zero disables the snapshots.
+    snapshot_interval: u64,
+
+    // The requests completed on this core in each snapshot interval.
+    pub snapshots: BTreeMap<u64, IntervalSample>,
+
+    // The tenant classes on this core, in the order of decreasing priority.
+    classes: Vec<ClassQueue>,
+
+    // Picks the class for each new request, by the arrival rate of the classes.
+    class_rng: Option<WeightedIndex<f64>>,
+
+    // The order in which the tenants within a class are picked.
+    intra_class_policy: IntraClassPolicy,
+
+    // The index into `classes` for each tenant on this core, by the index into `tenants`.
+    tenant_class: Vec<usize>,
+
+    // The latencies of the requests completed for each tenant class.
+    pub class_latencies: HashMap<u8, Vec<u64>>,
+
+    // If set, generates the service times instead of consts::TASK_DISTRIBUTION_TIME.
+    task_gen: Option<Box<dyn TaskGenerator>>,
+
+    // The latencies of the completed requests with each label.
+    pub label_latencies: HashMap<u32, Vec<u64>>,
+
+    // The latencies of the completed requests, by the core which served the request.
+    pub core_latencies: HashMap<u8, Vec<u64>>,
+
+    // The latencies of the completed requests for each tenant.
+    pub tenant_latencies: HashMap<u16, Vec<u64>>,
+
+    // If set, the timeline of each completed request, until the simulator writes it to the log.
+    pub trace_records: Option<Vec<TraceRecord>>,
+
+    // If set, the tasks picked by this core, until the simulator writes them to the log.
+    pub decision_records: Option<Vec<DecisionRecord>>,
+
+    // If set, a fraction of the arrivals is shed while the queue is too deep.
+    overload: Option<OverloadDetector>,
+
+    // If set, one of the tenants on this core emits a burst of requests at a scheduled time.
+    burst: Option<TenantBurst>,
+
+    // The number of requests injected by the burst, after the isolation and the shedding.
+    pub burst_requests: u64,
+
+    // If set, the arrivals wait in a server-side batch before they are added to the tenants.
+    batcher: Option<ArrivalBatcher>,
 }
 
 impl Core {
@@ -228,12 +999,7 @@ impl Core {
         tenants: &HashMap<u64, Arc<RefCell<Tenant>>>,
         coretype: CoreType,
     ) -> Core {
-        let uniform_divide: u16 = config.num_tenants as u16 / num_cores as u16;
-        let low = (id as u16 * uniform_divide) + 1 as u16;
-        let mut high = low + uniform_divide as u16;
-        if id == num_cores as u8 - 1 {
-            high = config.num_tenants as u16 + 1;
-        }
+        let (low, high) = Core::tenant_range(id, config.num_tenants, num_cores);
 
         // Partition tenants in MPK Domains.
         let tenants_per_domain = 15;
@@ -272,6 +1038,73 @@ impl Core {
             tenants_vec.push(Arc::clone(tenant));
         }
 
+        // Group the tenants on this core by their class; the higher priority classes go first.
+        let mut class_queues = Vec::new();
+        let mut tenant_class = vec![0; (high - low) as usize];
+        if !config.tenant_classes.is_empty() {
+            let assigned = classes::assign_classes(&config.tenant_classes);
+            let mut order: Vec<usize> = (0..config.tenant_classes.len()).collect();
+            order.sort_by_key(|class| std::cmp::Reverse(config.tenant_classes[*class].priority));
+            for class in order {
+                let mut queue = ClassQueue::new(config.tenant_classes[class].clone());
+                for (index, tenant) in (low..high).enumerate() {
+                    if assigned[tenant as usize - 1] == class {
+                        queue.tenants.push(index);
+                    }
+                }
+                if !queue.tenants.is_empty() {
+                    class_queues.push(queue);
+                }
+            }
+            for (position, queue) in class_queues.iter().enumerate() {
+                for index in queue.tenants.iter() {
+                    tenant_class[*index] = position;
+                }
+            }
+        }
+        let mut class_rng = None;
+        if !class_queues.is_empty() {
+            let rates = class_queues.iter().map(|queue| queue.class.arrival_rate);
+            class_rng = Some(WeightedIndex::new(rates.collect()).unwrap());
+        }
+
+        let mut task_gen: Option<Box<dyn TaskGenerator>> = None;
+        if config.service_time_distribution == ServiceTimeDistribution::Exponential {
+            let mean_s = config.service_time_mean_us / 1e6;
+            task_gen = match config.stream_seed(STREAMS_PER_CORE * id as u64 + 3) {
+                Some(seed) => Some(Box::new(ExponentialTaskGen::with_seed(mean_s, seed))),
+                None => Some(Box::new(ExponentialTaskGen::new(mean_s))),
+            };
+        }
+
+        let mut overload = None;
+        if config.overload_high_water != 0 {
+            overload = Some(OverloadDetector::new(
+                config.overload_high_water as usize,
+                cycles::from_microseconds(config.overload_window_us),
+                config.shed_fraction,
+            ));
+        }
+
+        let mut burst = None;
+        let burst_tenant = config.burst_tenant as u16;
+        if config.burst_tenant != 0 && burst_tenant >= low && burst_tenant < high {
+            burst = Some(TenantBurst::new(
+                burst_tenant,
+                cycles::from_microseconds(config.burst_at_us),
+                config.burst_size,
+            ));
+        }
+
+        let mut batcher = None;
+        if config.batch_window_ns > 0.0 {
+            batcher = Some(ArrivalBatcher::new(
+                cycles::from_microseconds(config.batch_window_ns / 1000.0),
+                config.max_batch_size as usize,
+                config.batch_efficiency,
+            ));
+        }
+
         let mut batch_size = 1;
         if config.batching == true {
             batch_size = consts::BATCH_SIZE;
@@ -302,13 +1135,14 @@ impl Core {
             rdtsc: 0,
             request_processed: 0,
             latencies: Vec::with_capacity(batch_size),
-            dispatcher: Dispatch::new(config, low, high, req_rate, num_reqs),
+            dispatcher: Dispatch::new(config, id, low, high, req_rate, num_reqs),
             start_tenant: low,
             end_tenant: high,
             num_context_switches: 0,
             num_mpk_switches: 0,
             num_vmfunc_switches: 0,
             num_preemptions: 0,
+            num_migrations: 0,
             isolation: config.isolation.clone(),
             tenants: tenants_vec,
             batch_size: batch_size,
@@ -316,11 +1150,62 @@ impl Core {
             mpk_domains: mpkdomains,
             vmfunc_domains: vmdomains,
             outstanding: 0,
+            arrivals: 0,
+            queue_area: 0,
+            depth_changed: 0,
             task_distribution: WeightedIndex::new(vec![99.9, 0.1]).unwrap(),
-            rng: Box::new(thread_rng()),
+            rng: Box::new(config.rng(STREAMS_PER_CORE * id as u64)),
             last_task_state: TaskState::Completed,
             core_type: coretype,
             is_core_partitioned: is_core_partitioned,
+            key_rng_zipf: Box::new(
+                ZipfDistribution::new(config.num_keys as usize, config.key_skew)
+                    .expect("Couldn't create key RNG."),
+            ),
+            cache: CacheSimulator::new(config.cache_size as usize),
+            cache_speedup: config.cache_speedup,
+            dispatch_cost: DispatchCost {
+                base_us: config.dispatch_base_us,
+                us_per_byte: config.dispatch_us_per_byte,
+            },
+            request_size_bytes: config.request_size_bytes as usize,
+            client_timeout: config.client_timeout_us.map(cycles::from_microseconds),
+            cancelled_count: 0,
+            min_task_time: config.min_task_time_us,
+            client_output: config.client_output,
+            max_preemptions: config.max_preemptions,
+            clamped_tasks: 0,
+            check_ordering: config.check_ordering,
+            queueing_cycles: 0,
+            service_cycles: 0,
+            replay: None,
+            replay_exact: false,
+            captured: None,
+            sibling_busy: false,
+            smt_slowdown: config.smt_slowdown,
+            speed: 1.0,
+            tenant_stats: HashMap::new(),
+            interval_stats: HashMap::new(),
+            isolated_tenant: None,
+            worker_pool: None,
+            snapshot_interval: config.snapshot_interval_ns * cycles::cycles_per_second()
+                / 1000000000,
+            snapshots: BTreeMap::new(),
+            classes: class_queues,
+            class_rng: class_rng,
+            intra_class_policy: config.intra_class_policy,
+            tenant_class: tenant_class,
+            class_latencies: HashMap::new(),
+            task_gen: task_gen,
+            label_latencies: HashMap::new(),
+            core_latencies: HashMap::new(),
+            tenant_latencies: HashMap::new(),
+            trace_records: None,
+            decision_records: None,
+            overload: overload,
+            burst: burst,
+            burst_requests: 0,
+            batcher: batcher,
         }
     }
 
@@ -328,9 +1213,79 @@ impl Core {
         self.rdtsc.clone()
     }
 
+    /// Replay the arrivals for the tenants on this core from the trace, instead of generating
+    /// the requests with the dispatcher.
+    pub fn set_replay(&mut self, mut replay: PcapReplay) {
+        replay.retain_tenants(self.start_tenant, self.end_tenant);
+        self.replay = Some(replay);
+    }
+
+    /// Return the tenants served by a core, as `low..high`; the tenants are split evenly across
+    /// the cores of a type, and the last core also gets the remainder.
+    ///
+    /// # Arguments
+    /// `id`: The id of the core among the cores of its type.
+    /// `num_tenants`: The number of tenants; numbered from 1.
+    /// `num_cores`: The number of cores of the type.
+    pub fn tenant_range(id: u8, num_tenants: u64, num_cores: u64) -> (u16, u16) {
+        let uniform_divide: u16 = num_tenants as u16 / num_cores as u16;
+        let low = (id as u16 * uniform_divide) + 1 as u16;
+        let mut high = low + uniform_divide as u16;
+        if id == num_cores as u8 - 1 {
+            high = num_tenants as u16 + 1;
+        }
+        (low, high)
+    }
+
+    /// Return the integral of the number of outstanding tasks over the simulated time in cycles,
+    /// up to the time stamp `rdtsc`.
+    pub fn queue_area(&self, rdtsc: u64) -> u128 {
+        let elapsed = rdtsc.saturating_sub(self.depth_changed);
+        self.queue_area + self.outstanding as u128 * elapsed as u128
+    }
+
+    /// Change the number of outstanding tasks to `outstanding` at the current time stamp.
+    fn set_outstanding(&mut self, outstanding: usize) {
+        self.queue_area = self.queue_area(self.rdtsc);
+        self.depth_changed = std::cmp::max(self.depth_changed, self.rdtsc);
+        self.outstanding = outstanding;
+    }
+
+    /// Return true if this core has outstanding tasks.
+    pub fn is_busy(&self) -> bool {
+        self.outstanding > 0
+    }
+
+    /// Return true if all the requests for this core have been generated and processed.
+    pub fn is_idle(&self) -> bool {
+        let exhausted = match self.replay {
+            Some(ref replay) => replay.is_empty(),
+            None => self.dispatcher.is_exhausted(),
+        };
+        let burst_pending = self.burst.as_ref().and_then(|b| b.pending()).is_some();
+        exhausted && !burst_pending && self.outstanding == 0
+    }
+
     pub fn update_rdtsc(&mut self) {
-        let next_dispatch_time = self.dispatcher.get_next();
-        if self.outstanding == 0 && self.rdtsc() < next_dispatch_time {
+        let mut next_dispatch_time = match self.replay {
+            Some(ref replay) => replay.next_time().unwrap_or(0),
+            None => self.dispatcher.get_next(),
+        };
+        let exhausted = match self.replay {
+            Some(ref replay) => replay.is_empty(),
+            None => self.dispatcher.is_exhausted(),
+        };
+        let burst = self.burst.as_ref().and_then(|b| b.pending());
+        let batch = self.batcher.as_ref().and_then(|b| b.deadline());
+        for at in burst.iter().chain(batch.iter()) {
+            if exhausted || *at < next_dispatch_time {
+                next_dispatch_time = *at;
+            }
+        }
+
+        // The requests waiting for their batch can't run yet.
+        let batched = self.batcher.as_ref().map_or(0, |b| b.len());
+        if self.outstanding == batched && self.rdtsc() < next_dispatch_time {
             self.rdtsc = next_dispatch_time;
         }
     }
@@ -403,8 +1358,16 @@ impl Core {
         }
     }
 
-    pub fn generate_req(&mut self) -> Option<u16> {
-        if let Some(t) = self.dispatcher.generate_request(self.rdtsc()) {
+    /// Return the next request which has arrived, if any; its task time is only set if the
+    /// replayed trace derives one from the request size, or for a captured workload.
+    pub fn generate_req(&mut self) -> Option<Arrival> {
+        if let Some(ref mut replay) = self.replay {
+            let curr = self.rdtsc;
+            return replay.next_arrival(curr);
+        }
+
+        let time = self.dispatcher.get_next();
+        let tenant = if let Some(t) = self.dispatcher.generate_request(self.rdtsc()) {
             let tenant;
             match self.distribution {
                 Dist::Zipf => {
@@ -427,7 +1390,14 @@ impl Core {
             }
         } else {
             None
-        }
+        };
+        let size_bytes = self.request_size_bytes;
+        tenant.map(|tenant| Arrival {
+            time: time,
+            tenant: tenant,
+            size: size_bytes,
+            task_time: None,
+        })
     }
 
     pub fn get_tenant_limit(&self) -> (u16, u16) {
@@ -435,26 +1405,71 @@ impl Core {
     }
 
     pub fn process_request(&mut self, mut req: Box<Request>, index: usize) {
+        if self.is_cancelled(&mut req) {
+            return;
+        }
+
+        // Wait for a worker thread to free up; the wait counts as queueing.
+        let mut worker = None;
+        if let Some(ref pool) = self.worker_pool {
+            let (id, start) = pool.borrow_mut().acquire(self.rdtsc);
+            self.rdtsc = start;
+            worker = Some((id, start));
+        }
+
+        // A new request is set up before it runs; the setup counts as service.
+        let first_dispatch = req.first_dispatch().is_none();
+        req.dispatch(self.rdtsc());
+        if first_dispatch {
+            self.rdtsc += req.dispatch_overhead(&self.dispatch_cost);
+        }
+        if let Some(last_core) = req.last_core() {
+            if last_core != self.core_id {
+                self.num_migrations += 1;
+            }
+        }
+        req.set_last_core(self.core_id);
         let tenant = req.get_tenant();
         if Some(tenant) != self.active_tenant {
             self.tenant_switch(tenant);
         }
 
-        let (time, taskstate) = req.run(&self.isolation, self.core_type);
+        let mut quantum = self.tenants[index].borrow().quantum(self.core_type);
+        if self.max_preemptions != 0 && req.preemptions() >= self.max_preemptions {
+            quantum = std::u64::MAX;
+        }
+        let (mut time, taskstate) = req.run(&self.isolation, quantum);
+        if self.sibling_busy {
+            // Both hyperthreads compete for the same physical core.
+            time = (time as f64 * self.smt_slowdown) as u64;
+        }
+        if self.speed != 1.0 {
+            time = (time as f64 / self.speed) as u64;
+        }
         self.rdtsc += time;
+        if let (Some(ref pool), Some((id, start))) = (&self.worker_pool, worker) {
+            pool.borrow_mut().release(id, start, self.rdtsc);
+        }
         match taskstate {
             TaskState::Completed => {
-                let latency = self.rdtsc() - req.start_time();
-                self.latencies.push(latency);
-                self.request_processed += 1;
-                self.outstanding -= 1;
+                self.record_completion(&req, index);
+
+                // The coalesced requests share the result, and complete along with this one.
+                let first_dispatch = req.first_dispatch().unwrap();
+                for mut follower in req.take_coalesced() {
+                    follower.dispatch(first_dispatch);
+                    follower.set_last_core(self.core_id);
+                    self.record_completion(&follower, index);
+                }
                 self.last_task_state = taskstate;
             }
 
             TaskState::Preempted => {
                 self.num_preemptions += 1;
-                self.tenants[index].borrow_mut().enqueue_task(req);
                 self.last_task_state = taskstate;
+                if !self.is_cancelled(&mut req) {
+                    self.tenants[index].borrow_mut().enqueue_task(req);
+                }
             }
 
             TaskState::Runnable | TaskState::Running => {
@@ -467,39 +1482,305 @@ impl Core {
         }
     }
 
+    /// Record the latency of a completed request in the stats, and count it as processed.
+    ///
+    /// # Arguments
+    /// `req`: The completed request; dispatched on this core.
+    /// `index`: The index of the request's tenant on this core.
+    fn record_completion(&mut self, req: &Request, index: usize) {
+        let tenant = req.get_tenant();
+        let latency = self.rdtsc() - req.start_time();
+        self.latencies.push(latency);
+        self.tenant_stats
+            .entry(tenant)
+            .or_insert_with(TenantStats::default)
+            .record(latency);
+        if let Some(label) = req.label() {
+            self.label_latencies
+                .entry(label)
+                .or_insert_with(Vec::new)
+                .push(latency);
+        }
+        self.tenant_latencies
+            .entry(tenant)
+            .or_insert_with(Vec::new)
+            .push(latency);
+        if let Some(core_id) = req.last_core() {
+            self.core_latencies
+                .entry(core_id)
+                .or_insert_with(Vec::new)
+                .push(latency);
+        }
+        if !self.classes.is_empty() {
+            let class = &self.classes[self.tenant_class[index]].class;
+            self.class_latencies
+                .entry(class.class_id)
+                .or_insert_with(Vec::new)
+                .push(latency);
+        }
+        let interval = self.rdtsc() / cycles::cycles_per_second();
+        self.interval_stats
+            .entry((tenant, interval))
+            .or_insert_with(TenantStats::default)
+            .record(latency);
+        let first_dispatch = req.first_dispatch().unwrap();
+        let now = self.rdtsc();
+        if let Some(ref mut records) = self.trace_records {
+            records.push(TraceRecord::new(
+                req.trace_id(),
+                tenant,
+                req.start_time(),
+                first_dispatch,
+                now,
+                req.max_time(),
+            ));
+        }
+        self.queueing_cycles += first_dispatch - req.start_time();
+        self.service_cycles += self.rdtsc() - first_dispatch;
+        self.request_processed += 1;
+        self.set_outstanding(self.outstanding - 1);
+        if self.snapshot_interval != 0 {
+            let sample = self
+                .snapshots
+                .entry(self.rdtsc() / self.snapshot_interval)
+                .or_insert_with(IntervalSample::default);
+            sample.latencies.push(latency);
+            sample.queue_depth = self.outstanding as u64;
+        }
+    }
+
+    /// Return true if the client cancelled the request, and drop it then. The client cancels the
+    /// requests which timed out through their tokens; see `client_timeout_us` in config.toml.
+    fn is_cancelled(&mut self, req: &mut Request) -> bool {
+        if let Some(timeout) = self.client_timeout {
+            if self.rdtsc() >= req.start_time() + timeout {
+                req.cancel_token().cancel();
+            }
+        }
+        if !req.is_cancelled() {
+            return false;
+        }
+
+        // The requests coalesced with this one are dropped along with it.
+        let dropped = 1 + req.num_coalesced();
+        self.cancelled_count += dropped as u64;
+        self.set_outstanding(self.outstanding - dropped);
+        true
+    }
+
+    /// Return the task time clamped up to the floor, and count it if it was shorter; the near-zero
+    /// task times make the simulation spin.
+    ///
+    /// # Arguments
+    /// `task_time`: The generated or replayed task time in micro-seconds.
+    fn clamp_task_time(&mut self, task_time: f64) -> f64 {
+        if task_time < self.min_task_time {
+            self.clamped_tasks += 1;
+            return self.min_task_time;
+        }
+        task_time
+    }
+
+    /// Return the task time in micro-seconds for a new request; from the task distribution, or
+    /// the service time generator if there is one.
+    fn sample_task_time(&mut self) -> f64 {
+        let dindex = self.task_distribution.sample(&mut *self.rng);
+        let mut task_time = consts::TASK_DISTRIBUTION_TIME[dindex];
+        if self.is_core_partitioned == true {
+            match self.core_type {
+                CoreType::Small => task_time = consts::TASK_DISTRIBUTION_TIME[0],
+
+                CoreType::Large => {
+                    task_time = consts::TASK_DISTRIBUTION_TIME[1];
+                }
+            }
+        }
+
+        if let Some(ref mut task_gen) = self.task_gen {
+            task_time = task_gen.next_service_time() * 1e6;
+        }
+        task_time
+    }
+
+    /// Inject the requests of the tenant burst once it is due. The requests arrive at the time
+    /// of the burst, even if the core was busy when that time passed.
+    fn inject_burst(&mut self) {
+        let rdtsc = self.rdtsc;
+        let (tenant_id, at, size) = match self.burst {
+            Some(ref mut burst) => {
+                if !burst.fire(rdtsc) {
+                    return;
+                }
+                (burst.tenant, burst.at, burst.size)
+            }
+            None => return,
+        };
+        if let Some(isolated) = self.isolated_tenant {
+            if tenant_id != isolated {
+                return;
+            }
+        }
+
+        let index = tenant_id as usize - self.start_tenant as usize;
+        for _i in 0..size {
+            let mut task_time = self.sample_task_time();
+            if !self.classes.is_empty() {
+                let queue = &self.classes[self.tenant_class[index]];
+                task_time = queue.service_time(&mut *self.rng);
+            }
+            let key_id = self.key_rng_zipf.sample(&mut *self.rng) as u64;
+
+            if let Some(ref mut overload) = self.overload {
+                if !overload.admit(self.outstanding, self.rdtsc) {
+                    continue;
+                }
+            }
+
+            if self.cache.access(key_id) {
+                task_time *= self.cache_speedup;
+            }
+            let task_time = self.clamp_task_time(task_time);
+
+            self.tenants[index].borrow_mut().add_request(
+                at,
+                task_time,
+                key_id,
+                self.request_size_bytes,
+            );
+            self.set_outstanding(self.outstanding + 1);
+            self.arrivals += 1;
+            self.burst_requests += 1;
+        }
+    }
+
     fn run_dispatcher(&mut self) {
-        while let Some(tenant_id) = self.generate_req() {
-            let dindex = self.task_distribution.sample(&mut *self.rng);
-            let mut task_time = consts::TASK_DISTRIBUTION_TIME[dindex];
-            if self.is_core_partitioned == true {
-                match self.core_type {
-                    CoreType::Small => task_time = consts::TASK_DISTRIBUTION_TIME[0],
-
-                    CoreType::Large => {
-                        task_time = consts::TASK_DISTRIBUTION_TIME[1];
-                    }
+        self.inject_burst();
+        while let Some(arrival) = self.generate_req() {
+            let mut tenant_id = arrival.tenant;
+            let size_bytes = arrival.size;
+            let mut task_time = self.sample_task_time();
+            if let Some(trace_task_time) = arrival.task_time {
+                task_time = trace_task_time;
+            }
+
+            // With the tenant classes, the classes decide the tenant and the task time instead.
+            if let (Some(class_rng), false) = (&self.class_rng, self.replay_exact) {
+                let queue = &self.classes[class_rng.sample(&mut *self.rng)];
+                let index = queue.tenants[self.rng.gen_range(0, queue.tenants.len())];
+                tenant_id = self.start_tenant + index as u16;
+                task_time = queue.service_time(&mut *self.rng);
+            }
+
+            if let Some(ref mut captured) = self.captured {
+                captured.push(Arrival {
+                    time: arrival.time,
+                    tenant: tenant_id,
+                    size: size_bytes,
+                    task_time: Some(task_time),
+                });
+            }
+
+            let key_id = self.key_rng_zipf.sample(&mut *self.rng) as u64;
+
+            // Drop the neighbors' requests after sampling so the isolated tenant sees the same
+            // task times as in a colocated run.
+            if let Some(isolated) = self.isolated_tenant {
+                if tenant_id != isolated {
+                    continue;
                 }
             }
-            let index = tenant_id as usize - self.start_tenant as usize;
-            self.tenants[index]
-                .borrow_mut()
-                .add_request(self.rdtsc, task_time);
-            self.outstanding += 1;
+
+            if let Some(ref mut overload) = self.overload {
+                if !overload.admit(self.outstanding, self.rdtsc) {
+                    continue;
+                }
+            }
+
+            // Requests which hit in the cache need less time to complete.
+            if self.cache.access(key_id) {
+                task_time *= self.cache_speedup;
+            }
+            let task_time = self.clamp_task_time(task_time);
+
+            if let Some(ref mut batcher) = self.batcher {
+                batcher.add(PendingRequest {
+                    tenant: tenant_id,
+                    arrival: self.rdtsc,
+                    task_time: task_time,
+                    key_id: key_id,
+                    size_bytes: size_bytes,
+                });
+            } else {
+                let index = tenant_id as usize - self.start_tenant as usize;
+                self.tenants[index]
+                    .borrow_mut()
+                    .add_request(self.rdtsc, task_time, key_id, size_bytes);
+            }
+            self.set_outstanding(self.outstanding + 1);
+            self.arrivals += 1;
+        }
+        self.dispatch_batch();
+    }
+
+    /// Add the requests of the server-side batch to the tenants, once the batch is due.
+    fn dispatch_batch(&mut self) {
+        let batch = match self.batcher {
+            Some(ref mut batcher) => batcher.take_due(self.rdtsc),
+            None => return,
+        };
+        for req in batch {
+            let index = req.tenant as usize - self.start_tenant as usize;
+            self.tenants[index].borrow_mut().add_request(
+                req.arrival,
+                req.task_time,
+                req.key_id,
+                req.size_bytes,
+            );
         }
     }
 
-    pub fn run(&mut self) {
+    /// Run one scheduling round on this core.
+    ///
+    /// # Arguments
+    /// `budget`: The maximum number of requests to complete in this round.
+    ///
+    /// # Return
+    /// The number of requests completed in this round.
+    pub fn run(&mut self, budget: u64) -> u64 {
+        if !self.classes.is_empty() {
+            return self.run_classes(budget);
+        }
+
         let (low, high) = self.get_tenant_limit();
+        let processed = self.request_processed;
 
         // Go through each tenant one by one; executing BATCH_SIZE tasks at a time.
         for t in low..high {
             let index: usize = (t - low) as usize;
             for _t in 0..self.batch_size {
+                if self.request_processed - processed >= budget {
+                    return self.request_processed - processed;
+                }
+
                 // Generate some more requests.
                 self.run_dispatcher();
 
                 let task = self.tenants[index].borrow_mut().get_request(self.core_type);
                 if let Some(task) = task {
+                    if self.check_ordering {
+                        let tenant = self.tenants[index].borrow();
+                        if let Err(e) = tenant.check_ordering(&task, self.core_type) {
+                            eprintln!("Scheduling policy violated on core {}: {}", self.core_id, e);
+                            eprintln!("Scheduler state: {}", tenant.debug_state());
+                            eprintln!(
+                                "Next tasks: {}",
+                                tenant
+                                    .describe_next_tasks(self.core_type, consts::DEBUG_NEXT_TASKS)
+                            );
+                            std::process::abort();
+                        }
+                    }
+                    self.record_decision(&task, DecisionReason::RoundRobin);
                     self.process_request(task, index);
                 } else {
                     break;
@@ -509,11 +1790,100 @@ impl Core {
 
         // Update the timestamp counter
         self.update_rdtsc();
+        self.request_processed - processed
+    }
+
+    /// Record the task picked by this core in the decision log, if there is one.
+    ///
+    /// # Arguments
+    /// `task`: The task which is about to run.
+    /// `reason`: Why the task was picked, if it is new; a task which ran before resumes after a
+    ///           preemption.
+    fn record_decision(&mut self, task: &Request, reason: DecisionReason) {
+        let now = self.rdtsc();
+        if let Some(ref mut records) = self.decision_records {
+            let reason = match task.first_dispatch() {
+                Some(_) => DecisionReason::Preempted,
+                None => reason,
+            };
+            records.push(DecisionRecord::new(
+                now,
+                self.core_id,
+                task.trace_id(),
+                task.get_tenant(),
+                reason,
+            ));
+        }
+    }
+
+    /// Run one scheduling round on this core with the tenant classes. The class with the highest
+    /// priority and a waiting task always runs first, and the tenants within a class are picked
+    /// by the `intra_class_policy`. A round runs up to `batch_size` tasks per tenant.
+    ///
+    /// # Arguments
+    /// `budget`: The maximum number of requests to complete in this round.
+    ///
+    /// # Return
+    /// The number of requests completed in this round.
+    fn run_classes(&mut self, budget: u64) -> u64 {
+        let processed = self.request_processed;
+        for _i in 0..self.batch_size * self.tenants.len() {
+            if self.request_processed - processed >= budget {
+                break;
+            }
+
+            // Generate some more requests.
+            self.run_dispatcher();
+
+            let mut picked = None;
+            for queue in self.classes.iter_mut() {
+                picked = queue.pick(&self.tenants, self.core_type, self.intra_class_policy);
+                if picked.is_some() {
+                    break;
+                }
+            }
+            let index = match picked {
+                Some(index) => index,
+                None => break,
+            };
+
+            let task = self.tenants[index].borrow_mut().get_request(self.core_type);
+            if let Some(task) = task {
+                if self.check_ordering {
+                    let tenant = self.tenants[index].borrow();
+                    if let Err(e) = tenant.check_ordering(&task, self.core_type) {
+                        eprintln!("Scheduling policy violated on core {}: {}", self.core_id, e);
+                        eprintln!("Scheduler state: {}", tenant.debug_state());
+                        eprintln!(
+                            "Next tasks: {}",
+                            tenant.describe_next_tasks(self.core_type, consts::DEBUG_NEXT_TASKS)
+                        );
+                        std::process::abort();
+                    }
+                }
+                self.record_decision(&task, DecisionReason::ClassPriority);
+                self.process_request(task, index);
+            }
+        }
+
+        // Update the timestamp counter
+        self.update_rdtsc();
+        self.request_processed - processed
     }
 }
 
 impl Drop for Simulator {
     fn drop(&mut self) {
+        if self.latencies.is_empty() {
+            return;
+        }
+
+        // Only the client's lines are printed, so that its scripts parse the output unchanged.
+        if self.config.client_output {
+            println!("{}", self.result().to_client_text());
+            return;
+        }
+
         // Calculate & print median & tail latency only on the master thread.
         self.latencies.sort();
 
@@ -529,14 +1899,108 @@ impl Drop for Simulator {
         }
         println!(
             "Latency: Median(us) {:.2} Tail(us) {:.2}",
-            cycles::to_seconds(m) * 1e6,
-            cycles::to_seconds(t) * 1e6,
+            cycles::to_microseconds(m),
+            cycles::to_microseconds(t),
         );
+
+        let result = self.result();
+        let mut cores: Vec<&u8> = result.core_percentiles.keys().collect();
+        cores.sort();
+        for core_id in cores {
+            let percentiles = &result.core_percentiles[core_id];
+            println!(
+                "Core {}: Completed {} Median(us) {:.2} Tail(us) {:.2}",
+                core_id,
+                percentiles.count,
+                cycles::to_microseconds(percentiles.median),
+                cycles::to_microseconds(percentiles.p99),
+            );
+        }
+
+        if result.requests_processed != 0 {
+            println!(
+                "Breakdown: Queueing(us) {:.2} Service(us) {:.2}",
+                cycles::to_microseconds(result.queueing_cycles / result.requests_processed),
+                cycles::to_microseconds(result.service_cycles / result.requests_processed),
+            );
+        }
+
+        if self.config.cache_size != 0 {
+            println!(
+                "Cache: Hit-Rate(%) {:.2}",
+                self.result().cache_hit_rate * 100.0
+            );
+        }
+
+        if !self.config.tenant_weights.is_empty() {
+            println!("Objective: Weighted-P99(us) {:.2}", result.weighted_p99);
+        }
+
+        if self.config.overload_high_water != 0 {
+            println!(
+                "Overload: Shed {} Time(us) {:.2} Max-Queue {}",
+                result.requests_shed,
+                cycles::to_microseconds(result.overload_cycles),
+                result.max_queue_depth
+            );
+        }
+
+        if self.config.measure_sched_overhead {
+            let overhead = &result.sched_overhead;
+            println!(
+                "Sched-Overhead(ns): Create {:.1} Pick {:.1} Enqueue {:.1}",
+                overhead.create.mean_ns(),
+                overhead.pick.mean_ns(),
+                overhead.enqueue.mean_ns()
+            );
+        }
+
+        if result.clamped_tasks != 0 {
+            println!(
+                "Clamped: Tasks {} Min-Task-Time(us) {:.2}",
+                result.clamped_tasks, self.config.min_task_time_us
+            );
+        }
+
+        if self.config.client_timeout_us.is_some() {
+            println!("Cancelled: Requests {}", result.cancelled_count);
+        }
+
+        if self.config.batch_window_ns > 0.0 && result.batches != 0 {
+            println!(
+                "Batching: Batches {} Mean-Size {:.2}",
+                result.batches,
+                result.requests_processed as f64 / result.batches as f64
+            );
+        }
+
+        if let Some(requests) = result.converged_after {
+            println!("Converged: P99 stable after {} requests", requests);
+        }
+
+        if result.littles_law_error() > 0.05 {
+            println!(
+                "Little's Law violation: simulation may have reached instability; L {:.2} Lambda*W {:.2}",
+                result.mean_queue_depth, result.littles_law_depth
+            );
+        }
+
+        if self.config.burst_tenant != 0 {
+            println!(
+                "Burst: Tenant {} At(us) {:.2} Requests {}",
+                self.config.burst_tenant, self.config.burst_at_us, result.burst_requests
+            );
+        }
     }
 }
 
 impl Drop for Core {
     fn drop(&mut self) {
+        // The client's format only has the Simulator's line.
+        if self.client_output {
+            return;
+        }
+
         let preemption_cycles;
         let cs_cycles;
         match self.isolation {
@@ -571,3 +2035,593 @@ impl Drop for Core {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::super::config::{test_config, TenantClass};
+    use super::*;
+
+    #[test]
+    fn test_num_requests() {
+        let mut config = test_config();
+        config.num_requests = 5000;
+
+        let mut simulator = Simulator::with_config(config);
+        let result = simulator.start();
+        assert_eq!(result.requests_processed, 5000);
+        assert_eq!(simulator.latencies.len(), 5000);
+    }
+
+    #[test]
+    fn test_latency_breakdown() {
+        let config = test_config();
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(0, &config, 32, &tenants, CoreType::Small);
+
+        // A 1us request generated at time 0 and picked by the core at time 1000.
+        core.tenants[0].borrow_mut().add_request(0, 1.0, 0, 0);
+        core.outstanding += 1;
+        core.rdtsc = 1000;
+        let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+        core.process_request(task.unwrap(), 0);
+
+        // The service time includes the switch to the tenant and the 1us execution.
+        assert_eq!(core.queueing_cycles, 1000);
+        assert_eq!(
+            core.service_cycles,
+            consts::PAGING_TENANT_SWITCH_CYCLES + cycles::cycles_per_us() as u64
+        );
+        assert_eq!(core.latencies, vec![1000 + core.service_cycles]);
+    }
+
+    #[test]
+    fn test_coalesced_requests() {
+        let mut config = test_config();
+        config.coalesce_requests = true;
+        let simulator = Simulator::with_config(config.clone());
+        let mut core = Core::new(0, &config, 32, &simulator.tenants, CoreType::Small);
+
+        // Three 1us requests for key 7 arrive before the core picks the first one.
+        for t in 0..3 {
+            core.tenants[0].borrow_mut().add_request(t * 100, 1.0, 7, 0);
+            core.outstanding += 1;
+        }
+        core.rdtsc = 1000;
+        let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+        assert!(core.tenants[0]
+            .borrow()
+            .sched
+            .top_task(CoreType::Small)
+            .is_none());
+        core.process_request(task.unwrap(), 0);
+
+        // One execution serves all three, and they complete at the same time.
+        assert_eq!(core.request_processed, 3);
+        assert_eq!(core.outstanding, 0);
+        let done = core.rdtsc;
+        assert_eq!(core.latencies, vec![done, done - 100, done - 200]);
+        assert_eq!(core.queueing_cycles, 1000 + 900 + 800);
+    }
+
+    #[test]
+    fn test_preempted_resumes_on_last_core() {
+        let config = test_config();
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(3, &config, 32, &tenants, CoreType::Small);
+
+        // A 12us request is preempted twice before it completes.
+        core.tenants[0].borrow_mut().add_request(0, 12.0, 0, 0);
+        core.outstanding += 1;
+        for _ in 0..3 {
+            let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+            let task = task.unwrap();
+            assert!(task.last_core() == None || task.last_core() == Some(3));
+            core.process_request(task, 0);
+        }
+        assert_eq!(core.num_preemptions, 2);
+        assert_eq!(core.num_migrations, 0);
+        assert_eq!(core.request_processed, 1);
+    }
+
+    #[test]
+    fn test_max_preemptions() {
+        let mut config = test_config();
+        config.max_preemptions = 1;
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(3, &config, 32, &tenants, CoreType::Small);
+
+        // The 12us request is only preempted once, and then runs to completion.
+        core.tenants[0].borrow_mut().add_request(0, 12.0, 0, 0);
+        core.outstanding += 1;
+        for preemptions in 0..2 {
+            let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+            let task = task.unwrap();
+            assert_eq!(task.preemptions(), preemptions);
+            core.process_request(task, 0);
+        }
+        assert!(core.tenants[0]
+            .borrow_mut()
+            .get_request(CoreType::Small)
+            .is_none());
+        assert_eq!(core.num_preemptions, 1);
+        assert_eq!(core.request_processed, 1);
+    }
+
+    #[test]
+    fn test_core_speeds() {
+        // Without the tenant switch cost, the core's time is the task's time.
+        let mut config = test_config();
+        config.isolation = Isolation::NoIsolation;
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+
+        // The same 4us task on a core of speed 1 and one twice as fast.
+        let mut cores = vec![];
+        for (id, speed) in [(0, 1.0), (1, 2.0)].iter() {
+            let mut core = Core::new(*id, &config, 32, &tenants, CoreType::Small);
+            core.speed = *speed;
+            core.tenants[0].borrow_mut().add_request(0, 4.0, 0, 0);
+            core.outstanding += 1;
+            let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+            core.process_request(task.unwrap(), 0);
+            cores.push(core);
+        }
+        assert_eq!(cores[1].request_processed, 1);
+        assert_eq!(cores[1].rdtsc(), cores[0].rdtsc() / 2);
+
+        // Each core serves one tenant; core 0 is fast.
+        config.core_speeds = vec![2.0, 1.0, 1.0, 3.0];
+        let speeds = Simulator::core_speeds(&config);
+        assert_eq!(&speeds[..4], &[2.0, 1.0, 1.0, 3.0]);
+        assert_eq!(speeds[31], 1.0);
+
+        // The fastest cores serve the latency-critical tenants 10 and 21 instead.
+        config.critical_tenants = vec![10, 21];
+        let speeds = Simulator::core_speeds(&config);
+        assert_eq!(Core::tenant_range(9, 32, 32), (10, 11));
+        assert!(speeds[9] == 3.0 || speeds[20] == 3.0);
+        assert!(speeds[9] >= 2.0 && speeds[20] >= 2.0);
+        assert_eq!(speeds.iter().filter(|speed| **speed > 1.0).count(), 2);
+    }
+
+    #[test]
+    fn test_smt_contention() {
+        let mut config = test_config();
+        config.smt_slowdown = 1.5;
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+
+        // Core 0 and core 1 are siblings; core 2 runs next to an idle sibling.
+        let mut cores = vec![];
+        for id in 0..3 {
+            let mut core = Core::new(id, &config, 32, &tenants, CoreType::Small);
+            core.tenants[0].borrow_mut().add_request(0, 2.0, 0, 0);
+            core.outstanding += 1;
+            cores.push(core);
+        }
+        assert_eq!(Simulator::sibling(0, 3), Some(1));
+        assert_eq!(Simulator::sibling(2, 3), None);
+
+        for c in 0..3 {
+            cores[c].sibling_busy = match Simulator::sibling(c, 3) {
+                Some(sibling) => cores[sibling].is_busy(),
+                None => false,
+            };
+            let task = cores[c].tenants[0]
+                .borrow_mut()
+                .get_request(CoreType::Small);
+            cores[c].process_request(task.unwrap(), 0);
+        }
+
+        // Core 1 runs after core 0 has finished, so only core 0 sees a busy sibling.
+        let unpaired = cores[2].rdtsc;
+        assert!(cores[0].rdtsc > unpaired);
+        assert_eq!(cores[1].rdtsc, unpaired);
+        assert_eq!(
+            cores[0].rdtsc - consts::PAGING_TENANT_SWITCH_CYCLES,
+            ((unpaired - consts::PAGING_TENANT_SWITCH_CYCLES) as f64 * 1.5) as u64
+        );
+    }
+
+    #[test]
+    fn test_arrivals_exhausted() {
+        let mut config = test_config();
+        config.num_reqs = 10;
+        config.num_requests = 1000000;
+
+        // Each core generates `num_reqs + 1` requests, and the run stops once all are processed.
+        let result = Simulator::with_config(config).start();
+        assert_eq!(result.requests_processed, 32 * 11);
+    }
+
+    #[test]
+    fn test_max_concurrent_tasks() {
+        let mut config = test_config();
+        config.num_reqs = 100;
+        config.max_concurrent_tasks = 4;
+
+        let mut simulator = Simulator::with_config(config);
+        let result = simulator.start();
+        assert_eq!(result.requests_processed, 32 * 101);
+
+        // Sweep over the task slices; a slice ending at the same time stamp as another starts
+        // frees its worker first.
+        let pool = simulator.worker_pool.as_ref().unwrap().borrow();
+        let mut events = Vec::new();
+        for (start, end) in pool.history.iter() {
+            events.push((*start, 1));
+            events.push((*end, -1));
+        }
+        events.sort();
+        let mut executing = 0;
+        let mut max_executing = 0;
+        for (_, delta) in events {
+            executing += delta;
+            max_executing = std::cmp::max(max_executing, executing);
+        }
+        assert_eq!(max_executing, 4);
+    }
+
+    #[test]
+    fn test_label_percentiles() {
+        let config = test_config();
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(0, &config, 32, &tenants, CoreType::Small);
+
+        // Hot reads take 1us and cold reads 4us, interleaved in the same run-queue.
+        for i in 0..100 {
+            let (label, time) = if i % 2 == 0 { (1, 1.0) } else { (2, 4.0) };
+            let mut req = Box::new(Request::new(1, 0, 0, time));
+            req.set_label(label);
+            core.tenants[0].borrow_mut().enqueue_task(req);
+            core.outstanding += 1;
+        }
+        // An unlabeled request isn't grouped.
+        core.tenants[0].borrow_mut().add_request(0, 1.0, 0, 0);
+        core.outstanding += 1;
+        for _ in 0..101 {
+            let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+            core.process_request(task.unwrap(), 0);
+        }
+
+        assert_eq!(core.label_latencies.len(), 2);
+        let hot = LatencyPercentiles::new(core.label_latencies.get_mut(&1).unwrap());
+        let cold = LatencyPercentiles::new(core.label_latencies.get_mut(&2).unwrap());
+        assert_eq!(hot.count, 50);
+        assert_eq!(cold.count, 50);
+
+        // Both queue behind the same requests; only the cold reads pay their own 4us.
+        let cycles_per_us = cycles::cycles_per_us() as u64;
+        assert_eq!(cold.p99 - hot.p99, 4 * cycles_per_us);
+    }
+
+    #[test]
+    fn test_dispatch_overhead() {
+        let mut config = test_config();
+        config.dispatch_base_us = 0.5;
+        config.dispatch_us_per_byte = 0.01;
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(0, &config, 32, &tenants, CoreType::Small);
+
+        // The first request switches to the tenant; the other two pay only their own setup.
+        for size_bytes in [0, 64, 1000].iter() {
+            core.tenants[0]
+                .borrow_mut()
+                .add_request(0, 1.0, 0, *size_bytes);
+            core.outstanding += 1;
+        }
+        let mut service = Vec::new();
+        for _ in 0..3 {
+            let before = core.service_cycles;
+            let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+            core.process_request(task.unwrap(), 0);
+            service.push(core.service_cycles - before);
+        }
+
+        let us = cycles::from_microseconds;
+        assert_eq!(service[1], us(0.5 + 0.01 * 64.0) + us(1.0));
+        assert_eq!(service[2], us(0.5 + 0.01 * 1000.0) + us(1.0));
+    }
+
+    #[test]
+    fn test_core_percentiles() {
+        let config = test_config();
+        let mut simulator = Simulator::with_config(config.clone());
+
+        // Core 0 gets ten times the requests of core 1, all at the same time.
+        for (core_id, num_reqs) in [(0, 100), (1, 10)].iter() {
+            let mut core = Core::new(*core_id, &config, 32, &simulator.tenants, CoreType::Small);
+            for _ in 0..*num_reqs {
+                core.tenants[0].borrow_mut().add_request(0, 1.0, 0, 0);
+                core.outstanding += 1;
+            }
+            for _ in 0..*num_reqs {
+                let task = core.tenants[0].borrow_mut().get_request(CoreType::Small);
+                core.process_request(task.unwrap(), 0);
+            }
+            simulator.cores.push(core);
+        }
+
+        let result = simulator.result();
+        let busy = result.core_percentiles[&0];
+        let idle = result.core_percentiles[&1];
+        assert_eq!(busy.count, 100);
+        assert_eq!(idle.count, 10);
+        assert!(busy.median > idle.median);
+        assert!(busy.p99 > idle.p99);
+    }
+
+    #[test]
+    fn test_tenant_class_priority() {
+        let class = |class_id: u8, priority: u8| TenantClass {
+            class_id: class_id,
+            priority: priority,
+            arrival_rate: 1.0,
+            service_time_mean: 1.0,
+            num_tenants: 64,
+        };
+
+        // Two tenants of each class per core, at 80% load without the tenant switch costs.
+        let mut config = test_config();
+        config.num_tenants = 128;
+        config.num_reqs = 2000;
+        config.req_rate = 800000;
+        config.isolation = Isolation::NoIsolation;
+        config.tenant_classes = vec![class(0, 0), class(1, 1)];
+        assert!(config.validate().is_ok());
+
+        let result = Simulator::with_config(config).start();
+        assert!(result.class_p99[&1] < result.class_p99[&0]);
+    }
+
+    #[test]
+    fn test_overload_shedding() {
+        // Each core gets 4 requests per micro-second, and serves less than one.
+        let mut config = test_config();
+        config.req_rate = 4000000;
+        config.num_reqs = 20000;
+        config.num_requests = 100000000;
+        config.overload_high_water = 100;
+        config.overload_window_us = 10.0;
+
+        // Without shedding, the queue keeps growing during the overload.
+        config.shed_fraction = 0.0;
+        let result = Simulator::with_config(config.clone()).start();
+        assert_eq!(result.requests_shed, 0);
+        assert!(result.overload_cycles > 0);
+        assert!(result.max_queue_depth > 1000);
+
+        // Shedding all the arrivals during the overload keeps the queue near the mark.
+        config.shed_fraction = 1.0;
+        let result = Simulator::with_config(config).start();
+        assert!(result.requests_shed > 0);
+        assert!(result.overload_cycles > 0);
+        assert!(result.max_queue_depth < 200);
+        assert_eq!(
+            result.requests_processed + result.requests_shed,
+            32 * (20000 + 1)
+        );
+    }
+
+    #[test]
+    fn test_cancelled_requests() {
+        // The client cancels each request as soon as it arrives; none of them runs.
+        let mut config = test_config();
+        config.num_requests = 100000000;
+        config.client_timeout_us = Some(0.0);
+        let result = Simulator::with_config(config).start();
+        assert_eq!(result.requests_processed, 0);
+        assert_eq!(result.cancelled_count, 32 * (1000 + 1));
+
+        // A token cancels the task for all of its clones.
+        let mut req = Request::new(1, 0, 0, 1.0);
+        assert!(!req.is_cancelled());
+        req.cancel_token().cancel();
+        assert!(req.is_cancelled());
+    }
+
+    #[test]
+    fn test_client_output() {
+        // One second of simulated time, with a 1us median and a 10us tail.
+        let mut result = SimulationResult::default();
+        result.requests_processed = 1000;
+        result.elapsed_cycles = cycles::cycles_per_second();
+        result.latency.median = cycles::cycles_per_second() / 1000000;
+        result.latency.p99 = cycles::cycles_per_second() / 100000;
+        assert_eq!(result.to_client_text(), "Throughput 1000\n>>> 1000 10000");
+
+        // A scripted run has the same two lines as the client.
+        let mut config = test_config();
+        config.client_output = true;
+        let result = Simulator::with_config(config).start();
+        let text = result.to_client_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let throughput: Vec<&str> = lines[0].split(' ').collect();
+        assert_eq!(throughput[0], "Throughput");
+        assert!(throughput[1].parse::<f64>().unwrap() > 0.0);
+        let latency: Vec<&str> = lines[1].split(' ').collect();
+        assert_eq!(latency.len(), 3);
+        assert_eq!(latency[0], ">>>");
+        let median = latency[1].parse::<f64>().unwrap();
+        let p99 = latency[2].parse::<f64>().unwrap();
+        assert!(median > 0.0 && median <= p99);
+    }
+
+    #[test]
+    fn test_replay_workload() {
+        // Exponential service times, so that each run generates a different workload.
+        let mut config = test_config();
+        config.service_time_distribution = ServiceTimeDistribution::Exponential;
+        config.service_time_mean_us = 1.0;
+        // Run until every core has generated all of its `num_reqs + 1` arrivals.
+        config.num_resps = config.num_reqs + 1;
+
+        let mut first = Simulator::with_config(config.clone());
+        first.capture_workload();
+        first.start();
+        let workload = first.captured_workload().unwrap();
+        assert_eq!(workload.len(), 32 * (1000 + 1));
+
+        // A scheduler with another quantum gets the identical arrivals.
+        config.rr_quantum_us = 5.0;
+        let mut second = Simulator::with_config(config.clone());
+        second.capture_workload();
+        second.replay_workload(workload.clone());
+        second.start();
+        assert_eq!(second.captured_workload().unwrap(), workload);
+
+        // Without the replay, the workload differs.
+        let mut third = Simulator::with_config(config);
+        third.capture_workload();
+        third.start();
+        assert!(third.captured_workload().unwrap() != workload);
+        assert!(Simulator::with_config(test_config())
+            .captured_workload()
+            .is_none());
+    }
+
+    #[test]
+    fn test_clamped_tasks() {
+        // All the tasks take 1us, below the floor; each one of them is clamped. The run goes on
+        // until every core has processed all of its `num_reqs + 1` arrivals.
+        let mut config = test_config();
+        config.min_task_time_us = 2.0;
+        config.num_resps = config.num_reqs + 1;
+        let result = Simulator::with_config(config.clone()).start();
+        assert_eq!(result.clamped_tasks, 32 * (1000 + 1));
+
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(3, &config, 32, &tenants, CoreType::Small);
+        assert_eq!(core.clamp_task_time(0.0), 2.0);
+        assert_eq!(core.clamp_task_time(2.0), 2.0);
+        assert_eq!(core.clamp_task_time(5.0), 5.0);
+        assert_eq!(core.clamped_tasks, 1);
+
+        // The floor is off by default.
+        let result = Simulator::with_config(test_config()).start();
+        assert_eq!(result.clamped_tasks, 0);
+    }
+
+    #[test]
+    fn test_tenant_burst() {
+        // Core 0 serves the tenants 1 and 2; tenant 1 bursts in between the regular arrivals,
+        // which are 10us apart.
+        let mut config = test_config();
+        config.num_tenants = 64;
+        config.burst_tenant = 1;
+        config.burst_at_us = 5005.0;
+        config.burst_size = 500;
+        let mut tenants = HashMap::new();
+        for i in 1..config.num_tenants + 1 {
+            let tenant = Tenant::new(i as u16, Box::new(RoundRobin::new(5.0)));
+            tenants.insert(i, Arc::new(RefCell::new(tenant)));
+        }
+        let mut core = Core::new(0, &config, 32, &tenants, CoreType::Small);
+        core.trace_records = Some(Vec::new());
+        while !core.is_idle() {
+            core.run(std::u64::MAX);
+        }
+        assert_eq!(core.burst_requests, 500);
+
+        // All the burst requests arrive at the time of the burst.
+        let records = core.trace_records.take().unwrap();
+        let at = TraceRecord::new(0, 0, cycles::from_microseconds(5005.0), 0, 0, 0.0).arrival_ns;
+        let burst = records
+            .iter()
+            .filter(|r| r.arrival_ns == at)
+            .collect::<Vec<_>>();
+        assert_eq!(burst.len(), 500);
+        assert!(burst.iter().all(|r| r.tenant_id == 1));
+
+        // The other tenant keeps getting served during and after the burst.
+        let last = burst.iter().map(|r| r.completion_ns).max().unwrap();
+        let neighbor = records.iter().filter(|r| r.tenant_id == 2);
+        assert!(neighbor
+            .clone()
+            .any(|r| r.arrival_ns > at && r.completion_ns < last));
+        assert!(neighbor.clone().any(|r| r.arrival_ns > last));
+        assert_eq!(records.len() as u64, config.num_reqs + 1 + 500);
+    }
+
+    #[test]
+    fn test_littles_law() {
+        let result = Simulator::with_config(test_config()).start();
+        assert!(result.mean_queue_depth > 0.0);
+        assert!(result.littles_law_error() < 0.05);
+
+        let mut result = SimulationResult::default();
+        assert_eq!(result.littles_law_error(), 0.0);
+        result.mean_queue_depth = 10.0;
+        result.littles_law_depth = 12.0;
+        assert!((result.littles_law_error() - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_convergence_stop() {
+        // A stationary workload, long enough that the p99 converges well before the end.
+        let mut config = test_config();
+        config.num_reqs = 20000;
+        config.num_resps = 20000;
+        config.convergence_epsilon = 0.05;
+        config.convergence_window = 10000;
+
+        let result = Simulator::with_config(config).start();
+        let converged = result.converged_after.unwrap();
+        assert!(converged >= 20000);
+        assert!(converged < 32 * 10000);
+        assert!(result.requests_processed < 32 * 20000);
+    }
+
+    #[test]
+    fn test_arrival_batching() {
+        // Each core gets 1.5 requests per micro-second of 1us each, more than it can serve.
+        let mut config = test_config();
+        config.req_rate = 1500000;
+        config.num_reqs = 5000;
+        config.num_resps = 5000;
+        let mean = |result: &SimulationResult| {
+            (result.queueing_cycles + result.service_cycles) / result.requests_processed
+        };
+        let unbatched = Simulator::with_config(config.clone()).start();
+        assert_eq!(unbatched.batches, 0);
+
+        // The batches halve the task times, which brings the load below 1 and the queue with it.
+        config.batch_window_ns = 2000.0;
+        config.max_batch_size = 8;
+        config.batch_efficiency = 0.5;
+        let batched = Simulator::with_config(config).start();
+        assert!(batched.batches > 0);
+        assert!(mean(&batched) * 10 < mean(&unbatched));
+    }
+}
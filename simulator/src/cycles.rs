@@ -41,6 +41,14 @@ pub fn cycles_per_second() -> u64 {
     }
 }
 
+/// Return the calibrated clock frequency in MHz; for the human-readable output.
+///
+/// # Return
+/// Number of CPU cycles per micro-second, rounded down.
+pub fn frequency_mhz() -> u64 {
+    cycles_per_second() / 1_000_000
+}
+
 pub fn cycles_per_us() -> f64 {
     cycles_per_second() as f64 / 1e6
 }
@@ -66,3 +74,46 @@ pub fn rdtsc() -> u64 {
 pub fn to_seconds(cycles: u64) -> f64 {
     cycles as f64 / cycles_per_second() as f64
 }
+
+/// Converts the number of CPU cycles to micro-seconds.
+///
+/// # Arguments
+/// *`cycles`: Number of CPU cycles.
+///
+/// # Return
+/// Number of micro-seconds corresponding to the given CPU cycles.
+pub fn to_microseconds(cycles: u64) -> f64 {
+    cycles as f64 / cycles_per_us()
+}
+
+/// Converts micro-seconds to the number of CPU cycles.
+///
+/// # Arguments
+/// *`us`: Number of micro-seconds.
+///
+/// # Return
+/// Number of CPU cycles corresponding to the given micro-seconds, truncated.
+pub fn from_microseconds(us: f64) -> u64 {
+    (us * cycles_per_us()) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_microseconds_round_trip() {
+        for us in [0.5, 1.0, 10.0, 37.25, 100.0, 1000.0].iter() {
+            let cycles = from_microseconds(*us);
+            assert!(((to_microseconds(cycles) - us) / us).abs() < 1e-4);
+        }
+        assert_eq!(from_microseconds(10.0), cycles_per_second() / 100000);
+        assert_eq!(to_microseconds(cycles_per_second()), 1e6);
+    }
+
+    #[test]
+    fn test_frequency_mhz() {
+        assert!(frequency_mhz() >= 1000 && frequency_mhz() <= 10000);
+        assert_eq!(frequency_mhz(), 3000);
+    }
+}
@@ -0,0 +1,179 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The reason a core picked a task.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecisionReason {
+    // A new task of the tenant whose turn it was in the round-robin over the tenants of the core.
+    RoundRobin,
+
+    // A new task of the highest priority class with a waiting task.
+    ClassPriority,
+
+    // A task which was preempted earlier, and resumes.
+    Preempted,
+}
+
+impl DecisionReason {
+    /// Return the name of the reason in the log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DecisionReason::RoundRobin => "round_robin",
+            DecisionReason::ClassPriority => "class_priority",
+            DecisionReason::Preempted => "preempted",
+        }
+    }
+}
+
+/// A scheduling decision, for the decision log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecisionRecord {
+    // The simulated time at which the core picked the task.
+    pub time_ns: u64,
+
+    // The core which picked the task.
+    pub core_id: u8,
+
+    // The random id of the request.
+    pub trace_id: u128,
+
+    // The tenant of the request.
+    pub tenant_id: u16,
+
+    // Why the task was picked.
+    pub reason: DecisionReason,
+}
+
+impl DecisionRecord {
+    /// This method creates the record for a task picked by a core.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The time stamp in cycles at which the task was picked.
+    /// `core_id`: The core which picked the task.
+    /// `trace_id`: The random id of the request.
+    /// `tenant_id`: The tenant of the request.
+    /// `reason`: Why the task was picked.
+    pub fn new(
+        rdtsc: u64,
+        core_id: u8,
+        trace_id: u128,
+        tenant_id: u16,
+        reason: DecisionReason,
+    ) -> DecisionRecord {
+        DecisionRecord {
+            time_ns: (cycles::to_seconds(rdtsc) * 1e9) as u64,
+            core_id: core_id,
+            trace_id: trace_id,
+            tenant_id: tenant_id,
+            reason: reason,
+        }
+    }
+
+    /// Return the record as a line of JSON; the trace id is a 32 digit hex string.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"time_ns\":{},\"core_id\":{},\"trace_id\":\"{:032x}\",\"tenant_id\":{},\"reason\":\"{}\"}}",
+            self.time_ns,
+            self.core_id,
+            self.trace_id,
+            self.tenant_id,
+            self.reason.name()
+        )
+    }
+}
+
+/// Writes one line of JSON for each task picked by a core.
+pub struct DecisionLog {
+    writer: BufWriter<File>,
+}
+
+impl DecisionLog {
+    pub fn create(path: &Path) -> io::Result<DecisionLog> {
+        Ok(DecisionLog {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Append a record to the log.
+    pub fn write(&mut self, record: &DecisionRecord) -> io::Result<()> {
+        writeln!(self.writer, "{}", record.to_json())
+    }
+
+    /// Flush the buffered records to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::super::cores::Simulator;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    // Parse a line of the log into the fields.
+    fn parse(line: &str) -> HashMap<String, String> {
+        let line = line.trim_start_matches('{').trim_end_matches('}');
+        line.split(',')
+            .map(|field| {
+                let mut pair = field.splitn(2, ':');
+                let key = pair.next().unwrap().trim_matches('"').to_string();
+                let value = pair.next().unwrap().trim_matches('"').to_string();
+                (key, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decision_log() {
+        let mut config = test_config();
+        config.num_requests = 1000;
+        let path = env::temp_dir().join("simulator_test_decision_log.json");
+
+        let mut simulator = Simulator::with_config(config);
+        simulator.set_decision_log(&path).unwrap();
+        let result = simulator.start();
+
+        // A task is picked once, and once more after each preemption.
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut dispatches: HashMap<String, Vec<String>> = HashMap::new();
+        for line in contents.lines() {
+            let record = parse(line);
+            assert_eq!(record.len(), 5);
+            assert!(record["time_ns"].parse::<u64>().is_ok());
+            assert!(record["core_id"].parse::<u8>().unwrap() < 32);
+            assert_eq!(record["trace_id"].len(), 32);
+            assert!(record["tenant_id"].parse::<u16>().unwrap() >= 1);
+            dispatches
+                .entry(record["trace_id"].clone())
+                .or_insert_with(Vec::new)
+                .push(record["reason"].clone());
+        }
+        assert!(dispatches.len() as u64 >= result.requests_processed);
+        for reasons in dispatches.values() {
+            assert_eq!(reasons[0], "round_robin");
+            assert!(reasons[1..].iter().all(|reason| reason == "preempted"));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -13,14 +13,18 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+use super::arrival::ArrivalGen;
 use super::config;
 use super::cycles;
 
 use rand::distributions::{Distribution, Uniform};
-use rand::prelude::*;
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
 use zipf::ZipfDistribution;
 
+/// The number of random number generator streams of each core; see `Config::stream_seed()`. The
+/// core's own generator, the dispatcher's, the arrivals', and the service times'.
+pub const STREAMS_PER_CORE: u64 = 4;
+
 pub struct Dispatch {
     // Total number of requests to generate.
     num_requests: u64,
@@ -28,13 +32,17 @@ pub struct Dispatch {
     // The number of requests generated so far.
     pub sent: u64,
 
-    // The inverse of the rate at which requests are to be generated. Basically, the time interval
-    // between two request generations in cycles.
-    rate_inv: u64,
+    // Generates the time intervals between two request generations in cycles; the inverse of the
+    // rate at which requests are to be generated on average.
+    arrivals: ArrivalGen,
 
     // The time stamp at which the next request must be issued in cycles.
     next: u64,
 
+    // The time stamp at which the next request must be issued, without truncating the intervals;
+    // the deterministic intervals are whole cycles, so these add up exactly.
+    next_exact: f64,
+
     // The tenant zipf number generator.
     tenant_rng_zipf: Box<ZipfDistribution>,
 
@@ -42,7 +50,7 @@ pub struct Dispatch {
     tenant_rng_uniform: Box<Uniform<u16>>,
 
     // Random number generator.
-    rng: Box<ThreadRng>,
+    rng: Box<StdRng>,
 
     // Distribution mechanism amoung tenants on a core.
     distribution: config::Distribution,
@@ -51,23 +59,30 @@ pub struct Dispatch {
 impl Dispatch {
     pub fn new(
         config: &config::Config,
+        id: u8,
         low: u16,
         high: u16,
         req_rate: u64,
         num_reqs: u64,
     ) -> Dispatch {
         let num_tenants = (high - low) as usize;
+        let mean = (cycles::cycles_per_second() / req_rate) as f64;
+        let arrivals = match config.stream_seed(STREAMS_PER_CORE * id as u64 + 2) {
+            Some(seed) => ArrivalGen::with_seed(config.arrival_process, mean, seed),
+            None => ArrivalGen::new(config.arrival_process, mean),
+        };
         Dispatch {
             num_requests: num_reqs,
             sent: 0,
-            rate_inv: cycles::cycles_per_second() / req_rate,
+            arrivals: arrivals,
             next: 0,
+            next_exact: 0.0,
             tenant_rng_zipf: Box::new(
                 ZipfDistribution::new(num_tenants, config.tenant_skew)
                     .expect("Couldn't create tenant RNG."),
             ),
             tenant_rng_uniform: Box::new(Uniform::from(low..high)),
-            rng: Box::new(thread_rng()),
+            rng: Box::new(config.rng(STREAMS_PER_CORE * id as u64 + 1)),
             distribution: config.distribution.clone(),
         }
     }
@@ -75,7 +90,8 @@ impl Dispatch {
     pub fn generate_request(&mut self, curr: u64) -> Option<u16> {
         if self.sent <= self.num_requests && (curr >= self.next || self.next == 0) {
             self.sent += 1;
-            self.next = 0 + self.sent * self.rate_inv;
+            self.next_exact += self.arrivals.next_gap();
+            self.next = self.next_exact as u64;
             match self.distribution {
                 config::Distribution::Uniform => {
                     Some(self.tenant_rng_uniform.sample(&mut *self.rng))
@@ -93,4 +109,9 @@ impl Dispatch {
     pub fn get_next(&self) -> u64 {
         self.next.clone()
     }
+
+    /// Return true if all the requests for this dispatcher have been generated.
+    pub fn is_exhausted(&self) -> bool {
+        self.sent > self.num_requests
+    }
 }
@@ -0,0 +1,243 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::Config;
+use super::cores::{Simulator, TenantStats};
+use super::cycles;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The latency of a tenant when running alone on its core, and when sharing it with neighbors.
+#[derive(Clone, Copy, Debug)]
+pub struct TenantInflation {
+    // The tenant id.
+    pub tenant: u16,
+
+    // The mean latency in cycles when the tenant runs alone.
+    pub isolated_latency: f64,
+
+    // The mean latency in cycles when the tenant runs with its neighbors.
+    pub colocated_latency: f64,
+
+    // The ratio of the colocated latency to the isolated latency.
+    pub inflation: f64,
+}
+
+/// This method runs each tenant alone to get its baseline latency, then runs all the tenants
+/// together, and reports how much each tenant's latency is inflated by its neighbors. Each run
+/// processes all the generated arrivals, so that the runs are comparable.
+///
+/// # Arguments
+/// `config`: The configuration for the runs.
+///
+/// # Return
+/// The latency inflation for each tenant which completed requests in both runs.
+pub fn colocation_report(config: &Config) -> Vec<TenantInflation> {
+    let mut config = config.clone();
+    config.num_requests = 0;
+    config.num_resps = std::u64::MAX;
+
+    let colocated = Simulator::with_config(config.clone()).start();
+
+    let mut report = Vec::with_capacity(config.num_tenants as usize);
+    for tenant in 1..(config.num_tenants + 1) as u16 {
+        let mut simulator = Simulator::with_config(config.clone());
+        simulator.isolate_tenant(tenant);
+        let isolated = simulator.start();
+
+        if let (Some(alone), Some(shared)) = (
+            isolated.tenant_stats.get(&tenant),
+            colocated.tenant_stats.get(&tenant),
+        ) {
+            report.push(TenantInflation {
+                tenant: tenant,
+                isolated_latency: alone.mean_latency(),
+                colocated_latency: shared.mean_latency(),
+                inflation: shared.mean_latency() / alone.mean_latency(),
+            });
+        }
+    }
+    report
+}
+
+/// Detects the tenants whose latency exceeds their SLA only while a specific other tenant runs.
+pub struct NoisyNeighborDetector {
+    // The latency SLA in cycles; the same for all the tenants.
+    sla: u64,
+}
+
+impl NoisyNeighborDetector {
+    pub fn new(sla: u64) -> NoisyNeighborDetector {
+        NoisyNeighborDetector { sla: sla }
+    }
+
+    /// This method compares each tenant's latency in the intervals where another tenant ran
+    /// against the intervals where it didn't. A tenant is a victim of a culprit if it missed the
+    /// SLA by more than 10% in an interval where the culprit ran, and met it in all the intervals
+    /// where the culprit didn't run. A culprit which ran in every interval is never reported, as
+    /// there is no baseline to compare against.
+    ///
+    /// # Arguments
+    /// `intervals`: The latency statistics for each tenant, keyed by the tenant and the interval.
+    ///
+    /// # Return
+    /// The (victim, culprit) pairs, sorted by the victim and then the culprit.
+    pub fn detect(&self, intervals: &HashMap<(u16, u64), TenantStats>) -> Vec<(u16, u16)> {
+        let threshold = self.sla as f64 * 1.1;
+
+        // The tenants which completed requests in each interval.
+        let mut active: BTreeMap<u64, HashMap<u16, TenantStats>> = BTreeMap::new();
+        let mut tenants = BTreeSet::new();
+        for (&(tenant, interval), stats) in intervals.iter() {
+            active
+                .entry(interval)
+                .or_insert_with(HashMap::new)
+                .insert(tenant, *stats);
+            tenants.insert(tenant);
+        }
+
+        let mut report = Vec::new();
+        for &victim in tenants.iter() {
+            for &culprit in tenants.iter().filter(|&&t| t != victim) {
+                let mut violated_with = false;
+                let mut violated_without = false;
+                let mut ran_without = false;
+                for running in active.values() {
+                    let violated = match running.get(&victim) {
+                        Some(stats) => stats.mean_latency() > threshold,
+                        None => continue,
+                    };
+                    if running.contains_key(&culprit) {
+                        violated_with |= violated;
+                    } else {
+                        ran_without = true;
+                        violated_without |= violated;
+                    }
+                }
+
+                if violated_with && ran_without && !violated_without {
+                    report.push((victim, culprit));
+                }
+            }
+        }
+        report
+    }
+}
+
+/// This method combines the p99 latencies of the tenants into a single cost, so that a sweep can
+/// compare the configurations; the mean of the p99 latencies, weighted by the tenant weights.
+///
+/// # Arguments
+/// `tenant_p99`: The p99 latency in cycles for each tenant which completed requests.
+/// `weights`: The weight of tenant `t` at index `t - 1`; if empty, all the weights are 1.
+///
+/// # Return
+/// The weighted mean of the p99 latencies in micro-seconds; zero if no weighted tenant completed
+/// any requests.
+pub fn weighted_p99(tenant_p99: &HashMap<u16, u64>, weights: &[f64]) -> f64 {
+    let mut cost = 0.0;
+    let mut total_weight = 0.0;
+    for (tenant, p99) in tenant_p99.iter() {
+        let weight = if weights.is_empty() {
+            1.0
+        } else {
+            weights.get(*tenant as usize - 1).cloned().unwrap_or(0.0)
+        };
+        cost += weight * cycles::to_microseconds(*p99);
+        total_weight += weight;
+    }
+    if total_weight == 0.0 {
+        return 0.0;
+    }
+    cost / total_weight
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::*;
+
+    #[test]
+    fn test_colocation_report() {
+        // Two tenants per core; the neighbors add the context switches between the tenants.
+        let mut config = test_config();
+        config.num_tenants = 64;
+        config.num_reqs = 200;
+
+        let report = colocation_report(&config);
+        assert_eq!(report.len(), 64);
+        for tenant in report.iter() {
+            assert!(tenant.isolated_latency > 0.0);
+            assert!(tenant.inflation >= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_weighted_p99() {
+        let us = |latency: u64| cycles::from_microseconds(latency as f64);
+        let mut tenant_p99 = HashMap::new();
+        tenant_p99.insert(1, us(10));
+        tenant_p99.insert(2, us(20));
+        tenant_p99.insert(3, us(100));
+
+        // (1 * 10 + 3 * 20 + 0 * 100) / 4 = 17.5
+        let cost = weighted_p99(&tenant_p99, &[1.0, 3.0, 0.0]);
+        assert!((cost - 17.5).abs() < 1e-9);
+
+        // Without the weights, the cost is the plain mean.
+        let cost = weighted_p99(&tenant_p99, &[]);
+        assert!((cost - 130.0 / 3.0).abs() < 1e-9);
+    }
+
+    fn stats(completed: u64, total_latency: u64) -> TenantStats {
+        TenantStats {
+            completed: completed,
+            total_latency: total_latency,
+        }
+    }
+
+    #[test]
+    fn test_noisy_neighbor() {
+        // Tenant 2 runs long tasks in intervals 2 and 3, and tenant 1's short tasks queue behind
+        // them. Tenant 3 runs in every interval and meets the SLA.
+        let mut intervals = HashMap::new();
+        for interval in 0..5 {
+            let latency = if interval == 2 || interval == 3 {
+                500
+            } else {
+                50
+            };
+            intervals.insert((1, interval), stats(10, 10 * latency));
+            intervals.insert((3, interval), stats(10, 10 * 50));
+        }
+        intervals.insert((2, 2), stats(1, 90));
+        intervals.insert((2, 3), stats(1, 90));
+
+        let detector = NoisyNeighborDetector::new(100);
+        assert_eq!(detector.detect(&intervals), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_no_noisy_neighbor_within_margin() {
+        // 10% over the SLA isn't a violation.
+        let mut intervals = HashMap::new();
+        intervals.insert((1, 0), stats(1, 50));
+        intervals.insert((1, 1), stats(1, 110));
+        intervals.insert((2, 1), stats(1, 50));
+
+        let detector = NoisyNeighborDetector::new(100);
+        assert!(detector.detect(&intervals).is_empty());
+    }
+}
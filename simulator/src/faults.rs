@@ -0,0 +1,86 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+/// Injects a burst of requests for one tenant at a scheduled simulated time, on top of the
+/// regular arrivals; models an incident where a single tenant suddenly floods the server.
+pub struct TenantBurst {
+    // The tenant which emits the burst.
+    pub tenant: u16,
+
+    // The simulated time stamp in cycles at which the burst arrives.
+    pub at: u64,
+
+    // The number of requests in the burst.
+    pub size: u64,
+
+    // True once the burst has been injected.
+    fired: bool,
+}
+
+impl TenantBurst {
+    /// Create a burst which is yet to be injected.
+    ///
+    /// # Arguments
+    /// `tenant`: The tenant which emits the burst.
+    /// `at`: The time stamp in cycles at which the burst arrives.
+    /// `size`: The number of requests in the burst.
+    pub fn new(tenant: u16, at: u64, size: u64) -> TenantBurst {
+        TenantBurst {
+            tenant: tenant,
+            at: at,
+            size: size,
+            fired: false,
+        }
+    }
+
+    /// Return the time stamp of the burst, if it is yet to be injected.
+    pub fn pending(&self) -> Option<u64> {
+        if self.fired {
+            None
+        } else {
+            Some(self.at)
+        }
+    }
+
+    /// This method checks whether the burst is due, and marks it as injected if it is.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The current time stamp on the core.
+    ///
+    /// # Return
+    /// True exactly once; on the first call at or after the time of the burst.
+    pub fn fire(&mut self, rdtsc: u64) -> bool {
+        if self.fired || rdtsc < self.at {
+            return false;
+        }
+        self.fired = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fires_once() {
+        let mut burst = TenantBurst::new(1, 1000, 10);
+        assert_eq!(burst.pending(), Some(1000));
+        assert!(!burst.fire(999));
+        assert!(burst.fire(1500));
+        assert!(!burst.fire(2000));
+        assert_eq!(burst.pending(), None);
+    }
+}
@@ -15,6 +15,7 @@
 
 #![feature(llvm_asm, integer_atomics)]
 
+extern crate bincode;
 extern crate serde;
 extern crate serde_aux;
 #[macro_use]
@@ -22,8 +23,12 @@ extern crate serde_derive;
 extern crate toml;
 #[macro_use]
 pub extern crate log;
+extern crate pcap;
 extern crate zipf;
 
+/// This module simulates a server-side LRU cache.
+pub mod cache;
+
 /// This module is used to read and parse the configuration file.
 pub mod config;
 
@@ -42,12 +47,93 @@ pub mod request;
 /// This module is used to generate the requests for given number of tenants.
 pub mod dispatcher;
 
+/// This module generates the gaps between the request arrivals of a renewal process.
+pub mod arrival;
+
+/// This module generates correlated arrivals for the tenants; e.g. flash crowds.
+pub mod tenant_arrivals;
+
 /// This module contains the CPU cycles related functionality; rdtsc() etc.
 pub mod cycles;
 
 /// This module contains a trait to implement the scheduler.
 pub mod sched;
 
+/// This module replays the request arrivals from a pcap trace.
+pub mod replay;
+
+/// This module reports the latency inflation for each tenant due to its neighbors on a core.
+pub mod fairness;
+
+/// This module generates the service times of the requests.
+pub mod taskgen;
+
+/// This module contains the tenant classes; strict priority between the classes.
+pub mod classes;
+
+/// This module records the state of the simulation at regular intervals of simulated time.
+pub mod snapshot;
+
+/// This module models a finite pool of server worker threads shared by the cores.
+pub mod workers;
+
+/// This module collects the arriving requests into batches, which share the per-request overhead.
+pub mod arrival_batch;
+
+/// This module contains a scheduler which dispatches the short tasks of a tenant in batches.
+pub mod batch_sched;
+
+/// This module contains a weighted round-robin scheduler over the tasks of several tenants.
+pub mod wrr_sched;
+
+/// This module contains a FCFS scheduler with priorities, where a task inherits the priority of
+/// the tasks waiting on it.
+pub mod pi_sched;
+
+/// This module contains a least-laxity-first scheduler for the tasks with deadlines.
+pub mod llf_sched;
+
+/// This module stops a run once its p99 latency converges.
+pub mod convergence;
+
+/// This module prints the progress of long runs.
+pub mod progress;
+
+/// This module detects the overloaded cores and sheds a fraction of their arrivals.
+pub mod overload;
+
+/// This module injects the faults into the simulation; a burst of requests from one tenant.
+pub mod faults;
+
+/// This module writes the timeline of each completed request to a JSON lines log.
+pub mod tracelog;
+
+/// This module contains a scheduler which runs the latency-critical tenants ahead of the
+/// best-effort ones.
+pub mod two_class_sched;
+
+/// This module writes each scheduling decision of the cores to a JSON lines log.
+pub mod decisionlog;
+
+/// This module models an idealized processor-sharing server.
+pub mod ps_sched;
+
+/// This module contains a scheduler with strict priority between the request classes, and a
+/// separate scheduler within each class.
+pub mod class_sched;
+
+/// This module runs the simulation once for each value of a configuration parameter.
+pub mod sweep;
+
+/// This module runs independent replications of the simulation and aggregates their results.
+pub mod replications;
+
+/// This module contains a scheduler with admission control over the tenants' reserved rates.
+pub mod admission_sched;
+
+/// This module measures the throughput of the scheduler operations, outside the simulation.
+pub mod sched_bench;
+
 // Different scheduling techniques.
 mod minos_sched;
 mod rr_sched;
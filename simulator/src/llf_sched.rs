@@ -0,0 +1,232 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::cycles;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+/// Least-laxity-first; the task with the least slack, i.e. its deadline minus the current time
+/// minus its remaining service time, runs next. All the waiting tasks share the current time, so
+/// the order only depends on the deadline minus the remaining time, which is recomputed at each
+/// pick as the preempted tasks come back with less time remaining. A task which already missed its
+/// deadline has a negative slack, and keeps running ahead of the others.
+#[derive(Serialize, Deserialize)]
+pub struct LeastLaxityScheduler {
+    // The waiting tasks, in the order they were added to the run-queue.
+    rq: Vec<Box<Request>>,
+
+    // The deadline in cycles after its creation given to each task without one.
+    relative_deadline: u64,
+
+    // The sequence number for the next task added to the run-queue.
+    next_seq: u64,
+}
+
+impl LeastLaxityScheduler {
+    /// Create a least-laxity-first scheduler.
+    ///
+    /// # Arguments
+    /// `deadline_us`: The deadline in micro-seconds after its creation for each task without one;
+    ///                e.g. the `sla_us` from the configuration. Must be a positive number.
+    pub fn new(deadline_us: f64) -> LeastLaxityScheduler {
+        assert!(deadline_us > 0.0, "The deadline must be positive");
+        LeastLaxityScheduler {
+            rq: Vec::new(),
+            relative_deadline: cycles::from_microseconds(deadline_us),
+            next_seq: 0,
+        }
+    }
+
+    /// Return the slack of a task plus the current time, in cycles; the tasks are ordered by this
+    /// and then by the order they were added in.
+    fn laxity(req: &Request) -> (i64, u64) {
+        let deadline = req
+            .deadline()
+            .map_or(std::i64::MAX, |deadline| deadline as i64);
+        let remaining = cycles::from_microseconds(req.remaining_time()) as i64;
+        (deadline - remaining, req.seq())
+    }
+
+    /// Return the indexes of the waiting tasks from the least to the most laxity.
+    fn by_laxity(&self) -> Vec<usize> {
+        let mut indexes: Vec<usize> = (0..self.rq.len()).collect();
+        indexes.sort_by_key(|index| LeastLaxityScheduler::laxity(&self.rq[*index]));
+        indexes
+    }
+
+    /// Return the index of the task with the least laxity.
+    fn pick_index(&self) -> Option<usize> {
+        (0..self.rq.len()).min_by_key(|index| LeastLaxityScheduler::laxity(&self.rq[*index]))
+    }
+}
+
+impl Scheduler for LeastLaxityScheduler {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        let index = self.pick_index()?;
+        Some(self.rq.remove(index))
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.pick_index().map(|index| self.rq[index].as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        self.by_laxity()
+            .iter()
+            .take(n)
+            .map(|index| self.rq[*index].as_ref())
+            .collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        if req.deadline().is_none() {
+            let deadline = req.start_time() + self.relative_deadline;
+            req.set_deadline(deadline);
+        }
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
+        self.rq.push(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method. The laxity is recomputed at
+    // each pick, so the task moves to its new place right away.
+    fn update_task_time(&mut self, tenant_id: u16, new_time: f64) -> bool {
+        let next = self
+            .by_laxity()
+            .into_iter()
+            .find(|index| self.rq[*index].get_tenant() == tenant_id);
+        match next {
+            Some(index) => {
+                self.rq[index].set_task_time(new_time);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        let laxity = LeastLaxityScheduler::laxity(req);
+        for waiting in self.rq.iter() {
+            if LeastLaxityScheduler::laxity(waiting) < laxity {
+                return Err(format!(
+                    "LeastLaxityScheduler picked task {} while task {} had less slack",
+                    req.seq(),
+                    waiting.seq()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(deadline_us: f64, remaining_us: f64) -> Box<Request> {
+        let mut req = Box::new(Request::new(1, 0, 0, remaining_us));
+        req.set_deadline(cycles::from_microseconds(deadline_us));
+        req
+    }
+
+    #[test]
+    fn test_least_laxity_first() {
+        let mut sched = LeastLaxityScheduler::new(100.0);
+
+        // The first task has the earliest deadline, but the second one has less slack; the third
+        // already missed its deadline.
+        sched.enqueue_task(task(100.0, 10.0));
+        sched.enqueue_task(task(150.0, 80.0));
+        sched.enqueue_task(task(5.0, 10.0));
+
+        let top: Vec<u64> = sched
+            .top_n_tasks(CoreType::Small, 3)
+            .iter()
+            .map(|req| req.seq())
+            .collect();
+        let mut seqs = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            seqs.push(req.seq());
+        }
+        assert_eq!(seqs, vec![2, 1, 0]);
+        assert_eq!(top, seqs);
+    }
+
+    #[test]
+    fn test_update_task_time() {
+        // The same deadline for both; tenant 1's longer task has less slack and runs first.
+        let mut sched = LeastLaxityScheduler::new(100.0);
+        sched.create_task(0, 50.0, 1, 0);
+        sched.create_task(0, 10.0, 2, 1);
+        assert_eq!(sched.top_task(CoreType::Small).unwrap().get_tenant(), 1);
+
+        // Tenant 2's task turns out to be longer than estimated, and now has less slack.
+        assert!(sched.update_task_time(2, 80.0));
+        assert!(!sched.update_task_time(3, 80.0));
+        let req = sched.pick_next_task(CoreType::Small).unwrap();
+        assert_eq!(req.get_tenant(), 2);
+        assert_eq!(req.remaining_time(), 80.0);
+        assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+        assert_eq!(
+            sched.pick_next_task(CoreType::Small).unwrap().get_tenant(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_default_deadline() {
+        let mut sched = LeastLaxityScheduler::new(100.0);
+        sched.create_task(cycles::from_microseconds(50.0), 1.0, 1, 0);
+        let req = sched.pick_next_task(CoreType::Small).unwrap();
+        assert_eq!(req.deadline(), Some(cycles::from_microseconds(150.0)));
+    }
+}
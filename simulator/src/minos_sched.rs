@@ -16,16 +16,20 @@
 use super::consts;
 use super::cores::CoreType;
 use super::request::Request;
-use super::sched::Scheduler;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
 
 use std::collections::VecDeque;
 
+#[derive(Serialize, Deserialize)]
 pub struct Minos {
     // Task runqueue for small requests.
     pub small_rq: VecDeque<Box<Request>>,
 
     // Task runqueue for large request.
     pub large_rq: VecDeque<Box<Request>>,
+
+    // The sequence number for the next task added to a runqueue.
+    next_seq: u64,
 }
 
 impl Minos {
@@ -33,14 +37,25 @@ impl Minos {
         Minos {
             small_rq: VecDeque::with_capacity(32),
             large_rq: VecDeque::with_capacity(32),
+            next_seq: 0,
         }
     }
 }
 
 impl Scheduler for Minos {
     // Lookup the `Scheduler` trait for documentation on this method.
-    fn create_task(&mut self, rdtsc: u64, task_time: f64, tenant_id: u16) {
-        let req = Box::new(Request::new(tenant_id, rdtsc, task_time));
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
         if task_time == consts::TASK_DISTRIBUTION_TIME[0] {
             self.small_rq.push_back(req);
         } else {
@@ -63,8 +78,214 @@ impl Scheduler for Minos {
         }
     }
 
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, coretype: CoreType) -> Option<&Request> {
+        let rq = match coretype {
+            CoreType::Small => &self.small_rq,
+            CoreType::Large => &self.large_rq,
+        };
+        rq.front().map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, coretype: CoreType, n: usize) -> Vec<&Request> {
+        let rq = match coretype {
+            CoreType::Small => &self.small_rq,
+            CoreType::Large => &self.large_rq,
+        };
+        rq.iter().take(n).map(|req| req.as_ref()).collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
     // Lookup the `Scheduler` trait for documentation on this method.
     fn enqueue_task(&mut self, req: Box<Request>) {
         self.large_rq.push_front(req);
     }
+
+    // Lookup the `Scheduler` trait for documentation on this method. The task stays in its
+    // run-queue; Minos only classifies the tasks when they are created.
+    fn update_task_time(&mut self, tenant_id: u16, new_time: f64) -> bool {
+        match self
+            .small_rq
+            .iter_mut()
+            .chain(self.large_rq.iter_mut())
+            .find(|req| req.get_tenant() == tenant_id)
+        {
+            Some(req) => {
+                req.set_task_time(new_time);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, coretype: CoreType) -> Result<(), String> {
+        let is_small = req.max_time() == consts::TASK_DISTRIBUTION_TIME[0];
+        match coretype {
+            CoreType::Small => {
+                if !is_small {
+                    return Err(format!(
+                        "Minos picked large task {} on a small core",
+                        req.seq()
+                    ));
+                }
+
+                // Small tasks are never preempted; these must be picked in the arrival order.
+                for waiting in self.small_rq.iter() {
+                    if waiting.seq() < req.seq() {
+                        return Err(format!(
+                            "Minos picked task {} while task {} was waiting",
+                            req.seq(),
+                            waiting.seq()
+                        ));
+                    }
+                }
+            }
+
+            CoreType::Large => {
+                if is_small {
+                    return Err(format!(
+                        "Minos picked small task {} on a large core",
+                        req.seq()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn debug_state(&self) -> String {
+        let head = |rq: &VecDeque<Box<Request>>| match rq.front() {
+            Some(req) => format!("{:.2}us", req.remaining_time()),
+            None => "empty".to_string(),
+        };
+        format!(
+            "small_rq {} (head {}) large_rq {} (head {})",
+            self.small_rq.len(),
+            head(&self.small_rq),
+            self.large_rq.len(),
+            head(&self.large_rq)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::time::Instant;
+
+    #[test]
+    fn test_top_task() {
+        let mut minos = Minos::new();
+        assert!(minos.top_task(CoreType::Small).is_none());
+        assert!(minos.top_task(CoreType::Large).is_none());
+
+        // A small task is only visible to the small cores.
+        minos.create_task(0, consts::TASK_DISTRIBUTION_TIME[0], 1, 7);
+        assert_eq!(minos.top_task(CoreType::Small).unwrap().key_id(), 7);
+        assert!(minos.top_task(CoreType::Large).is_none());
+
+        // A preempted task goes to the head of the large runqueue.
+        let req = minos.pick_next_task(CoreType::Small).unwrap();
+        minos.enqueue_task(req);
+        assert!(minos.top_task(CoreType::Small).is_none());
+        assert_eq!(minos.top_task(CoreType::Large).unwrap().key_id(), 7);
+    }
+
+    #[test]
+    fn test_top_n_tasks() {
+        let mut minos = Minos::new();
+        for i in 0..4 {
+            minos.create_task(0, consts::TASK_DISTRIBUTION_TIME[0], 1, i);
+        }
+        minos.create_task(0, 250.5, 1, 4);
+
+        // Each core type only sees its own runqueue, in the order the tasks are picked.
+        let large = minos.top_n_tasks(CoreType::Large, 3);
+        assert_eq!(large.len(), 1);
+        assert_eq!(large[0].key_id(), 4);
+        let small: Vec<u64> = minos
+            .top_n_tasks(CoreType::Small, 3)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        for key in small {
+            assert_eq!(minos.pick_next_task(CoreType::Small).unwrap().key_id(), key);
+        }
+        assert_eq!(minos.top_n_tasks(CoreType::Small, 3).len(), 1);
+    }
+
+    #[test]
+    fn test_debug_state() {
+        let mut minos = Minos::new();
+        assert_eq!(
+            minos.debug_state(),
+            "small_rq 0 (head empty) large_rq 0 (head empty)"
+        );
+
+        minos.create_task(0, consts::TASK_DISTRIBUTION_TIME[0], 1, 0);
+        minos.create_task(0, consts::TASK_DISTRIBUTION_TIME[0], 1, 1);
+        minos.enqueue_task(Box::new(Request::new(1, 2, 0, 250.5)));
+        assert_eq!(
+            minos.debug_state(),
+            format!(
+                "small_rq 2 (head {:.2}us) large_rq 1 (head 250.50us)",
+                consts::TASK_DISTRIBUTION_TIME[0]
+            )
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_stress_million_tasks() {
+        // Half the tasks are small; the others take up to 500us.
+        let tasks = 1000000;
+        let mut rng = StdRng::seed_from_u64(146);
+        let mut minos = Minos::new();
+        let start = Instant::now();
+        for i in 0..tasks {
+            let mut task_time = consts::TASK_DISTRIBUTION_TIME[0];
+            if rng.gen::<bool>() {
+                task_time = rng.gen_range(2.0, 500.0);
+            }
+            minos.create_task(i, task_time, 1, i);
+        }
+
+        // The small runqueue drains without touching the large one.
+        let large = minos.large_rq.len();
+        let mut picked = 0;
+        while let Some(req) = minos.pick_next_task(CoreType::Small) {
+            assert_eq!(req.max_time(), consts::TASK_DISTRIBUTION_TIME[0]);
+            assert_eq!(minos.large_rq.len(), large);
+            picked += 1;
+        }
+        assert!(minos.small_rq.is_empty());
+        while let Some(req) = minos.pick_next_task(CoreType::Large) {
+            assert!(req.max_time() != consts::TASK_DISTRIBUTION_TIME[0]);
+            picked += 1;
+        }
+        assert_eq!(picked, tasks);
+
+        // A baseline for the performance regressions; run with `cargo test --release -- --ignored`.
+        assert!(start.elapsed().as_secs_f64() < 1.0);
+    }
 }
@@ -0,0 +1,142 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+/// Detects when the queue on a core stays above a high-water mark for a sustained period, and
+/// then sheds a fraction of the new arrivals until the queue drains below the mark again. Without
+/// the shedding, the queue grows without bound once the arrival rate exceeds the service rate.
+pub struct OverloadDetector {
+    // The queue depth above which the core may be overloaded.
+    high_water: usize,
+
+    // The time in cycles for which the queue must stay above the mark to be an overload.
+    window: u64,
+
+    // The fraction of the arrivals shed during an overload.
+    shed_fraction: f64,
+
+    // The time stamp at which the queue went above the mark, if it is above the mark.
+    above_since: Option<u64>,
+
+    // The time stamp at which the current overload started, if the core is overloaded.
+    overloaded_since: Option<u64>,
+
+    // The time in cycles spent in the overloads which are over.
+    overload_cycles: u64,
+
+    // Accumulates `shed_fraction` on each arrival during an overload; an arrival is shed each
+    // time it reaches one.
+    credit: f64,
+
+    // The number of arrivals shed so far.
+    pub shed: u64,
+
+    // The deepest queue seen on an arrival.
+    pub max_queue: usize,
+}
+
+impl OverloadDetector {
+    /// Create an overload detector.
+    ///
+    /// # Arguments
+    /// `high_water`: The queue depth above which the core may be overloaded.
+    /// `window`: The time in cycles the queue must stay above `high_water` to be an overload.
+    /// `shed_fraction`: The fraction of the arrivals shed during an overload.
+    pub fn new(high_water: usize, window: u64, shed_fraction: f64) -> OverloadDetector {
+        OverloadDetector {
+            high_water: high_water,
+            window: window,
+            shed_fraction: shed_fraction,
+            above_since: None,
+            overloaded_since: None,
+            overload_cycles: 0,
+            credit: 0.0,
+            shed: 0,
+            max_queue: 0,
+        }
+    }
+
+    /// This method checks the queue on each arrival, and decides whether to admit the arrival.
+    ///
+    /// # Arguments
+    /// `queue`: The number of outstanding tasks on the core, before this arrival.
+    /// `rdtsc`: The time stamp of the arrival.
+    ///
+    /// # Return
+    /// False if the arrival must be shed.
+    pub fn admit(&mut self, queue: usize, rdtsc: u64) -> bool {
+        self.max_queue = std::cmp::max(self.max_queue, queue);
+        if queue >= self.high_water {
+            let since = *self.above_since.get_or_insert(rdtsc);
+            if self.overloaded_since.is_none() && rdtsc - since >= self.window {
+                self.overloaded_since = Some(rdtsc);
+            }
+        } else {
+            self.above_since = None;
+            if let Some(since) = self.overloaded_since.take() {
+                self.overload_cycles += rdtsc - since;
+            }
+        }
+
+        if self.overloaded_since.is_none() {
+            return true;
+        }
+        self.credit += self.shed_fraction;
+        if self.credit >= 1.0 {
+            self.credit -= 1.0;
+            self.shed += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Return true if the core is overloaded.
+    pub fn is_overloaded(&self) -> bool {
+        self.overloaded_since.is_some()
+    }
+
+    /// Return the time in cycles spent in overload up to the time stamp `rdtsc`.
+    pub fn overload_cycles(&self, rdtsc: u64) -> u64 {
+        self.overload_cycles + self.overloaded_since.map_or(0, |since| rdtsc - since)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shed_after_window() {
+        let mut overload = OverloadDetector::new(10, 100, 0.5);
+
+        // A short burst above the mark isn't an overload.
+        assert!(overload.admit(10, 0));
+        assert!(overload.admit(5, 50));
+        assert!(!overload.is_overloaded());
+
+        // Half the arrivals are shed once the queue stays above the mark for the window.
+        let admitted = (0..100)
+            .filter(|i| overload.admit(20, 100 + i * 10))
+            .count();
+        assert!(overload.is_overloaded());
+        assert_eq!(overload.shed, 45);
+        assert_eq!(admitted, 55);
+
+        // The overload ends once the queue drains.
+        assert!(overload.admit(0, 2000));
+        assert!(!overload.is_overloaded());
+        assert_eq!(overload.overload_cycles(5000), 2000 - 200);
+        assert_eq!(overload.max_queue, 20);
+    }
+}
@@ -0,0 +1,283 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::collections::HashMap;
+
+/// First-come first-served within each priority, with priority inheritance. A task which depends
+/// on another task only runs once that task completes, and the prerequisite runs with the highest
+/// priority among the tasks waiting on it, directly or through a chain of dependencies. Without
+/// the inheritance, a high priority task waiting on a low priority prerequisite would also wait
+/// behind all the medium priority tasks(priority inversion).
+#[derive(Serialize, Deserialize)]
+pub struct PriorityInheritance {
+    // The waiting tasks, in the order they were added to the run-queue.
+    rq: Vec<Box<Request>>,
+
+    // The sequence number for the next task added to the run-queue.
+    next_seq: u64,
+}
+
+impl PriorityInheritance {
+    pub fn new() -> PriorityInheritance {
+        PriorityInheritance {
+            rq: Vec::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Return the effective priority of each waiting task by its trace id; the highest of its own
+    /// priority and the effective priorities of the tasks waiting on it.
+    fn effective_priorities(&self) -> HashMap<u128, u8> {
+        self.effective_priorities_without(&[])
+    }
+
+    /// Same as `effective_priorities`, as if the tasks at the given indexes in the run-queue were
+    /// already picked.
+    fn effective_priorities_without(&self, picked: &[usize]) -> HashMap<u128, u8> {
+        let waiting = move || {
+            self.rq
+                .iter()
+                .enumerate()
+                .filter(move |(index, _)| !picked.contains(index))
+                .map(|(_, req)| req)
+        };
+        let mut priorities: HashMap<u128, u8> = waiting()
+            .map(|req| (req.trace_id(), req.priority()))
+            .collect();
+
+        // Each pass pushes the priorities one more step down the dependency chains.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for req in waiting() {
+                let prerequisite = match req.depends_on() {
+                    Some(id) if priorities.contains_key(&id) => id,
+                    _ => continue,
+                };
+                let inherited = priorities[&req.trace_id()];
+                if priorities[&prerequisite] < inherited {
+                    priorities.insert(prerequisite, inherited);
+                    changed = true;
+                }
+            }
+        }
+        priorities
+    }
+
+    /// Return true if the task can run; its prerequisite, if any, isn't waiting anymore.
+    fn is_runnable(&self, req: &Request) -> bool {
+        match req.depends_on() {
+            Some(id) => !self.rq.iter().any(|waiting| waiting.trace_id() == id),
+            None => true,
+        }
+    }
+
+    /// Return the index of the task to run next; the runnable task with the highest effective
+    /// priority, and the earliest one among those.
+    fn pick_index(&self) -> Option<usize> {
+        self.pick_index_without(&[])
+    }
+
+    /// Same as `pick_index`, as if the tasks at the given indexes in the run-queue were already
+    /// picked.
+    fn pick_index_without(&self, picked: &[usize]) -> Option<usize> {
+        let priorities = self.effective_priorities_without(picked);
+        let is_waiting = |id: u128| {
+            self.rq
+                .iter()
+                .enumerate()
+                .any(|(index, req)| req.trace_id() == id && !picked.contains(&index))
+        };
+        let mut next: Option<(usize, u8)> = None;
+        for (index, req) in self.rq.iter().enumerate() {
+            if picked.contains(&index) || req.depends_on().map_or(false, &is_waiting) {
+                continue;
+            }
+            let priority = priorities[&req.trace_id()];
+            match next {
+                Some((_, p)) if p >= priority => {}
+                _ => next = Some((index, priority)),
+            }
+        }
+        next.map(|(index, _)| index)
+    }
+}
+
+impl Scheduler for PriorityInheritance {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        let index = self.pick_index()?;
+        Some(self.rq.remove(index))
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.pick_index().map(|index| self.rq[index].as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        // Each pick may unblock a task, or change the inherited priorities; replay the picks.
+        let mut picked = Vec::with_capacity(n);
+        while picked.len() < n {
+            match self.pick_index_without(&picked) {
+                Some(index) => picked.push(index),
+                None => break,
+            }
+        }
+        picked
+            .iter()
+            .map(|index| self.rq[*index].as_ref())
+            .collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
+        self.rq.push(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        if !self.is_runnable(req) {
+            return Err(format!(
+                "PriorityInheritance picked task {} before its prerequisite",
+                req.seq()
+            ));
+        }
+
+        // The picked task inherits from the tasks still waiting on it.
+        let priorities = self.effective_priorities();
+        let inherited = self
+            .rq
+            .iter()
+            .filter(|waiting| waiting.depends_on() == Some(req.trace_id()))
+            .map(|waiting| priorities[&waiting.trace_id()])
+            .max()
+            .unwrap_or(0);
+        let priority = std::cmp::max(req.priority(), inherited);
+        for waiting in self.rq.iter().filter(|waiting| self.is_runnable(waiting)) {
+            let other = priorities[&waiting.trace_id()];
+            if other > priority || (other == priority && waiting.seq() < req.seq()) {
+                return Err(format!(
+                    "PriorityInheritance picked task {} (priority {}) while task {} (priority {}) was runnable",
+                    req.seq(),
+                    priority,
+                    waiting.seq(),
+                    other
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn debug_state(&self) -> String {
+        let blocked = self.rq.iter().filter(|req| !self.is_runnable(req)).count();
+        format!("rq {} (blocked {})", self.rq.len(), blocked)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(priority: u8) -> Box<Request> {
+        let mut req = Box::new(Request::new(1, 0, 0, 1.0));
+        req.set_priority(priority);
+        req
+    }
+
+    #[test]
+    fn test_priority_inheritance() {
+        // A medium priority task arrives first, then a low priority prerequisite, and a high
+        // priority task which waits on it.
+        let medium = task(1);
+        let low = task(0);
+        let mut high = task(2);
+        high.set_depends_on(low.trace_id());
+        let ids = (medium.trace_id(), low.trace_id(), high.trace_id());
+
+        let mut sched = PriorityInheritance::new();
+        sched.enqueue_task(medium);
+        sched.enqueue_task(low);
+        sched.enqueue_task(high);
+        assert_eq!(sched.debug_state(), "rq 3 (blocked 1)");
+
+        // The prerequisite runs before the medium task, as the high task waits on it.
+        let top: Vec<u128> = sched
+            .top_n_tasks(CoreType::Small, 3)
+            .iter()
+            .map(|req| req.trace_id())
+            .collect();
+        let mut order = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            order.push(req.trace_id());
+        }
+        assert_eq!(order, vec![ids.1, ids.2, ids.0]);
+        assert_eq!(top, order);
+    }
+
+    #[test]
+    fn test_fcfs_within_priority() {
+        let mut sched = PriorityInheritance::new();
+        for priority in [0, 1, 1, 0].iter() {
+            sched.enqueue_task(task(*priority));
+        }
+
+        let mut seqs = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            seqs.push(req.seq());
+        }
+        assert_eq!(seqs, vec![1, 2, 0, 3]);
+    }
+}
@@ -0,0 +1,115 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+
+/// Decides when to print the progress of a run; every `interval` completed requests, or every
+/// simulated second if the interval is zero. Each line has the number of completed requests, the
+/// throughput since the previous line and the number of outstanding requests.
+pub struct ProgressReporter {
+    // The number of completed requests between two lines; zero for a line every simulated second.
+    interval: u64,
+
+    // The number of completed requests at the previous line.
+    last_completed: u64,
+
+    // The simulated time in cycles at the previous line.
+    last_time: u64,
+}
+
+impl ProgressReporter {
+    /// Create a reporter.
+    ///
+    /// # Arguments
+    /// `interval`: The number of completed requests between two lines; zero for a line every
+    ///             simulated second.
+    pub fn new(interval: u64) -> ProgressReporter {
+        ProgressReporter {
+            interval: interval,
+            last_completed: 0,
+            last_time: 0,
+        }
+    }
+
+    /// Return true if a line is due.
+    ///
+    /// # Arguments
+    /// `completed`: The number of requests completed so far, on all the cores.
+    /// `now`: The simulated time in cycles.
+    pub fn is_due(&self, completed: u64, now: u64) -> bool {
+        if self.interval != 0 {
+            completed >= self.last_completed + self.interval
+        } else {
+            now >= self.last_time + cycles::cycles_per_second()
+        }
+    }
+
+    /// This method returns the progress line, if one is due.
+    ///
+    /// # Arguments
+    /// `completed`: The number of requests completed so far, on all the cores.
+    /// `now`: The simulated time in cycles.
+    /// `queue_depth`: The number of outstanding requests, on all the cores.
+    ///
+    /// # Return
+    /// The line to print, or None if it isn't due yet.
+    pub fn report(&mut self, completed: u64, now: u64, queue_depth: u64) -> Option<String> {
+        if !self.is_due(completed, now) {
+            return None;
+        }
+
+        let mut throughput = 0.0;
+        if now > self.last_time {
+            throughput =
+                (completed - self.last_completed) as f64 / cycles::to_seconds(now - self.last_time);
+        }
+        self.last_completed = completed;
+        self.last_time = now;
+        Some(format!(
+            "Progress: Completed {} Throughput(rps) {:.2} Queue-Depth {}",
+            completed, throughput, queue_depth
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_interval() {
+        let mut progress = ProgressReporter::new(1000);
+        assert!(progress.report(999, 0, 5).is_none());
+
+        // A line once 1000 requests complete, then not before another 1000.
+        let second = cycles::cycles_per_second();
+        assert_eq!(
+            progress.report(1200, second, 5).unwrap(),
+            "Progress: Completed 1200 Throughput(rps) 1200.00 Queue-Depth 5"
+        );
+        assert!(!progress.is_due(2199, 10 * second));
+        assert!(progress.is_due(2200, second));
+    }
+
+    #[test]
+    fn test_time_interval() {
+        let mut progress = ProgressReporter::new(0);
+        let second = cycles::cycles_per_second();
+        assert!(progress.report(100000, second - 1, 0).is_none());
+        assert!(progress.report(100000, second, 0).is_some());
+        assert!(!progress.is_due(200000, 2 * second - 1));
+        assert!(progress.is_due(200000, 2 * second));
+    }
+}
@@ -0,0 +1,187 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+use super::request::Request;
+
+/// An idealized processor-sharing server; all the requests in service make progress at the same
+/// time, each at 1/n of the core when there are n of them. The `Scheduler` trait runs one task at
+/// a time for a quantum, which can only approximate this, so the requests are served here between
+/// the arrivals instead; a request completes once it received its full task time.
+pub struct ProcessorSharing {
+    // The requests in service, with their remaining service time in cycles.
+    jobs: Vec<(f64, Box<Request>)>,
+
+    // The simulated time stamp in cycles up to which the requests were served.
+    now: f64,
+}
+
+impl ProcessorSharing {
+    pub fn new() -> ProcessorSharing {
+        ProcessorSharing {
+            jobs: Vec::new(),
+            now: 0.0,
+        }
+    }
+
+    /// Return the number of requests in service.
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Return true if no request is in service.
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// This method adds an arriving request to the requests in service.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The arrival time stamp in cycles.
+    /// `req`: The arriving request.
+    ///
+    /// # Return
+    /// The requests which completed before the arrival, with their completion time stamps.
+    pub fn add(&mut self, rdtsc: u64, req: Box<Request>) -> Vec<(u64, Box<Request>)> {
+        let completed = self.advance(rdtsc);
+        let remaining = req.remaining_time() * cycles::cycles_per_us();
+        self.jobs.push((remaining, req));
+        completed
+    }
+
+    /// Return the time stamp in cycles of the next completion, if nothing else arrives.
+    pub fn next_completion(&self) -> Option<u64> {
+        if self.jobs.is_empty() {
+            return None;
+        }
+        Some((self.now + self.shortest() * self.jobs.len() as f64) as u64)
+    }
+
+    /// Return the shortest remaining service time in cycles among the requests in service.
+    fn shortest(&self) -> f64 {
+        self.jobs
+            .iter()
+            .map(|(remaining, _)| *remaining)
+            .fold(std::f64::INFINITY, f64::min)
+    }
+
+    /// This method serves the requests up to a time stamp. Between two completions, each of the n
+    /// requests in service receives 1/n of the elapsed time.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The time stamp in cycles up to which the requests are served.
+    ///
+    /// # Return
+    /// The requests which completed, with their completion time stamps, in the completion order.
+    pub fn advance(&mut self, rdtsc: u64) -> Vec<(u64, Box<Request>)> {
+        let to = rdtsc as f64;
+        let mut completed = Vec::new();
+        while !self.jobs.is_empty() && self.now < to {
+            let n = self.jobs.len() as f64;
+            let shortest = self.shortest();
+
+            // Nothing completes before `to`; serve the requests for the rest of the time.
+            let finish = self.now + shortest * n;
+            if finish > to {
+                let served = (to - self.now) / n;
+                for job in self.jobs.iter_mut() {
+                    job.0 -= served;
+                }
+                break;
+            }
+
+            for job in self.jobs.iter_mut() {
+                job.0 -= shortest;
+            }
+            self.now = finish;
+            let mut i = 0;
+            while i < self.jobs.len() {
+                if self.jobs[i].0 <= 0.0 {
+                    let (_, req) = self.jobs.remove(i);
+                    completed.push((finish as u64, req));
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.now = self.now.max(to);
+        completed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::taskgen::{ExponentialTaskGen, TaskGenerator};
+    use super::*;
+
+    #[test]
+    fn test_equal_share() {
+        let mut ps = ProcessorSharing::new();
+        let us = cycles::from_microseconds;
+
+        // Two 2us requests arrive together and share the core. A 1us request arrives at 1us, and
+        // gets a third of the core until it completes at 4us; the first two then have 0.5us left
+        // each, and complete at 5us.
+        assert!(ps.add(0, Box::new(Request::new(1, 0, 0, 2.0))).is_empty());
+        assert!(ps.add(0, Box::new(Request::new(1, 1, 0, 2.0))).is_empty());
+        assert!(ps
+            .add(us(1.0), Box::new(Request::new(1, 2, us(1.0), 1.0)))
+            .is_empty());
+        assert_eq!(ps.len(), 3);
+        let near = |stamp: u64, t: f64| (stamp as i64 - us(t) as i64).abs() <= 1;
+        assert!(near(ps.next_completion().unwrap(), 4.0));
+
+        let completed = ps.advance(us(10.0));
+        let finished: Vec<(u64, u64)> = completed
+            .iter()
+            .map(|(finish, req)| (req.key_id(), *finish))
+            .collect();
+        assert_eq!(finished.len(), 3);
+        assert_eq!(finished[0].0, 2);
+        assert!(near(finished[0].1, 4.0));
+        assert!(near(finished[1].1, 5.0) && near(finished[2].1, 5.0));
+        assert!(ps.is_empty());
+        assert_eq!(ps.next_completion(), None);
+    }
+
+    #[test]
+    fn test_mm1_ps_response_time() {
+        // Poisson arrivals every 2us on average, and exponential 1us task times; the load is 0.5.
+        let (mean_s, load) = (1e-6, 0.5);
+        let mut gaps = ExponentialTaskGen::with_seed(mean_s / load, 1);
+        let mut tasks = ExponentialTaskGen::with_seed(mean_s, 2);
+
+        let mut ps = ProcessorSharing::new();
+        let mut completed = Vec::new();
+        let mut arrival = 0.0;
+        for i in 0..200000 {
+            arrival += gaps.next_service_time() * cycles::cycles_per_second() as f64;
+            let task_time = tasks.next_service_time() * 1e6;
+            let req = Box::new(Request::new(1, i, arrival as u64, task_time));
+            completed.extend(ps.add(arrival as u64, req));
+        }
+        completed.extend(ps.advance(std::u64::MAX));
+        assert_eq!(completed.len(), 200000);
+
+        // M/M/1-PS: the mean response time is E[S] / (1 - load).
+        let total: f64 = completed
+            .iter()
+            .map(|(finish, req)| cycles::to_seconds(finish - req.start_time()))
+            .sum();
+        let mean = total / completed.len() as f64;
+        let expected = mean_s / (1.0 - load);
+        assert!((mean - expected).abs() < 0.05 * expected, "mean {}", mean);
+    }
+}
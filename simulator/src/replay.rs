@@ -0,0 +1,247 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+
+use pcap::{Capture, Error};
+
+// Offsets in an Ethernet frame carrying an IPv4 packet.
+const ETHERTYPE_OFFSET: usize = 12;
+const IPV4_OFFSET: usize = 14;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// A request arrival extracted from a packet trace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arrival {
+    // The simulated time in cycles at which the request arrives.
+    pub time: u64,
+
+    // The tenant for this request; derived from the UDP destination port.
+    pub tenant: u16,
+
+    // The size of the UDP payload in bytes.
+    pub size: usize,
+
+    // The task time in micro-seconds derived from the size; only set for the text traces.
+    pub task_time: Option<f64>,
+}
+
+/// A linear model which maps the size of a request to its task time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TaskTimeModel {
+    // The task time in micro-seconds for an empty request.
+    pub base_us: f64,
+
+    // The task time in micro-seconds added for each byte of the request.
+    pub us_per_byte: f64,
+}
+
+impl TaskTimeModel {
+    /// Return the task time in micro-seconds for a request of `size` bytes.
+    pub fn task_time(&self, size: usize) -> f64 {
+        self.base_us + self.us_per_byte * size as f64
+    }
+}
+
+/// The arrivals generated on each core during a run, with their final tenants and task times.
+/// Replaying them in another run, e.g. with another scheduler, gives it the identical workload,
+/// which the random number generators can't guarantee across the policies.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CapturedWorkload {
+    // The arrivals on each core, in the order of the simulator's cores.
+    pub cores: Vec<Vec<Arrival>>,
+}
+
+impl CapturedWorkload {
+    /// Return the number of arrivals on all the cores.
+    pub fn len(&self) -> usize {
+        self.cores.iter().map(|arrivals| arrivals.len()).sum()
+    }
+
+    /// Return true if no request arrived.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone)]
+pub struct PcapReplay {
+    // The arrivals which are yet to be replayed, ordered by the arrival time.
+    arrivals: VecDeque<Arrival>,
+}
+
+impl PcapReplay {
+    /// This method reads the UDP packets from a pcap file with Ethernet framing, and converts
+    /// them into request arrivals.
+    ///
+    /// # Arguments
+    /// `path`: The pcap file to read.
+    /// `speedup`: The factor by which the trace is sped up; 2.0 replays it in half the time.
+    /// `num_tenants`: The tenant for a packet is `(destination port % num_tenants) + 1`.
+    ///
+    /// # Return
+    /// The arrivals, with the first packet in the trace arriving at time zero.
+    pub fn open(path: &str, speedup: f64, num_tenants: u64) -> Result<PcapReplay, Error> {
+        let mut capture = Capture::from_file(path)?;
+        let mut arrivals = VecDeque::new();
+        let mut first = None;
+
+        loop {
+            let packet = match capture.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::NoMorePackets) => break,
+                Err(e) => return Err(e),
+            };
+
+            let (port, size) = match udp_header(packet.data) {
+                Some(header) => header,
+                None => continue,
+            };
+
+            let usecs = packet.header.ts.tv_sec as u64 * 1000000 + packet.header.ts.tv_usec as u64;
+            let first = *first.get_or_insert(usecs);
+            let time = ((usecs - first) as f64 / speedup) * cycles::cycles_per_us();
+            arrivals.push_back(Arrival {
+                time: time as u64,
+                tenant: ((port as u64 % num_tenants) + 1) as u16,
+                size: size,
+                task_time: None,
+            });
+        }
+
+        Ok(PcapReplay { arrivals: arrivals })
+    }
+
+    /// This method reads a text trace, with one `timestamp_us,size_bytes` line per request, and
+    /// converts the lines into request arrivals. Empty lines and lines starting with `#` are
+    /// skipped.
+    ///
+    /// # Arguments
+    /// `path`: The text file to read.
+    /// `speedup`: The factor by which the trace is sped up; 2.0 replays it in half the time.
+    /// `num_tenants`: The requests are assigned to the tenants in turn; `(line % num_tenants) + 1`.
+    /// `model`: Maps the size of a request to its task time.
+    ///
+    /// # Return
+    /// The arrivals, with the first request in the trace arriving at time zero.
+    pub fn open_text(
+        path: &str,
+        speedup: f64,
+        num_tenants: u64,
+        model: TaskTimeModel,
+    ) -> io::Result<PcapReplay> {
+        let contents = fs::read_to_string(path)?;
+        let mut arrivals = VecDeque::new();
+        let mut first = None;
+
+        let lines = contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for (i, line) in lines.enumerate() {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected timestamp_us,size_bytes: {}", line),
+                )
+            };
+            let mut fields = line.split(',').map(|field| field.trim());
+            let usecs: f64 = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(invalid)?;
+            let size: usize = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(invalid)?;
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+
+            let first = *first.get_or_insert(usecs);
+            let time = ((usecs - first) / speedup) * cycles::cycles_per_us();
+            arrivals.push_back(Arrival {
+                time: time as u64,
+                tenant: ((i as u64 % num_tenants) + 1) as u16,
+                size: size,
+                task_time: Some(model.task_time(size)),
+            });
+        }
+
+        Ok(PcapReplay { arrivals: arrivals })
+    }
+
+    /// Replay the given arrivals, ordered by the arrival time; e.g. a `CapturedWorkload`.
+    pub fn from_arrivals(arrivals: Vec<Arrival>) -> PcapReplay {
+        PcapReplay {
+            arrivals: arrivals.into_iter().collect(),
+        }
+    }
+
+    /// Keep the arrivals only for the tenants in `low..high`; used to split a trace across cores.
+    pub fn retain_tenants(&mut self, low: u16, high: u16) {
+        self.arrivals
+            .retain(|arrival| arrival.tenant >= low && arrival.tenant < high);
+    }
+
+    /// Return the time at which the next request arrives, if any.
+    pub fn next_time(&self) -> Option<u64> {
+        self.arrivals.front().map(|arrival| arrival.time)
+    }
+
+    /// Return the next arrival, if it arrives at or before `curr`.
+    pub fn next_arrival(&mut self, curr: u64) -> Option<Arrival> {
+        match self.next_time() {
+            Some(time) if time <= curr => self.arrivals.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Return the number of arrivals which are yet to be replayed.
+    pub fn len(&self) -> usize {
+        self.arrivals.len()
+    }
+
+    /// Return true if all the arrivals have been replayed.
+    pub fn is_empty(&self) -> bool {
+        self.arrivals.is_empty()
+    }
+}
+
+// Return the destination port and the payload size for an Ethernet/IPv4/UDP frame.
+fn udp_header(data: &[u8]) -> Option<(u16, usize)> {
+    if data.len() < IPV4_OFFSET + 20 {
+        return None;
+    }
+
+    let ethertype = u16::from_be_bytes([data[ETHERTYPE_OFFSET], data[ETHERTYPE_OFFSET + 1]]);
+    if ethertype != ETHERTYPE_IPV4 || data[IPV4_OFFSET + 9] != IP_PROTOCOL_UDP {
+        return None;
+    }
+
+    let udp = IPV4_OFFSET + (data[IPV4_OFFSET] & 0x0f) as usize * 4;
+    if data.len() < udp + 8 {
+        return None;
+    }
+
+    let port = u16::from_be_bytes([data[udp + 2], data[udp + 3]]);
+    let length = u16::from_be_bytes([data[udp + 4], data[udp + 5]]) as usize;
+    Some((port, length.saturating_sub(8)))
+}
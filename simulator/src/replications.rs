@@ -0,0 +1,202 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::Config;
+use super::cores::{SimulationResult, Simulator};
+use super::cycles;
+
+// The two-sided 95% critical values of the t-distribution, for 1 to 30 degrees of freedom.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Return the two-sided 95% critical value of the t-distribution; the normal value above 120
+/// degrees of freedom.
+///
+/// # Arguments
+/// `df`: The degrees of freedom; at least 1.
+pub fn t_critical_95(df: u32) -> f64 {
+    match df {
+        0 => std::f64::INFINITY,
+        1..=30 => T_95[df as usize - 1],
+        31..=40 => 2.021,
+        41..=60 => 2.000,
+        61..=120 => 1.980,
+        _ => 1.960,
+    }
+}
+
+/// The spread of one metric over the replications of a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MetricSummary {
+    // The mean over the replications.
+    pub mean: f64,
+
+    // The sample standard deviation over the replications.
+    pub std_dev: f64,
+
+    // The 95% confidence interval of the mean; (low, high).
+    pub ci95: (f64, f64),
+}
+
+impl MetricSummary {
+    /// Return the summary of a metric, with a confidence interval from the t-distribution.
+    ///
+    /// # Arguments
+    /// `samples`: The value of the metric in each replication; at least 2.
+    pub fn new(samples: &[f64]) -> MetricSummary {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let std_dev = variance.sqrt();
+        let half_width = t_critical_95(samples.len() as u32 - 1) * std_dev / n.sqrt();
+        MetricSummary {
+            mean: mean,
+            std_dev: std_dev,
+            ci95: (mean - half_width, mean + half_width),
+        }
+    }
+
+    /// Return true if the value lies in the confidence interval.
+    pub fn contains(&self, value: f64) -> bool {
+        self.ci95.0 <= value && value <= self.ci95.1
+    }
+}
+
+/// The results of the independent replications of a run.
+#[derive(Clone, Debug, Default)]
+pub struct AggregatedResult {
+    // The seed of each replication.
+    pub seeds: Vec<u64>,
+
+    // The completed requests per second of simulated time.
+    pub throughput: MetricSummary,
+
+    // The mean latency in micro-seconds.
+    pub mean_latency_us: MetricSummary,
+
+    // The median latency in micro-seconds.
+    pub median_latency_us: MetricSummary,
+
+    // The 99th percentile latency in micro-seconds.
+    pub p99_latency_us: MetricSummary,
+}
+
+/// Return the throughput of a run in completed requests per second of simulated time.
+fn throughput(result: &SimulationResult) -> f64 {
+    if result.elapsed_cycles == 0 {
+        return 0.0;
+    }
+    result.requests_processed as f64 / cycles::to_seconds(result.elapsed_cycles)
+}
+
+/// This method runs the simulation several times with different seeds, and aggregates the
+/// results; to tell the effect of a change apart from the noise of the random numbers. Each run
+/// starts from a new simulator, like in a sweep.
+///
+/// # Arguments
+/// `config`: The configuration for the runs; its seed is replaced.
+/// `n`: The number of replications; at least 2.
+/// `seeds`: The seed for each replication, to reproduce the runs; random seeds if none.
+///
+/// # Return
+/// The mean, standard deviation and 95% confidence interval of each metric, or an error if there
+/// are too few replications, or not one seed for each.
+pub fn run_replications(
+    config: &Config,
+    n: u32,
+    seeds: Option<Vec<u64>>,
+) -> Result<AggregatedResult, String> {
+    if n < 2 {
+        return Err(format!("{} replications; at least 2 are needed", n));
+    }
+    let seeds = seeds.unwrap_or_else(|| (0..n).map(|_| rand::random::<u64>()).collect());
+    if seeds.len() != n as usize {
+        return Err(format!("{} seeds for {} replications", seeds.len(), n));
+    }
+
+    let results: Vec<SimulationResult> = seeds
+        .iter()
+        .map(|seed| {
+            let mut config = config.clone();
+            config.seed = Some(*seed);
+            Simulator::with_config(config).start()
+        })
+        .collect();
+    let metric = |f: &dyn Fn(&SimulationResult) -> f64| {
+        let samples: Vec<f64> = results.iter().map(f).collect();
+        MetricSummary::new(&samples)
+    };
+
+    Ok(AggregatedResult {
+        seeds: seeds,
+        throughput: metric(&throughput),
+        mean_latency_us: metric(&|r| r.mean_latency_us()),
+        median_latency_us: metric(&|r| cycles::to_microseconds(r.latency.median)),
+        p99_latency_us: metric(&|r| cycles::to_microseconds(r.latency.p99)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::{test_config, ServiceTimeDistribution};
+    use super::*;
+
+    #[test]
+    fn test_replications() {
+        // Exponential service times of 1us at a load of 0.8, so that each run is different.
+        let mut config = test_config();
+        config.service_time_distribution = ServiceTimeDistribution::Exponential;
+        config.service_time_mean_us = 1.0;
+        config.req_rate = 800000;
+        let seeds: Vec<u64> = (1..11).collect();
+
+        let result = run_replications(&config, 10, Some(seeds.clone())).unwrap();
+        assert_eq!(result.seeds, seeds);
+        for metric in [
+            result.throughput,
+            result.mean_latency_us,
+            result.median_latency_us,
+            result.p99_latency_us,
+        ]
+        .iter()
+        {
+            assert!(metric.std_dev > 0.0);
+            assert!(metric.contains(metric.mean));
+            let half_width = metric.ci95.1 - metric.mean;
+            let expected = 2.262 * metric.std_dev / 10f64.sqrt();
+            assert!((half_width - expected).abs() <= 1e-9 * expected.abs());
+        }
+
+        // The same seeds reproduce the same runs.
+        let again = run_replications(&config, 10, Some(seeds)).unwrap();
+        assert_eq!(again.p99_latency_us, result.p99_latency_us);
+    }
+
+    #[test]
+    fn test_metric_summary() {
+        let summary = MetricSummary::new(&[1.0, 2.0, 3.0]);
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.std_dev, 1.0);
+        assert!((summary.ci95.0 - (2.0 - 4.303 / 3f64.sqrt())).abs() < 1e-9);
+        assert_eq!(t_critical_95(1000), 1.960);
+
+        let config = test_config();
+        assert!(run_replications(&config, 1, None).is_err());
+        assert!(run_replications(&config, 3, Some(vec![1, 2])).is_err());
+    }
+}
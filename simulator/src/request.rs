@@ -13,12 +13,51 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
-use super::{config::Isolation, consts, cores::CoreType, cycles};
+use super::{config::Isolation, consts, cycles};
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Lets the client cancel a request, e.g. after it timed out waiting for the response; the
+/// clones share the same flag. A core drops a cancelled request instead of running it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    // True once the request is cancelled.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cancel the request; for all the clones of this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Request {
     // This task belong to tenant `tenant_id`.
     tenant_id: u16,
 
+    // The key accessed by this task; used to simulate the server-side cache.
+    key_id: u64,
+
+    // The key of the result of this task, if the task can share it with the other tasks for the
+    // same key; see `coalesce()`.
+    key: Option<u64>,
+
+    // The tasks coalesced with this one, which complete when this task completes.
+    coalesced: Vec<Box<Request>>,
+
     // The starting time for this task.
     start_time: u64,
 
@@ -30,9 +69,54 @@ pub struct Request {
 
     // The current state of the task.
     taskstate: TaskState,
+
+    // The order in which this task was last added to a run-queue; used to check the ordering.
+    seq: u64,
+
+    // The time at which this task was picked by a core for the first time.
+    first_dispatch: Option<u64>,
+
+    // The core on which this task ran the last time.
+    last_core: Option<u8>,
+
+    // A custom label to group the latency of this task by in the stats; e.g. hot or cold reads.
+    label: Option<u32>,
+
+    // A random id to follow this task in the trace log; like a UUID.
+    trace_id: u128,
+
+    // The priority of this task; higher runs first with the priority schedulers.
+    priority: u8,
+
+    // The trace id of the task which must complete before this task can run, if any.
+    depends_on: Option<u128>,
+
+    // The size of the request in bytes; zero if unknown.
+    size_bytes: usize,
+
+    // The time in cycles by which this task should complete, if it has a deadline.
+    deadline: Option<u64>,
+
+    // The number of times this task was preempted.
+    preemptions: u32,
+
+    // Lets the client cancel this task, if it can; not kept in the scheduler checkpoints.
+    #[serde(skip)]
+    cancel_token: Option<CancellationToken>,
+}
+
+/// A linear model of the time a core spends to set up a request before it first runs; e.g. the
+/// DMA and the parsing, which take longer for larger requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DispatchCost {
+    // The overhead in micro-seconds for an empty request.
+    pub base_us: f64,
+
+    // The overhead in micro-seconds added for each byte of the request.
+    pub us_per_byte: f64,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum TaskState {
     Runnable,
     Running,
@@ -41,36 +125,44 @@ pub enum TaskState {
 }
 
 impl Request {
-    pub fn new(tenant: u16, rdstc: u64, task_time: f64) -> Request {
+    pub fn new(tenant: u16, key: u64, rdstc: u64, task_time: f64) -> Request {
         Request {
             tenant_id: tenant,
+            key_id: key,
+            key: None,
+            coalesced: Vec::new(),
             start_time: rdstc,
             max_time: task_time,
             remaining_time: task_time,
             taskstate: TaskState::Runnable,
+            seq: 0,
+            first_dispatch: None,
+            last_core: None,
+            label: None,
+            trace_id: rand::random(),
+            priority: 0,
+            depends_on: None,
+            size_bytes: 0,
+            deadline: None,
+            preemptions: 0,
+            cancel_token: None,
         }
     }
 
-    pub fn run(&mut self, isolation: &Isolation, coretype: CoreType) -> (u64, TaskState) {
+    /// Run the task for at most `quantum` CPU cycles; the scheduler of the tenant decides the
+    /// quantum.
+    pub fn run(&mut self, isolation: &Isolation, quantum: u64) -> (u64, TaskState) {
         let mut time = 0;
-        let quant_time;
-        match coretype {
-            CoreType::Small => {
-                quant_time = consts::QUANTA_TIME;
-            }
-
-            CoreType::Large => {
-                quant_time = consts::LARGE_QUNATA_TIME;
-            }
-        }
+        let quant_time = cycles::to_microseconds(quantum);
 
         if self.remaining_time() <= quant_time {
-            time += ((cycles::cycles_per_second() as f64 / 1e6) * self.remaining_time) as u64;
+            time += cycles::from_microseconds(self.remaining_time);
             self.taskstate = TaskState::Completed;
         } else {
-            time += ((cycles::cycles_per_second() as f64 / 1e6) * quant_time) as u64;
+            time += quantum;
             self.remaining_time -= quant_time;
             self.taskstate = TaskState::Preempted;
+            self.preemptions += 1;
 
             match isolation {
                 Isolation::NoIsolation => {
@@ -94,6 +186,34 @@ impl Request {
         self.tenant_id.clone()
     }
 
+    pub fn key_id(&self) -> u64 {
+        self.key_id.clone()
+    }
+
+    pub fn key(&self) -> Option<u64> {
+        self.key.clone()
+    }
+
+    pub fn set_key(&mut self, key: u64) {
+        self.key = Some(key);
+    }
+
+    /// Attach a task for the same key to this one; it doesn't run, and completes when this task
+    /// completes.
+    pub fn coalesce(&mut self, req: Box<Request>) {
+        self.coalesced.push(req);
+    }
+
+    /// Return the number of tasks coalesced with this one.
+    pub fn num_coalesced(&self) -> usize {
+        self.coalesced.len()
+    }
+
+    /// Detach the tasks coalesced with this one, e.g. once it completed.
+    pub fn take_coalesced(&mut self) -> Vec<Box<Request>> {
+        std::mem::replace(&mut self.coalesced, Vec::new())
+    }
+
     pub fn start_time(&self) -> u64 {
         self.start_time.clone()
     }
@@ -105,4 +225,115 @@ impl Request {
     pub fn remaining_time(&self) -> f64 {
         self.remaining_time.clone()
     }
+
+    /// Replace the estimated task time with a refined one; the time the task already ran is
+    /// kept, so the remaining time shrinks or grows by the same amount, down to zero.
+    pub fn set_task_time(&mut self, task_time: f64) {
+        let ran = self.max_time - self.remaining_time;
+        self.max_time = task_time;
+        self.remaining_time = (task_time - ran).max(0.0);
+    }
+
+    /// Return the number of times this task was preempted.
+    pub fn preemptions(&self) -> u32 {
+        self.preemptions
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq.clone()
+    }
+
+    pub fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    /// Record the time at which a core picked this task; only the first dispatch is kept.
+    pub fn dispatch(&mut self, rdtsc: u64) {
+        if self.first_dispatch.is_none() {
+            self.first_dispatch = Some(rdtsc);
+        }
+    }
+
+    pub fn first_dispatch(&self) -> Option<u64> {
+        self.first_dispatch.clone()
+    }
+
+    pub fn last_core(&self) -> Option<u8> {
+        self.last_core.clone()
+    }
+
+    pub fn set_last_core(&mut self, core_id: u8) {
+        self.last_core = Some(core_id);
+    }
+
+    pub fn label(&self) -> Option<u32> {
+        self.label.clone()
+    }
+
+    pub fn set_label(&mut self, label: u32) {
+        self.label = Some(label);
+    }
+
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id.clone()
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority.clone()
+    }
+
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority;
+    }
+
+    pub fn depends_on(&self) -> Option<u128> {
+        self.depends_on.clone()
+    }
+
+    /// Make this task wait until the task with the trace id `prerequisite` completes.
+    pub fn set_depends_on(&mut self, prerequisite: u128) {
+        self.depends_on = Some(prerequisite);
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.size_bytes.clone()
+    }
+
+    pub fn set_size_bytes(&mut self, size_bytes: usize) {
+        self.size_bytes = size_bytes;
+    }
+
+    pub fn deadline(&self) -> Option<u64> {
+        self.deadline.clone()
+    }
+
+    pub fn set_deadline(&mut self, deadline: u64) {
+        self.deadline = Some(deadline);
+    }
+
+    /// Return the token to cancel this task, and attach one first if it has none.
+    pub fn cancel_token(&mut self) -> CancellationToken {
+        self.cancel_token
+            .get_or_insert_with(CancellationToken::new)
+            .clone()
+    }
+
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.cancel_token = Some(token);
+    }
+
+    /// Return true if the client cancelled this task.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token
+            .as_ref()
+            .map_or(false, |token| token.is_cancelled())
+    }
+
+    /// Return the time in CPU cycles to set up this task before it first runs.
+    ///
+    /// # Arguments
+    /// `cost`: The cost model for the dispatch overhead.
+    pub fn dispatch_overhead(&self, cost: &DispatchCost) -> u64 {
+        cycles::from_microseconds(cost.base_us + cost.us_per_byte * self.size_bytes as f64)
+    }
 }
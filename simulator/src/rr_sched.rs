@@ -14,29 +14,70 @@
  */
 
 use super::cores::CoreType;
+use super::cycles;
 use super::request::Request;
-use super::sched::Scheduler;
+use super::sched::{decode_state, encode_state, Scheduler};
 
 use std::collections::VecDeque;
 
+#[derive(Serialize, Deserialize)]
 pub struct RoundRobin {
     // Task runqueue for this tenant.
     pub rq: VecDeque<Box<Request>>,
+
+    // The sequence number for the next task added to the runqueue.
+    next_seq: u64,
+
+    // The time slice in CPU cycles after which a running task is preempted.
+    quantum: u64,
+
+    // If set, a new task is coalesced with a waiting task for the same key.
+    coalesce: bool,
+
+    // The number of tasks coalesced with another one instead of queueing.
+    pub coalesced: u64,
 }
 
 impl RoundRobin {
-    pub fn new() -> RoundRobin {
+    /// Create a round-robin scheduler which preempts the tasks after `quantum_us` micro-seconds.
+    /// The quantum is converted to cycles here, so the configs are portable across machines.
+    ///
+    /// # Arguments
+    /// `quantum_us`: The time slice in micro-seconds; must be a positive number.
+    pub fn new(quantum_us: f64) -> RoundRobin {
+        assert!(quantum_us > 0.0, "The quantum must be positive");
         RoundRobin {
             rq: VecDeque::with_capacity(32),
+            next_seq: 0,
+            quantum: cycles::from_microseconds(quantum_us),
+            coalesce: false,
+            coalesced: 0,
         }
     }
+
+    /// Coalesce each new task with a waiting task for the same key which didn't run yet, instead
+    /// of queueing it; see `Request::coalesce()`. The tasks are keyed by their `key_id`.
+    pub fn coalesce_by_key(&mut self) {
+        self.coalesce = true;
+    }
 }
 
 impl Scheduler for RoundRobin {
     // Lookup the `Scheduler` trait for documentation on this method.
-    fn create_task(&mut self, rdtsc: u64, task_time: f64, tenant_id: u16) {
-        let req = Box::new(Request::new(tenant_id, rdtsc, task_time));
-        self.rq.push_back(req);
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        if self.coalesce {
+            req.set_key(key_id);
+        }
+        self.enqueue_task(req);
     }
 
     // Lookup the `Scheduler` trait for documentation on this method.
@@ -45,7 +86,204 @@ impl Scheduler for RoundRobin {
     }
 
     // Lookup the `Scheduler` trait for documentation on this method.
-    fn enqueue_task(&mut self, req: Box<Request>) {
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.rq.front().map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        self.rq.iter().take(n).map(|req| req.as_ref()).collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, _type: CoreType) -> u64 {
+        self.quantum
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        if self.coalesce && req.key().is_some() && req.first_dispatch().is_none() {
+            let waiting = self
+                .rq
+                .iter_mut()
+                .find(|waiting| waiting.key() == req.key() && waiting.first_dispatch().is_none());
+            if let Some(waiting) = waiting {
+                self.coalesced += 1;
+                waiting.coalesce(req);
+                return;
+            }
+        }
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
         self.rq.push_back(req);
     }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn update_task_time(&mut self, tenant_id: u16, new_time: f64) -> bool {
+        match self.rq.iter_mut().find(|req| req.get_tenant() == tenant_id) {
+            Some(req) => {
+                req.set_task_time(new_time);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        // Tasks must be picked in the order they were added to the runqueue.
+        for waiting in self.rq.iter() {
+            if waiting.seq() < req.seq() {
+                return Err(format!(
+                    "RoundRobin picked task {} while task {} was waiting",
+                    req.seq(),
+                    waiting.seq()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::Isolation;
+    use super::super::request::TaskState;
+    use super::*;
+
+    #[test]
+    fn test_quantum_cycles() {
+        // The simulated clock runs at 3 GHz; 10 micro-seconds are 30000 cycles.
+        assert_eq!(cycles::cycles_per_second(), 3000000000);
+        let rr = RoundRobin::new(10.0);
+        assert_eq!(rr.quantum(CoreType::Small), 30000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_quantum_positive() {
+        RoundRobin::new(0.0);
+    }
+
+    #[test]
+    fn test_top_task() {
+        let mut rr = RoundRobin::new(5.0);
+        assert!(rr.top_task(CoreType::Small).is_none());
+
+        for i in 0..3 {
+            rr.create_task(i, 1.0 + i as f64, 1, i);
+        }
+        let top = rr.top_task(CoreType::Small).unwrap();
+        assert_eq!(top.key_id(), 0);
+
+        // Peeking leaves the task in the runqueue.
+        assert_eq!(rr.rq.len(), 3);
+        assert_eq!(rr.pick_next_task(CoreType::Small).unwrap().key_id(), 0);
+        assert_eq!(rr.top_task(CoreType::Small).unwrap().key_id(), 1);
+    }
+
+    #[test]
+    fn test_top_n_tasks() {
+        let mut rr = RoundRobin::new(5.0);
+        for i in 0..5 {
+            rr.create_task(i, 1.0, 1, i);
+        }
+        let top: Vec<u64> = rr
+            .top_n_tasks(CoreType::Small, 3)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        assert_eq!(rr.top_n_tasks(CoreType::Small, 10).len(), 5);
+
+        // The tasks are picked in the same order.
+        for key in top {
+            assert_eq!(rr.pick_next_task(CoreType::Small).unwrap().key_id(), key);
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut rr = RoundRobin::new(5.0);
+        for i in 0..100 {
+            rr.create_task(i, 1.0 + i as f64, (i % 4) as u16 + 1, i);
+        }
+        let state = rr.serialize_state();
+
+        // The quantum is restored along with the tasks.
+        let mut restored = RoundRobin::new(10.0);
+        restored.restore_state(&state).unwrap();
+        assert_eq!(
+            restored.quantum(CoreType::Small),
+            rr.quantum(CoreType::Small)
+        );
+        assert_eq!(restored.rq.len(), 100);
+        for i in 0..100 {
+            let req = restored.pick_next_task(CoreType::Small).unwrap();
+            assert_eq!(req.key_id(), i);
+            assert_eq!(req.get_tenant(), (i % 4) as u16 + 1);
+            assert_eq!(req.remaining_time(), 1.0 + i as f64);
+        }
+
+        // A new task gets the next sequence number after the restored ones.
+        restored.create_task(0, 1.0, 1, 100);
+        assert_eq!(restored.rq[0].seq(), 100);
+        assert!(restored.restore_state(&state[..10]).is_err());
+    }
+
+    #[test]
+    fn test_coalesce_by_key() {
+        let mut rr = RoundRobin::new(5.0);
+        rr.coalesce_by_key();
+        for i in 0..3 {
+            rr.create_task(i, 2.0, 1, 7);
+        }
+        rr.create_task(3, 2.0, 1, 8);
+        assert_eq!(rr.coalesced, 2);
+        assert_eq!(rr.rq.len(), 2);
+
+        // The three tasks for key 7 are served by a single execution.
+        let mut req = rr.pick_next_task(CoreType::Small).unwrap();
+        assert_eq!(req.num_coalesced(), 2);
+        let (_, state) = req.run(&Isolation::NoIsolation, rr.quantum(CoreType::Small));
+        assert!(state == TaskState::Completed);
+        let followers = req.take_coalesced();
+        let starts: Vec<u64> = followers.iter().map(|f| f.start_time()).collect();
+        assert_eq!(starts, vec![1, 2]);
+        assert!(followers.iter().all(|f| f.remaining_time() == 2.0));
+
+        // A task which already ran isn't coalesced with; the next task for its key queues.
+        let mut preempted = rr.pick_next_task(CoreType::Small).unwrap();
+        preempted.dispatch(4);
+        rr.enqueue_task(preempted);
+        rr.create_task(5, 2.0, 1, 8);
+        assert_eq!(rr.rq.len(), 2);
+        assert_eq!(rr.coalesced, 2);
+    }
+
+    #[test]
+    fn test_check_ordering() {
+        let mut rr = RoundRobin::new(5.0);
+        for i in 0..4 {
+            rr.create_task(i, 1.0, 1, 0);
+        }
+        let first = rr.pick_next_task(CoreType::Small).unwrap();
+        assert!(rr.check_ordering(&first, CoreType::Small).is_ok());
+
+        // Break the ordering by moving the last task to the head of the runqueue.
+        let last = rr.rq.pop_back().unwrap();
+        rr.rq.push_front(last);
+        let task = rr.pick_next_task(CoreType::Small).unwrap();
+        assert!(rr.check_ordering(&task, CoreType::Small).is_err());
+    }
 }
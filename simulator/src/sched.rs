@@ -13,17 +13,42 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+use super::consts;
 use super::cores::CoreType;
+use super::cycles;
 use super::request::Request;
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
 pub trait Scheduler {
+    /// This method creates a new task of an unknown size and adds that to the first run-queue.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The CPU time at which the task was created.
+    /// `task_time`: The amount of CPU Cycles this task needs to complete.
+    /// `tenant_id`: The tells the tenant for which this was created.
+    /// `key_id`: The key accessed by this task.
+    fn create_task(&mut self, rdtsc: u64, task_time: f64, tenant_id: u16, key_id: u64) {
+        self.create_sized_task(rdtsc, task_time, tenant_id, key_id, 0);
+    }
+
     /// This method creates a new task and adds that to the first run-queue.
     ///
     /// # Arguments
     /// `rdtsc`: The CPU time at which the task was created.
     /// `task_time`: The amount of CPU Cycles this task needs to complete.
     /// `tenant_id`: The tells the tenant for which this was created.
-    fn create_task(&mut self, rdtsc: u64, task_time: f64, tenant_id: u16);
+    /// `key_id`: The key accessed by this task.
+    /// `size_bytes`: The size of the request in bytes; see `Request::dispatch_overhead()`.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    );
 
     /// This method picks the next task to execute on the CPU.
     ///
@@ -34,9 +59,130 @@ pub trait Scheduler {
     /// Return a task to execute on the current CPU.
     fn pick_next_task(&mut self, coretype: CoreType) -> Option<Box<Request>>;
 
+    /// This method returns the task which `pick_next_task` would return, without removing it
+    /// from the run-queue.
+    ///
+    /// # Arguments
+    /// `coretype`: The current core type value; used in deciding which task to pick next.
+    ///
+    /// # Return
+    /// The next task, if any task is waiting.
+    fn top_task(&self, coretype: CoreType) -> Option<&Request>;
+
+    /// This method returns the next `n` tasks in the order in which `pick_next_task` would
+    /// return them, without removing them from the run-queues; e.g. to show the queue state.
+    ///
+    /// # Arguments
+    /// `coretype`: The current core type value; used in deciding which task to pick next.
+    /// `n`: The maximum number of tasks to return.
+    ///
+    /// # Return
+    /// Up to `n` waiting tasks, the first of which is the one `top_task` returns.
+    fn top_n_tasks(&self, coretype: CoreType, n: usize) -> Vec<&Request>;
+
+    /// This method returns the remaining time of the task which `pick_next_task` would return,
+    /// without removing it from the run-queue.
+    ///
+    /// # Arguments
+    /// `coretype`: The current core type value; used in deciding which task to pick next.
+    ///
+    /// # Return
+    /// The remaining time in micro-seconds of the next task, if any task is waiting.
+    fn peek_task_time(&self, coretype: CoreType) -> Option<f64> {
+        self.top_task(coretype).map(|req| req.remaining_time())
+    }
+
+    /// This method returns the time slice after which a running task is preempted.
+    ///
+    /// # Arguments
+    /// `coretype`: The type of the core on which the task runs.
+    ///
+    /// # Return
+    /// The time slice in CPU cycles.
+    fn quantum(&self, coretype: CoreType) -> u64;
+
     /// This method decides where to execute the task after preemption.
     ///
     /// # Argument
     /// `req`: The preempted task.
     fn enqueue_task(&mut self, req: Box<Request>);
+
+    /// This method refines the estimated task time of the next waiting task of a tenant, e.g. as
+    /// the estimate improves with the speculation; the schedulers which order the tasks by their
+    /// time take the new time into account from the next pick. This is O(n) in the number of
+    /// waiting tasks.
+    ///
+    /// # Arguments
+    /// `tenant_id`: The tenant of the task.
+    /// `new_time`: The new estimated task time in micro-seconds.
+    ///
+    /// # Return
+    /// True if a waiting task of the tenant was found; false if none is waiting, or if the
+    /// scheduler doesn't support the updates.
+    fn update_task_time(&mut self, _tenant_id: u16, _new_time: f64) -> bool {
+        false
+    }
+
+    /// This method checks that a task returned by `pick_next_task` is the one the scheduling
+    /// policy should have picked, given the tasks which are still waiting in the run-queues.
+    /// This is O(n) in the number of waiting tasks and only used with `--check-ordering`.
+    ///
+    /// # Arguments
+    /// `req`: The task returned by `pick_next_task`.
+    /// `coretype`: The core type value passed to `pick_next_task`.
+    ///
+    /// # Return
+    /// An error describing the violation, if the task shouldn't have been picked.
+    fn check_ordering(&self, req: &Request, coretype: CoreType) -> Result<(), String>;
+
+    /// This method serializes the full state of the scheduler for a checkpoint of the simulation;
+    /// the waiting tasks, the counters and the configuration.
+    ///
+    /// # Return
+    /// The state, which `restore_state` reads back into a scheduler of the same kind.
+    fn serialize_state(&self) -> Vec<u8>;
+
+    /// This method replaces the state of the scheduler with one from `serialize_state`.
+    ///
+    /// # Arguments
+    /// `data`: The serialized state of a scheduler of the same kind.
+    ///
+    /// # Return
+    /// An error if the data isn't a valid state for this scheduler; the state is unchanged then.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String>;
+
+    /// This method describes the internal state of the scheduler; e.g. the run-queue lengths. It
+    /// is only used to debug the experiments which behave oddly.
+    ///
+    /// # Return
+    /// A one line description of the state; empty if the scheduler doesn't describe it.
+    fn debug_state(&self) -> String {
+        String::new()
+    }
+}
+
+/// Serialize the state of a scheduler with bincode; see `Scheduler::serialize_state()`.
+pub fn encode_state<T: Serialize>(state: &T) -> Vec<u8> {
+    bincode::serialize(state).expect("couldn't serialize the scheduler state")
+}
+
+/// Deserialize the state of a scheduler written by `encode_state()`.
+///
+/// # Arguments
+/// `data`: The serialized state.
+///
+/// # Return
+/// The state, or an error if the data isn't a valid state of this type.
+pub fn decode_state<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+    bincode::deserialize(data).map_err(|e| format!("invalid scheduler state: {}", e))
+}
+
+/// Return the time slice in CPU cycles for the schedulers without a configurable quantum; see
+/// consts::QUANTA_TIME and consts::LARGE_QUNATA_TIME.
+pub fn default_quantum(coretype: CoreType) -> u64 {
+    let quantum_us = match coretype {
+        CoreType::Small => consts::QUANTA_TIME,
+        CoreType::Large => consts::LARGE_QUNATA_TIME,
+    };
+    cycles::from_microseconds(quantum_us)
 }
@@ -0,0 +1,145 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::{Config, Policy};
+use super::cores::CoreType;
+use super::minos_sched::Minos;
+use super::request::Request;
+use super::rr_sched::RoundRobin;
+use super::sched::Scheduler;
+
+use std::fmt;
+use std::time::Instant;
+
+// The number of tasks waiting in the run-queue during the measurement; the operations of some
+// schedulers get slower with longer queues.
+const QUEUE_DEPTH: u64 = 64;
+
+// The number of rounds run with `--bench-sched`.
+pub const DEFAULT_ROUNDS: u64 = 1000000;
+
+/// The throughput of the scheduler operations measured by `run()`.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchResult {
+    // The number of operations measured; creates, picks and enqueues.
+    pub ops: u64,
+
+    // The wall-clock time the operations took in nano-seconds.
+    pub nanos: u64,
+}
+
+impl BenchResult {
+    /// Return the number of operations per second of wall-clock time.
+    pub fn ops_per_sec(&self) -> f64 {
+        self.ops as f64 * 1e9 / std::cmp::max(self.nanos, 1) as f64
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Ops {} Time(ms) {:.2} Ops/sec {:.0}",
+            self.ops,
+            self.nanos as f64 / 1e6,
+            self.ops_per_sec()
+        )
+    }
+}
+
+/// Return a scheduler for one tenant with the policy in the config, like the simulator creates.
+pub fn scheduler_for(config: &Config) -> Box<dyn Scheduler> {
+    match config.policy {
+        Policy::RoundRobin => {
+            let mut rr = RoundRobin::new(config.rr_quantum_us);
+            if config.coalesce_requests {
+                rr.coalesce_by_key();
+            }
+            Box::new(rr)
+        }
+        Policy::Minos => Box::new(Minos::new()),
+    }
+}
+
+/// Pick the next task for a small core, or for a large core if none is waiting for a small one;
+/// like a pair of cores sharing the tenant. Each call to the scheduler is counted in `ops`.
+fn pick(sched: &mut dyn Scheduler, ops: &mut u64) -> Option<Box<Request>> {
+    *ops += 1;
+    match sched.pick_next_task(CoreType::Small) {
+        Some(req) => Some(req),
+        None => {
+            *ops += 1;
+            sched.pick_next_task(CoreType::Large)
+        }
+    }
+}
+
+/// Measure the throughput of a scheduler's operations, outside the simulation. The run-queue is
+/// first filled with `QUEUE_DEPTH` tasks; then each round creates a task, picks the next task and
+/// enqueues it back as if it was preempted, and picks the next task again as if it completed, so
+/// the run-queue stays at the same depth. The task times cycle through 1 to 16 micro-seconds, so
+/// the schedulers which order the tasks by their time see a mix.
+///
+/// # Arguments
+/// `sched`: The scheduler to measure.
+/// `rounds`: The number of rounds; each runs at least four operations.
+///
+/// # Return
+/// The number of operations run and the wall-clock time they took.
+pub fn run(mut sched: Box<dyn Scheduler>, rounds: u64) -> BenchResult {
+    let task_time = |i: u64| (i % 16 + 1) as f64;
+    for i in 0..QUEUE_DEPTH {
+        sched.create_task(i, task_time(i), 1, i);
+    }
+
+    let mut ops = 0;
+    let start = Instant::now();
+    for round in 0..rounds {
+        let i = QUEUE_DEPTH + round;
+        sched.create_task(i, task_time(i), 1, i);
+        ops += 1;
+        if let Some(req) = pick(sched.as_mut(), &mut ops) {
+            sched.enqueue_task(req);
+            ops += 1;
+        }
+        pick(sched.as_mut(), &mut ops);
+    }
+    BenchResult {
+        ops: ops,
+        nanos: start.elapsed().as_nanos() as u64,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::*;
+
+    #[test]
+    fn test_bench_policies() {
+        let mut config = test_config();
+        for policy in [Policy::RoundRobin, Policy::Minos].iter() {
+            config.policy = policy.clone();
+            let result = run(scheduler_for(&config), 10000);
+            assert!(result.ops >= 40000);
+            assert!(result.ops_per_sec() > 0.0);
+        }
+
+        // The round-robin run-queue always has a task for the small core.
+        config.policy = Policy::RoundRobin;
+        assert_eq!(run(scheduler_for(&config), 10000).ops, 40000);
+        assert_eq!(run(scheduler_for(&config), 0).ops, 0);
+    }
+}
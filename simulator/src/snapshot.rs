@@ -0,0 +1,148 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The requests completed on a core in one snapshot interval.
+#[derive(Clone, Debug, Default)]
+pub struct IntervalSample {
+    // The latencies in cycles of the requests completed in this interval.
+    pub latencies: Vec<u64>,
+
+    // The number of outstanding requests after the last completion in this interval.
+    pub queue_depth: u64,
+}
+
+impl IntervalSample {
+    /// Add the requests completed on another core in the same interval.
+    pub fn merge(&mut self, other: &IntervalSample) {
+        self.latencies.extend_from_slice(&other.latencies);
+        self.queue_depth += other.queue_depth;
+    }
+}
+
+/// The state of the simulation in one interval of simulated time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulationSnapshot {
+    // The simulated time at the start of the interval in nanoseconds.
+    pub time_ns: u64,
+
+    // The number of outstanding requests across all the cores at the end of the interval.
+    pub queue_depth: u64,
+
+    // The number of requests completed per second in the interval.
+    pub throughput_rps: f64,
+
+    // The mean latency of the requests completed in the interval.
+    pub mean_latency_ns: f64,
+
+    // The 99th percentile latency of the requests completed in the interval.
+    pub p99_latency_ns: f64,
+}
+
+impl SimulationSnapshot {
+    /// This method summarizes the requests completed in an interval.
+    ///
+    /// # Arguments
+    /// `interval`: The index of the interval, from the start of the simulation.
+    /// `interval_ns`: The length of each interval in nanoseconds.
+    /// `sample`: The requests completed in the interval, across all the cores.
+    ///
+    /// # Return
+    /// The snapshot for the interval.
+    pub fn new(interval: u64, interval_ns: u64, sample: &mut IntervalSample) -> SimulationSnapshot {
+        let to_ns = |cycles: f64| cycles * 1e9 / cycles::cycles_per_second() as f64;
+        let count = sample.latencies.len();
+
+        let mut mean = 0.0;
+        let mut p99 = 0.0;
+        if count != 0 {
+            sample.latencies.sort();
+            mean = sample.latencies.iter().sum::<u64>() as f64 / count as f64;
+            p99 = sample.latencies[(count * 99) / 100] as f64;
+        }
+
+        SimulationSnapshot {
+            time_ns: interval * interval_ns,
+            queue_depth: sample.queue_depth,
+            throughput_rps: count as f64 * 1e9 / interval_ns as f64,
+            mean_latency_ns: to_ns(mean),
+            p99_latency_ns: to_ns(p99),
+        }
+    }
+}
+
+/// Write the snapshots to a CSV file, one line per interval.
+///
+/// # Arguments
+/// `path`: The CSV file to write.
+/// `snapshots`: The snapshots, in the order of the simulated time.
+pub fn write_csv(path: &Path, snapshots: &[SimulationSnapshot]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "time_ns,queue_depth,throughput_rps,mean_latency_ns,p99_latency_ns"
+    )?;
+    for snapshot in snapshots {
+        writeln!(
+            writer,
+            "{},{},{:.2},{:.2},{:.2}",
+            snapshot.time_ns,
+            snapshot.queue_depth,
+            snapshot.throughput_rps,
+            snapshot.mean_latency_ns,
+            snapshot.p99_latency_ns
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::super::cores::Simulator;
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_snapshots() {
+        // The requests arrive every 10us on each core, for about 10ms.
+        let mut config = test_config();
+        config.snapshot_interval_ns = 1000000;
+
+        let result = Simulator::with_config(config).start();
+        assert!(result.snapshots.len() >= 10);
+        let mut completed = 0.0;
+        for pair in result.snapshots.windows(2) {
+            assert!(pair[0].time_ns < pair[1].time_ns);
+        }
+        for snapshot in result.snapshots.iter() {
+            assert_eq!(snapshot.time_ns % 1000000, 0);
+            completed += snapshot.throughput_rps * 1e-3;
+        }
+        assert!((completed - result.requests_processed as f64).abs() < 1e-3);
+
+        let path = env::temp_dir().join("simulator_test_snapshots.csv");
+        write_csv(&path, &result.snapshots).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), result.snapshots.len() + 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::config::{Config, ServiceTimeDistribution};
+use super::cores::{SimulationResult, Simulator};
+use super::cycles;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The configuration parameters which can be swept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SweepParameter {
+    // The request rate per second for each core; `req_rate`.
+    ArrivalRate,
+
+    // The mean service time in micro-seconds; `service_time_mean_us`, with the exponential
+    // service times.
+    ServiceTimeMean,
+}
+
+impl SweepParameter {
+    /// Return the parameter for its name on the command line; `arrival_rate` or
+    /// `service_time_mean`.
+    pub fn parse(name: &str) -> Option<SweepParameter> {
+        match name {
+            "arrival_rate" => Some(SweepParameter::ArrivalRate),
+            "service_time_mean" => Some(SweepParameter::ServiceTimeMean),
+            _ => None,
+        }
+    }
+
+    /// This method sets the parameter in the configuration.
+    ///
+    /// # Arguments
+    /// `config`: The configuration to update.
+    /// `value`: The value of the parameter for the run.
+    pub fn apply(&self, config: &mut Config, value: f64) {
+        match self {
+            SweepParameter::ArrivalRate => config.req_rate = value as u64,
+            SweepParameter::ServiceTimeMean => {
+                config.service_time_distribution = ServiceTimeDistribution::Exponential;
+                config.service_time_mean_us = value;
+            }
+        }
+    }
+}
+
+/// This method runs the simulation once for each value of a parameter, with all the other
+/// parameters from the configuration. Each run starts from a new simulator, so that no scheduler
+/// or core state carries over from the previous run.
+///
+/// # Arguments
+/// `config`: The configuration for the runs.
+/// `parameter`: The name of the parameter to sweep; see `SweepParameter::parse()`.
+/// `values`: The values of the parameter, one run for each.
+///
+/// # Return
+/// The result of each run in the order of `values`, or an error if the parameter is unknown or a
+/// value makes the configuration invalid.
+pub fn run_sweep(
+    config: &Config,
+    parameter: &str,
+    values: &[f64],
+) -> Result<Vec<SimulationResult>, String> {
+    let parameter = SweepParameter::parse(parameter).ok_or_else(|| {
+        format!(
+            "Unknown sweep parameter {}; expected arrival_rate or service_time_mean",
+            parameter
+        )
+    })?;
+
+    let mut configs = Vec::with_capacity(values.len());
+    for value in values.iter() {
+        let mut config = config.clone();
+        parameter.apply(&mut config, *value);
+        if let Err(errors) = config.validate() {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(format!(
+                "Invalid config for {:?} {}: {}",
+                parameter,
+                value,
+                errors.join("; ")
+            ));
+        }
+        configs.push(config);
+    }
+
+    Ok(configs
+        .into_iter()
+        .map(|config| Simulator::with_config(config).start())
+        .collect())
+}
+
+/// Write the results of a sweep to a CSV file, one line per value of the parameter.
+///
+/// # Arguments
+/// `path`: The CSV file to write.
+/// `parameter`: The name of the swept parameter; the header of the first column.
+/// `values`: The values of the parameter.
+/// `results`: The result of the run for each value.
+pub fn write_csv(
+    path: &Path,
+    parameter: &str,
+    values: &[f64],
+    results: &[SimulationResult],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "{},requests_processed,mean_latency_us,median_latency_us,p99_latency_us",
+        parameter
+    )?;
+    for (value, result) in values.iter().zip(results.iter()) {
+        writeln!(
+            writer,
+            "{},{},{:.2},{:.2},{:.2}",
+            value,
+            result.requests_processed,
+            result.mean_latency_us(),
+            cycles::to_microseconds(result.latency.median),
+            cycles::to_microseconds(result.latency.p99)
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_arrival_rate_sweep() {
+        // Exponential service times of 1us on each core; the load goes from 0.5 to 1.5.
+        let mut config = test_config();
+        config.num_reqs = 5000;
+        config.num_resps = 5000;
+        config.service_time_distribution = ServiceTimeDistribution::Exponential;
+        config.service_time_mean_us = 1.0;
+        let values = [500000.0, 750000.0, 1000000.0, 1250000.0, 1500000.0];
+
+        let results = run_sweep(&config, "arrival_rate", &values).unwrap();
+        assert_eq!(results.len(), values.len());
+        for pair in results.windows(2) {
+            assert!(pair[0].mean_latency_us() < pair[1].mean_latency_us());
+        }
+        assert!(results[4].latency.p99 > results[0].latency.p99 * 10);
+
+        let path = env::temp_dir().join("simulator_test_sweep.csv");
+        write_csv(&path, "arrival_rate", &values, &results).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("arrival_rate,"));
+        assert!(lines[1].starts_with("500000,"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_sweep() {
+        let config = test_config();
+        assert!(run_sweep(&config, "num_tenants", &[1.0]).is_err());
+        assert!(run_sweep(&config, "arrival_rate", &[0.0]).is_err());
+        assert!(run_sweep(&config, "service_time_mean", &[-1.0]).is_err());
+    }
+}
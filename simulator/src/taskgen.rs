@@ -0,0 +1,75 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Generates the service times of the new requests.
+pub trait TaskGenerator {
+    /// This method returns the service time for the next request.
+    ///
+    /// # Return
+    /// The service time in seconds.
+    fn next_service_time(&mut self) -> f64;
+}
+
+/// Exponentially distributed service times; with the Poisson arrivals this is the M/M/1 model.
+pub struct ExponentialTaskGen {
+    // The mean service time in seconds.
+    mean_s: f64,
+
+    // Random number generator for the service times.
+    rng: StdRng,
+}
+
+impl ExponentialTaskGen {
+    pub fn new(mean_s: f64) -> ExponentialTaskGen {
+        ExponentialTaskGen {
+            mean_s: mean_s,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Same as `new()`, but the service times are reproducible across runs.
+    pub fn with_seed(mean_s: f64, seed: u64) -> ExponentialTaskGen {
+        ExponentialTaskGen {
+            mean_s: mean_s,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl TaskGenerator for ExponentialTaskGen {
+    // Lookup the `TaskGenerator` trait for documentation on this method.
+    fn next_service_time(&mut self) -> f64 {
+        // Inverse transform sampling; 1 - u is in (0, 1], so the log is finite.
+        -self.mean_s * (1.0 - self.rng.gen::<f64>()).ln()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exponential_mean() {
+        let mean_s = 5e-6;
+        let mut gen = ExponentialTaskGen::with_seed(mean_s, 42);
+        let samples = 100000;
+        let sum: f64 = (0..samples).map(|_| gen.next_service_time()).sum();
+        let mean = sum / samples as f64;
+        assert!((mean - mean_s).abs() < 0.01 * mean_s, "mean {}", mean);
+    }
+}
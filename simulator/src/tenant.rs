@@ -17,12 +17,72 @@ use super::cores::CoreType;
 use super::request::Request;
 use super::sched::Scheduler;
 
+use std::time::{Duration, Instant};
+
+/// The wall-clock time spent in one kind of scheduler call.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpCost {
+    // The number of calls measured.
+    pub calls: u64,
+
+    // The total time spent in the calls in nano-seconds.
+    pub nanos: u64,
+}
+
+impl OpCost {
+    /// Count a call which took `elapsed`.
+    pub fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.nanos += elapsed.as_nanos() as u64;
+    }
+
+    /// Add the calls measured for another tenant.
+    pub fn merge(&mut self, other: &OpCost) {
+        self.calls += other.calls;
+        self.nanos += other.nanos;
+    }
+
+    /// Return the mean time per call in nano-seconds, or 0 if no call was measured.
+    pub fn mean_ns(&self) -> f64 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        self.nanos as f64 / self.calls as f64
+    }
+}
+
+/// The wall-clock time spent in the scheduler, by the kind of call; used to compare the overhead
+/// of the policies.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SchedOverhead {
+    // The calls creating a task for a new request.
+    pub create: OpCost,
+
+    // The calls to pick_next_task().
+    pub pick: OpCost,
+
+    // The calls to enqueue_task(), for the preempted tasks.
+    pub enqueue: OpCost,
+}
+
+impl SchedOverhead {
+    /// Add the calls measured for another tenant.
+    pub fn merge(&mut self, other: &SchedOverhead) {
+        self.create.merge(&other.create);
+        self.pick.merge(&other.pick);
+        self.enqueue.merge(&other.enqueue);
+    }
+}
+
 pub struct Tenant {
     /// The scheduler is used to determine the schedule for the tasks for current tenant.
     pub sched: Box<dyn Scheduler>,
 
     // The ID of the current tenant.
     pub tenant_id: u16,
+
+    // If set, the time spent in the scheduler calls is measured.
+    pub overhead: Option<SchedOverhead>,
 }
 
 impl Tenant {
@@ -30,18 +90,102 @@ impl Tenant {
         Tenant {
             sched: policy,
             tenant_id: tenant,
+            overhead: None,
         }
     }
 
-    pub fn add_request(&mut self, rdtsc: u64, task_time: f64) {
-        self.sched.create_task(rdtsc, task_time, self.tenant_id);
+    /// Start measuring the wall-clock time spent in the scheduler calls of this tenant.
+    pub fn measure_overhead(&mut self) {
+        self.overhead = Some(SchedOverhead::default());
+    }
+
+    pub fn add_request(&mut self, rdtsc: u64, task_time: f64, key_id: u64, size_bytes: usize) {
+        let start = self.overhead.map(|_| Instant::now());
+        self.sched
+            .create_sized_task(rdtsc, task_time, self.tenant_id, key_id, size_bytes);
+        if let (Some(start), Some(ref mut overhead)) = (start, &mut self.overhead) {
+            overhead.create.record(start.elapsed());
+        }
     }
 
     pub fn get_request(&mut self, coretype: CoreType) -> Option<Box<Request>> {
-        self.sched.pick_next_task(coretype)
+        let start = self.overhead.map(|_| Instant::now());
+        let req = self.sched.pick_next_task(coretype);
+        if let (Some(start), Some(ref mut overhead)) = (start, &mut self.overhead) {
+            overhead.pick.record(start.elapsed());
+        }
+        req
+    }
+
+    /// Return the remaining time of the next task for this tenant, if any task is waiting.
+    pub fn peek_task_time(&self, coretype: CoreType) -> Option<f64> {
+        self.sched.peek_task_time(coretype)
+    }
+
+    /// Return the time slice in CPU cycles after which a task of this tenant is preempted.
+    pub fn quantum(&self, coretype: CoreType) -> u64 {
+        self.sched.quantum(coretype)
     }
 
     pub fn enqueue_task(&mut self, req: Box<Request>) {
+        let start = self.overhead.map(|_| Instant::now());
         self.sched.enqueue_task(req);
+        if let (Some(start), Some(ref mut overhead)) = (start, &mut self.overhead) {
+            overhead.enqueue.record(start.elapsed());
+        }
+    }
+
+    /// Return the internal state of the scheduler for this tenant; see `Scheduler::debug_state`.
+    pub fn debug_state(&self) -> String {
+        self.sched.debug_state()
+    }
+
+    /// Return the next `n` tasks of this tenant in the order they will run, as the sequence
+    /// numbers and the remaining times; see `Scheduler::top_n_tasks`.
+    pub fn describe_next_tasks(&self, coretype: CoreType, n: usize) -> String {
+        let tasks: Vec<String> = self
+            .sched
+            .top_n_tasks(coretype, n)
+            .iter()
+            .map(|req| format!("{} ({:.2}us)", req.seq(), req.remaining_time()))
+            .collect();
+        tasks.join(", ")
+    }
+
+    pub fn check_ordering(&self, req: &Request, coretype: CoreType) -> Result<(), String> {
+        self.sched.check_ordering(req, coretype)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::consts;
+    use super::super::rr_sched::RoundRobin;
+    use super::*;
+
+    #[test]
+    fn test_sched_overhead() {
+        let mut tenant = Tenant::new(1, Box::new(RoundRobin::new(consts::QUANTA_TIME)));
+        tenant.add_request(0, 1.0, 0, 0);
+        assert!(tenant.overhead.is_none());
+
+        // Each call is counted once measuring starts.
+        tenant.measure_overhead();
+        for i in 0..10 {
+            tenant.add_request(i, 1.0, 0, 0);
+        }
+        let req = tenant.get_request(CoreType::Small).unwrap();
+        tenant.enqueue_task(req);
+        let overhead = tenant.overhead.unwrap();
+        assert_eq!(overhead.create.calls, 10);
+        assert_eq!(overhead.pick.calls, 1);
+        assert_eq!(overhead.enqueue.calls, 1);
+
+        let mut total = SchedOverhead::default();
+        total.merge(&overhead);
+        total.merge(&overhead);
+        assert_eq!(total.create.calls, 20);
+        assert_eq!(total.create.nanos, 2 * overhead.create.nanos);
+        assert_eq!(OpCost::default().mean_ns(), 0.0);
     }
 }
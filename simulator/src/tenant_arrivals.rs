@@ -0,0 +1,227 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Correlated Poisson arrivals for several tenants, e.g. the flash crowds which hit many tenants
+/// at the same time. The number of arrivals of each tenant in an interval is Poisson, and the
+/// tenants are tied together by a Gaussian copula; correlated normals from the Cholesky factor of
+/// `rho` are mapped to uniforms by the normal CDF, and then to counts by the Poisson inverse CDF.
+pub struct TenantArrivalModel {
+    // The mean number of arrivals in an interval for each tenant.
+    rates: Vec<f64>,
+
+    // The lower triangular Cholesky factor of the correlation matrix between the tenants.
+    cholesky: Vec<Vec<f64>>,
+
+    // Random number generator for the normals.
+    rng: StdRng,
+}
+
+impl TenantArrivalModel {
+    /// Create the model for the given rates and correlations.
+    ///
+    /// # Arguments
+    /// `rates`: The mean number of arrivals in an interval for each tenant.
+    /// `rho`: The correlation matrix between the tenants; symmetric and positive definite, with
+    ///        ones on the diagonal.
+    ///
+    /// # Return
+    /// The model, or an error if `rho` isn't a valid correlation matrix for the tenants.
+    pub fn new(rates: Vec<f64>, rho: &[Vec<f64>]) -> Result<TenantArrivalModel, String> {
+        TenantArrivalModel::with_rng(rates, rho, StdRng::from_entropy())
+    }
+
+    /// Same as `new()`, but the arrivals are reproducible across runs.
+    pub fn with_seed(
+        rates: Vec<f64>,
+        rho: &[Vec<f64>],
+        seed: u64,
+    ) -> Result<TenantArrivalModel, String> {
+        TenantArrivalModel::with_rng(rates, rho, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(
+        rates: Vec<f64>,
+        rho: &[Vec<f64>],
+        rng: StdRng,
+    ) -> Result<TenantArrivalModel, String> {
+        let n = rates.len();
+        if rho.len() != n || rho.iter().any(|row| row.len() != n) {
+            return Err(format!("rho must be a {}x{} matrix", n, n));
+        }
+        if rates.iter().any(|rate| !(*rate >= 0.0)) {
+            return Err("the arrival rates can't be negative".to_string());
+        }
+        for i in 0..n {
+            if rho[i][i] != 1.0 || (0..n).any(|j| rho[i][j] != rho[j][i]) {
+                return Err("rho must be symmetric with ones on the diagonal".to_string());
+            }
+        }
+
+        Ok(TenantArrivalModel {
+            rates: rates,
+            cholesky: cholesky(rho).ok_or_else(|| "rho must be positive definite".to_string())?,
+            rng: rng,
+        })
+    }
+
+    /// Return a standard normal sample; Box-Muller.
+    fn normal(&mut self) -> f64 {
+        // 1 - u is in (0, 1], so the log is finite.
+        let u1 = 1.0 - self.rng.gen::<f64>();
+        let u2 = self.rng.gen::<f64>();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    /// This method generates the number of arrivals of each tenant in the next interval.
+    ///
+    /// # Return
+    /// The number of arrivals for each tenant, in the order of `rates`.
+    pub fn next_counts(&mut self) -> Vec<u64> {
+        let n = self.rates.len();
+        let independent: Vec<f64> = (0..n).map(|_| self.normal()).collect();
+        (0..n)
+            .map(|i| {
+                let z: f64 = (0..=i).map(|j| self.cholesky[i][j] * independent[j]).sum();
+                poisson_inverse_cdf(self.rates[i], normal_cdf(z))
+            })
+            .collect()
+    }
+}
+
+/// Return the lower triangular Cholesky factor of a symmetric matrix, or None if the matrix isn't
+/// positive definite.
+fn cholesky(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut lower = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| lower[i][k] * lower[j][k]).sum();
+            if i == j {
+                let diagonal = matrix[i][i] - sum;
+                if !(diagonal > 0.0) {
+                    return None;
+                }
+                lower[i][j] = diagonal.sqrt();
+            } else {
+                lower[i][j] = (matrix[i][j] - sum) / lower[j][j];
+            }
+        }
+    }
+    Some(lower)
+}
+
+/// Return the standard normal CDF at `z`; the erf approximation 7.1.26 from Abramowitz and
+/// Stegun, with an absolute error below 1.5e-7.
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if z >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// Return the smallest count whose Poisson CDF is at least `u`. The probabilities are computed in
+/// the log space, so that the large means don't underflow.
+///
+/// # Arguments
+/// `mean`: The mean of the Poisson distribution.
+/// `u`: The quantile, in [0, 1].
+fn poisson_inverse_cdf(mean: f64, u: f64) -> u64 {
+    if mean == 0.0 {
+        return 0;
+    }
+    let mut k = 0;
+    let mut log_pmf = -mean;
+    let mut cdf = log_pmf.exp();
+    while cdf < u {
+        k += 1;
+        log_pmf += mean.ln() - (k as f64).ln();
+        let pmf = log_pmf.exp();
+
+        // The CDF can round off just below a quantile close to 1.
+        if pmf == 0.0 && k as f64 > mean {
+            break;
+        }
+        cdf += pmf;
+    }
+    k
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_correlated_arrivals() {
+        let rho = vec![
+            vec![1.0, 0.6, 0.3],
+            vec![0.6, 1.0, 0.0],
+            vec![0.3, 0.0, 1.0],
+        ];
+        let rates = vec![200.0, 100.0, 400.0];
+        let mut model = TenantArrivalModel::with_seed(rates.clone(), &rho, 42).unwrap();
+        let samples: Vec<Vec<f64>> = (0..50000)
+            .map(|_| model.next_counts().iter().map(|c| *c as f64).collect())
+            .collect();
+
+        let mean = |i: usize| samples.iter().map(|s| s[i]).sum::<f64>() / samples.len() as f64;
+        let cov = |i: usize, j: usize| {
+            let (mi, mj) = (mean(i), mean(j));
+            samples
+                .iter()
+                .map(|s| (s[i] - mi) * (s[j] - mj))
+                .sum::<f64>()
+                / samples.len() as f64
+        };
+        for i in 0..3 {
+            assert!(
+                (mean(i) - rates[i]).abs() < 0.01 * rates[i],
+                "mean {}",
+                mean(i)
+            );
+            for j in 0..i {
+                let corr = cov(i, j) / (cov(i, i) * cov(j, j)).sqrt();
+                // The uncorrelated tenants are only checked against the sampling noise.
+                assert!(
+                    (corr - rho[i][j]).abs() <= (0.05 * rho[i][j]).max(0.02),
+                    "corr({}, {}) {} expected {}",
+                    i,
+                    j,
+                    corr,
+                    rho[i][j]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_rho() {
+        let rates = vec![1.0, 1.0];
+        assert!(TenantArrivalModel::new(rates.clone(), &[vec![1.0]]).is_err());
+        assert!(TenantArrivalModel::new(rates.clone(), &[vec![1.0, 0.5], vec![0.4, 1.0]]).is_err());
+
+        // Perfectly correlated tenants aren't positive definite.
+        assert!(TenantArrivalModel::new(rates, &[vec![1.0, 1.0], vec![1.0, 1.0]]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cycles;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The timeline of a completed request, for the trace log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceRecord {
+    // The random id of the request.
+    pub trace_id: u128,
+
+    // The simulated time at which the request arrived.
+    pub arrival_ns: u64,
+
+    // The simulated time at which a core picked the request for the first time.
+    pub dispatch_ns: u64,
+
+    // The simulated time at which the request completed.
+    pub completion_ns: u64,
+
+    // The tenant of the request.
+    pub tenant_id: u16,
+
+    // The time the request needed on a core, without the queueing and the preemptions.
+    pub service_time_ns: u64,
+}
+
+impl TraceRecord {
+    /// This method creates the record from the time stamps of a request.
+    ///
+    /// # Arguments
+    /// `trace_id`: The random id of the request.
+    /// `tenant_id`: The tenant of the request.
+    /// `arrival`: The arrival time stamp in cycles.
+    /// `dispatch`: The first dispatch time stamp in cycles.
+    /// `completion`: The completion time stamp in cycles.
+    /// `task_time`: The task time of the request in micro-seconds.
+    pub fn new(
+        trace_id: u128,
+        tenant_id: u16,
+        arrival: u64,
+        dispatch: u64,
+        completion: u64,
+        task_time: f64,
+    ) -> TraceRecord {
+        let to_ns = |cycles: u64| (cycles::to_seconds(cycles) * 1e9) as u64;
+        TraceRecord {
+            trace_id: trace_id,
+            arrival_ns: to_ns(arrival),
+            dispatch_ns: to_ns(dispatch),
+            completion_ns: to_ns(completion),
+            tenant_id: tenant_id,
+            service_time_ns: (task_time * 1e3) as u64,
+        }
+    }
+
+    /// Return the record as a line of JSON; the trace id is a 32 digit hex string.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"trace_id\":\"{:032x}\",\"arrival_ns\":{},\"dispatch_ns\":{},\"completion_ns\":{},\"tenant_id\":{},\"service_time_ns\":{}}}",
+            self.trace_id,
+            self.arrival_ns,
+            self.dispatch_ns,
+            self.completion_ns,
+            self.tenant_id,
+            self.service_time_ns
+        )
+    }
+}
+
+/// Writes one line of JSON for each completed request.
+pub struct TraceLog {
+    writer: BufWriter<File>,
+}
+
+impl TraceLog {
+    pub fn create(path: &Path) -> io::Result<TraceLog> {
+        Ok(TraceLog {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Append a record to the log.
+    pub fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        writeln!(self.writer, "{}", record.to_json())
+    }
+
+    /// Flush the buffered records to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::test_config;
+    use super::super::cores::Simulator;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    // Parse a line of the log into the fields; the values are all numbers but the trace id.
+    fn parse(line: &str) -> HashMap<String, String> {
+        let line = line.trim_start_matches('{').trim_end_matches('}');
+        line.split(',')
+            .map(|field| {
+                let mut pair = field.splitn(2, ':');
+                let key = pair.next().unwrap().trim_matches('"').to_string();
+                let value = pair.next().unwrap().trim_matches('"').to_string();
+                (key, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trace_log_causality() {
+        let mut config = test_config();
+        config.num_requests = 1000;
+        let path = env::temp_dir().join("simulator_test_trace_log.json");
+
+        let mut simulator = Simulator::with_config(config);
+        simulator.set_trace_log(&path).unwrap();
+        let result = simulator.start();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count() as u64, result.requests_processed);
+        for line in contents.lines() {
+            let record = parse(line);
+            let field = |key: &str| record[key].parse::<u64>().unwrap();
+            assert_eq!(record["trace_id"].len(), 32);
+            assert!(field("arrival_ns") <= field("dispatch_ns"));
+            assert!(field("dispatch_ns") <= field("completion_ns"));
+            assert!(field("service_time_ns") > 0);
+            assert!(field("tenant_id") >= 1);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
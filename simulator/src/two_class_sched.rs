@@ -0,0 +1,259 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::collections::{HashMap, VecDeque};
+
+/// The class of a tenant for the `TwoClassScheduler`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Criticality {
+    // The tasks of these tenants always run ahead of the best-effort tasks.
+    LatencyCritical,
+
+    // The tasks of these tenants only run when no latency-critical task is waiting.
+    BestEffort,
+}
+
+/// Strict priority between two tenant classes, with first-come first-served within each class.
+/// The best-effort run-queue is only served when the latency-critical run-queue is empty, and a
+/// running best-effort task must be preempted as soon as a latency-critical task arrives; see
+/// `should_preempt()`.
+#[derive(Serialize, Deserialize)]
+pub struct TwoClassScheduler {
+    // The class of each tenant; the tenants without an entry are best-effort.
+    classes: HashMap<u16, Criticality>,
+
+    // Task runqueue for the latency-critical tenants.
+    critical_rq: VecDeque<Box<Request>>,
+
+    // Task runqueue for the best-effort tenants.
+    best_effort_rq: VecDeque<Box<Request>>,
+
+    // The sequence number for the next task added to a runqueue.
+    next_seq: u64,
+}
+
+impl TwoClassScheduler {
+    pub fn new(classes: HashMap<u16, Criticality>) -> TwoClassScheduler {
+        TwoClassScheduler {
+            classes: classes,
+            critical_rq: VecDeque::new(),
+            best_effort_rq: VecDeque::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Return the class of the tenant; best-effort if it isn't in the map.
+    pub fn class(&self, tenant: u16) -> Criticality {
+        *self
+            .classes
+            .get(&tenant)
+            .unwrap_or(&Criticality::BestEffort)
+    }
+
+    /// This method decides if the running task must be preempted before its quantum expires. It
+    /// is meant to be called on each arrival while a task runs.
+    ///
+    /// # Arguments
+    /// `running`: The task running on the core.
+    ///
+    /// # Return
+    /// True if the running task is best-effort and a latency-critical task is waiting.
+    pub fn should_preempt(&self, running: &Request) -> bool {
+        self.class(running.get_tenant()) == Criticality::BestEffort && !self.critical_rq.is_empty()
+    }
+
+    /// Return the runqueue for the tasks of the class.
+    fn rq(&self, class: Criticality) -> &VecDeque<Box<Request>> {
+        match class {
+            Criticality::LatencyCritical => &self.critical_rq,
+            Criticality::BestEffort => &self.best_effort_rq,
+        }
+    }
+}
+
+impl Scheduler for TwoClassScheduler {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        self.critical_rq
+            .pop_front()
+            .or_else(|| self.best_effort_rq.pop_front())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        self.critical_rq
+            .front()
+            .or_else(|| self.best_effort_rq.front())
+            .map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        self.critical_rq
+            .iter()
+            .chain(self.best_effort_rq.iter())
+            .take(n)
+            .map(|req| req.as_ref())
+            .collect()
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        let class = self.class(req.get_tenant());
+        let rq = match class {
+            Criticality::LatencyCritical => &mut self.critical_rq,
+            Criticality::BestEffort => &mut self.best_effort_rq,
+        };
+
+        // A preempted task arrived before all the waiting tasks of its class; it goes back to
+        // the head of the runqueue, and keeps its place in the arrival order.
+        if req.first_dispatch().is_some() {
+            rq.push_front(req);
+        } else {
+            req.set_seq(self.next_seq);
+            self.next_seq += 1;
+            rq.push_back(req);
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        let class = self.class(req.get_tenant());
+        if class == Criticality::BestEffort {
+            if let Some(waiting) = self.critical_rq.front() {
+                return Err(format!(
+                    "TwoClassScheduler picked best-effort task {} while latency-critical task {} was waiting",
+                    req.seq(),
+                    waiting.seq()
+                ));
+            }
+        }
+        match self
+            .rq(class)
+            .iter()
+            .find(|waiting| waiting.seq() < req.seq())
+        {
+            Some(waiting) => Err(format!(
+                "TwoClassScheduler picked task {} before the earlier task {} of its class",
+                req.seq(),
+                waiting.seq()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn debug_state(&self) -> String {
+        format!(
+            "critical_rq {} best_effort_rq {}",
+            self.critical_rq.len(),
+            self.best_effort_rq.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_best_effort_suspended() {
+        let mut classes = HashMap::new();
+        classes.insert(1, Criticality::LatencyCritical);
+        classes.insert(2, Criticality::BestEffort);
+        let mut sched = TwoClassScheduler::new(classes);
+
+        // A best-effort task starts running on an idle core.
+        sched.create_task(0, 10.0, 2, 0);
+        sched.create_task(0, 10.0, 2, 1);
+        let mut running = sched.pick_next_task(CoreType::Small).unwrap();
+        running.dispatch(0);
+        assert!(!sched.should_preempt(&running));
+
+        // A latency-critical arrival preempts it, and runs first along with the next one.
+        sched.create_task(1, 1.0, 1, 2);
+        assert!(sched.should_preempt(&running));
+        sched.enqueue_task(running);
+        sched.create_task(2, 1.0, 1, 3);
+        assert_eq!(sched.debug_state(), "critical_rq 2 best_effort_rq 2");
+
+        // The best-effort tasks resume in their arrival order once the latency-critical ones
+        // are done.
+        let top: Vec<u64> = sched
+            .top_n_tasks(CoreType::Small, 4)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        let mut keys = Vec::new();
+        while let Some(req) = sched.pick_next_task(CoreType::Small) {
+            assert!(sched.check_ordering(&req, CoreType::Small).is_ok());
+            if sched.class(req.get_tenant()) == Criticality::BestEffort {
+                assert!(sched.top_task(CoreType::Small).map_or(true, |next| {
+                    sched.class(next.get_tenant()) == Criticality::BestEffort
+                }));
+            }
+            keys.push(req.key_id());
+        }
+        assert_eq!(keys, vec![2, 3, 0, 1]);
+        assert_eq!(top, keys);
+    }
+
+    #[test]
+    fn test_check_ordering() {
+        let mut classes = HashMap::new();
+        classes.insert(1, Criticality::LatencyCritical);
+        let mut sched = TwoClassScheduler::new(classes);
+
+        // Tenant 2 isn't in the map, so it is best-effort.
+        sched.create_task(0, 1.0, 2, 0);
+        sched.create_task(0, 1.0, 1, 1);
+        let best_effort = sched.best_effort_rq.pop_front().unwrap();
+        assert!(sched.check_ordering(&best_effort, CoreType::Small).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use std::cmp::max;
+
+/// A finite pool of server worker threads shared by all the cores. A task executes only while it
+/// holds a worker; if all the workers are busy, the core waits for the first one to free up. This
+/// bounds the number of tasks executing at the same time, regardless of the number of cores.
+pub struct WorkerPool {
+    // The time stamp in cycles at which each worker becomes free.
+    free_at: Vec<u64>,
+
+    // The (start, end) time stamps of each task slice executed on a worker.
+    #[cfg(test)]
+    pub history: Vec<(u64, u64)>,
+}
+
+impl WorkerPool {
+    pub fn new(workers: u64) -> WorkerPool {
+        WorkerPool {
+            free_at: vec![0; workers as usize],
+            #[cfg(test)]
+            history: Vec::new(),
+        }
+    }
+
+    /// This method assigns the worker which frees up first to a task.
+    ///
+    /// # Arguments
+    /// `rdtsc`: The time stamp at which the core wants to execute the task.
+    ///
+    /// # Return
+    /// The worker assigned to the task, and the time stamp at which the task can start executing.
+    pub fn acquire(&mut self, rdtsc: u64) -> (usize, u64) {
+        let (worker, free_at) = self
+            .free_at
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, free_at)| *free_at)
+            .map(|(worker, free_at)| (worker, *free_at))
+            .unwrap();
+        (worker, max(rdtsc, free_at))
+    }
+
+    /// This method frees up a worker once the task stops executing.
+    ///
+    /// # Arguments
+    /// `worker`: The worker returned by `acquire()`.
+    /// `start`: The time stamp at which the task started executing.
+    /// `end`: The time stamp at which the task stopped executing.
+    pub fn release(&mut self, worker: usize, start: u64, end: u64) {
+        debug_assert!(start <= end);
+        self.free_at[worker] = end;
+        #[cfg(test)]
+        self.history.push((start, end));
+    }
+}
@@ -0,0 +1,245 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+use super::cores::CoreType;
+use super::request::Request;
+use super::sched::{decode_state, default_quantum, encode_state, Scheduler};
+
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Serialize, Deserialize)]
+pub struct WeightedRoundRobin {
+    // Task runqueue for each tenant.
+    rqs: HashMap<u16, VecDeque<Box<Request>>>,
+
+    // The number of consecutive slots each tenant gets in a round; tenants without an entry get
+    // a single slot.
+    weights: HashMap<u16, u32>,
+
+    // The slots left for each tenant in the current round.
+    current_weights: HashMap<u16, u32>,
+
+    // The sequence number for the next task added to a runqueue.
+    next_seq: u64,
+}
+
+impl WeightedRoundRobin {
+    pub fn new(weights: HashMap<u16, u32>) -> WeightedRoundRobin {
+        WeightedRoundRobin {
+            rqs: HashMap::new(),
+            current_weights: weights.clone(),
+            weights: weights,
+            next_seq: 0,
+        }
+    }
+
+    /// Return the tenant with waiting tasks and the highest weight left in this round. Ties go
+    /// to the lower tenant id so that the order is deterministic.
+    fn pick_tenant(&self) -> Option<u16> {
+        self.pick_tenant_from(&self.current_weights, &HashMap::new())
+    }
+
+    /// Same as `pick_tenant`, with the given weights left for each tenant, and the given number
+    /// of tasks already taken from the head of each runqueue.
+    fn pick_tenant_from(
+        &self,
+        weights: &HashMap<u16, u32>,
+        taken: &HashMap<u16, usize>,
+    ) -> Option<u16> {
+        let mut picked: Option<(u16, u32)> = None;
+        for (tenant, rq) in self.rqs.iter() {
+            let weight = *weights.get(tenant).unwrap_or(&0);
+            if rq.len() <= *taken.get(tenant).unwrap_or(&0) || weight == 0 {
+                continue;
+            }
+            picked = match picked {
+                Some((t, w)) if w > weight || (w == weight && t < *tenant) => Some((t, w)),
+                _ => Some((*tenant, weight)),
+            };
+        }
+        picked.map(|(tenant, _)| tenant)
+    }
+}
+
+impl Scheduler for WeightedRoundRobin {
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn create_sized_task(
+        &mut self,
+        rdtsc: u64,
+        task_time: f64,
+        tenant_id: u16,
+        key_id: u64,
+        size_bytes: usize,
+    ) {
+        let mut req = Box::new(Request::new(tenant_id, key_id, rdtsc, task_time));
+        req.set_size_bytes(size_bytes);
+        self.enqueue_task(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn pick_next_task(&mut self, _type: CoreType) -> Option<Box<Request>> {
+        // Start a new round once the tenants with waiting tasks have used up their slots.
+        let tenant = match self.pick_tenant() {
+            Some(tenant) => tenant,
+            None => {
+                self.current_weights = self.weights.clone();
+                self.pick_tenant()?
+            }
+        };
+
+        if let Some(weight) = self.current_weights.get_mut(&tenant) {
+            *weight -= 1;
+        }
+        self.rqs.get_mut(&tenant).and_then(|rq| rq.pop_front())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_task(&self, _type: CoreType) -> Option<&Request> {
+        // If the current round is over, the next round starts from the full weights.
+        let tenant = match self.pick_tenant() {
+            Some(tenant) => tenant,
+            None => self.pick_tenant_from(&self.weights, &HashMap::new())?,
+        };
+        self.rqs[&tenant].front().map(|req| req.as_ref())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn top_n_tasks(&self, _type: CoreType, n: usize) -> Vec<&Request> {
+        // Replay the picks on a copy of the weights left in this round.
+        let mut weights = self.current_weights.clone();
+        let mut taken: HashMap<u16, usize> = HashMap::new();
+        let mut tasks = Vec::with_capacity(n);
+        while tasks.len() < n {
+            let tenant = match self.pick_tenant_from(&weights, &taken) {
+                Some(tenant) => tenant,
+                None => {
+                    weights = self.weights.clone();
+                    match self.pick_tenant_from(&weights, &taken) {
+                        Some(tenant) => tenant,
+                        None => break,
+                    }
+                }
+            };
+
+            if let Some(weight) = weights.get_mut(&tenant) {
+                *weight -= 1;
+            }
+            let index = taken.entry(tenant).or_insert(0);
+            tasks.push(self.rqs[&tenant][*index].as_ref());
+            *index += 1;
+        }
+        tasks
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn quantum(&self, coretype: CoreType) -> u64 {
+        default_quantum(coretype)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn enqueue_task(&mut self, mut req: Box<Request>) {
+        let tenant = req.get_tenant();
+        if !self.weights.contains_key(&tenant) {
+            self.weights.insert(tenant, 1);
+            self.current_weights.insert(tenant, 1);
+        }
+
+        req.set_seq(self.next_seq);
+        self.next_seq += 1;
+        self.rqs
+            .entry(tenant)
+            .or_insert_with(|| VecDeque::with_capacity(32))
+            .push_back(req);
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn serialize_state(&self) -> Vec<u8> {
+        encode_state(self)
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = decode_state(data)?;
+        Ok(())
+    }
+
+    // Lookup the `Scheduler` trait for documentation on this method.
+    fn check_ordering(&self, req: &Request, _type: CoreType) -> Result<(), String> {
+        // Tasks of a tenant must be picked in the order they were added to its runqueue.
+        if let Some(rq) = self.rqs.get(&req.get_tenant()) {
+            for waiting in rq.iter() {
+                if waiting.seq() < req.seq() {
+                    return Err(format!(
+                        "WeightedRoundRobin picked task {} while task {} was waiting",
+                        req.seq(),
+                        waiting.seq()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_weighted_dispatch() {
+        let mut weights = HashMap::new();
+        weights.insert(1, 3);
+        weights.insert(2, 1);
+        let mut wrr = WeightedRoundRobin::new(weights);
+        for i in 0..100 {
+            wrr.create_task(i, 1.0, 1, 0);
+            wrr.create_task(i, 1.0, 2, 0);
+        }
+
+        let mut dispatched = HashMap::new();
+        for _ in 0..100 {
+            let task = wrr.pick_next_task(CoreType::Small).unwrap();
+            assert!(wrr.check_ordering(&task, CoreType::Small).is_ok());
+            *dispatched.entry(task.get_tenant()).or_insert(0) += 1;
+        }
+        assert_eq!(dispatched[&1], 75);
+        assert_eq!(dispatched[&2], 25);
+    }
+
+    #[test]
+    fn test_top_n_tasks() {
+        let mut weights = HashMap::new();
+        weights.insert(1, 2);
+        let mut wrr = WeightedRoundRobin::new(weights);
+        for i in 0..3 {
+            wrr.create_task(i, 1.0, 1, i);
+            wrr.create_task(i, 1.0, 2, 10 + i);
+        }
+        wrr.pick_next_task(CoreType::Small).unwrap();
+
+        // The order spans the end of the current round and the start of the next one, and the
+        // tenant 2 runs out of tasks.
+        let top: Vec<u64> = wrr
+            .top_n_tasks(CoreType::Small, 10)
+            .iter()
+            .map(|req| req.key_id())
+            .collect();
+        assert_eq!(top.len(), 5);
+        for key in top {
+            assert_eq!(wrr.pick_next_task(CoreType::Small).unwrap().key_id(), key);
+        }
+        assert!(wrr.pick_next_task(CoreType::Small).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+/* Copyright (c) 2019 University of Utah
+ *
+ * Permission to use, copy, modify, and distribute this software for any
+ * purpose with or without fee is hereby granted, provided that the above
+ * copyright notice and this permission notice appear in all copies.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR(S) DISCLAIM ALL WARRANTIES
+ * WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+ * MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL AUTHORS BE LIABLE FOR
+ * ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+ * WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+ * ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+ * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+ */
+
+extern crate simulator;
+
+use simulator::cycles;
+use simulator::replay::{PcapReplay, TaskTimeModel};
+
+// 100 UDP packets sent 10us apart, to the destination ports 1024 to 1027 in turn.
+const TRACE: &str = "tests/data/replay_100.pcap";
+
+#[test]
+fn test_replay_arrivals() {
+    let mut replay = PcapReplay::open(TRACE, 1.0, 4).unwrap();
+    assert_eq!(replay.len(), 100);
+
+    let gap = (10.0 * cycles::cycles_per_us()) as u64;
+    for i in 0..100 {
+        let arrival = replay.next_arrival(std::u64::MAX).unwrap();
+        assert_eq!(arrival.time, i * gap);
+        assert_eq!(arrival.tenant, (((1024 + i % 4) % 4) + 1) as u16);
+        assert_eq!(arrival.size, 8);
+    }
+    assert!(replay.is_empty());
+}
+
+#[test]
+fn test_replay_speedup() {
+    let mut replay = PcapReplay::open(TRACE, 2.0, 4).unwrap();
+    let gap = (5.0 * cycles::cycles_per_us()) as u64;
+
+    // Nothing arrives until the simulated time reaches the arrival time.
+    assert_eq!(replay.next_arrival(0).unwrap().time, 0);
+    assert_eq!(replay.next_arrival(gap - 1), None);
+    assert_eq!(replay.next_arrival(gap).unwrap().time, gap);
+    assert_eq!(replay.next_time(), Some(2 * gap));
+}
+
+#[test]
+fn test_replay_retain_tenants() {
+    let mut replay = PcapReplay::open(TRACE, 1.0, 4).unwrap();
+    replay.retain_tenants(1, 3);
+    assert_eq!(replay.len(), 50);
+    while let Some(arrival) = replay.next_arrival(std::u64::MAX) {
+        assert!(arrival.tenant == 1 || arrival.tenant == 2);
+    }
+}
+
+// 4 requests at 0, 10, 25.5 and 40us after the first one.
+const TEXT_TRACE: &str = "tests/data/replay_4.txt";
+
+#[test]
+fn test_replay_text_trace() {
+    let model = TaskTimeModel {
+        base_us: 1.0,
+        us_per_byte: 0.01,
+    };
+    let mut replay = PcapReplay::open_text(TEXT_TRACE, 1.0, 2, model).unwrap();
+    assert_eq!(replay.len(), 4);
+
+    let expected = [(0.0, 64), (10.0, 128), (25.5, 0), (40.0, 1024)];
+    for (i, (usecs, size)) in expected.iter().enumerate() {
+        let arrival = replay.next_arrival(std::u64::MAX).unwrap();
+        assert_eq!(arrival.time, (usecs * cycles::cycles_per_us()) as u64);
+        assert_eq!(arrival.tenant, (i % 2 + 1) as u16);
+        assert_eq!(arrival.size, *size);
+        let task_time = arrival.task_time.unwrap();
+        assert!((task_time - (1.0 + 0.01 * *size as f64)).abs() < 1e-9);
+    }
+    assert!(replay.is_empty());
+}
+
+// 3 requests at 0, 20 and 60us after the first one.
+const SHORT_TRACE: &str = "tests/data/replay_3.txt";
+
+#[test]
+fn test_replay_speed() {
+    let model = TaskTimeModel {
+        base_us: 1.0,
+        us_per_byte: 0.0,
+    };
+
+    // 2.0 halves the time between the arrivals, and 0.5 doubles it.
+    for (speed, expected) in [(2.0, [0.0, 10.0, 30.0]), (0.5, [0.0, 40.0, 120.0])].iter() {
+        let mut replay = PcapReplay::open_text(SHORT_TRACE, *speed, 1, model).unwrap();
+        assert_eq!(replay.len(), 3);
+        for usecs in expected.iter() {
+            let arrival = replay.next_arrival(std::u64::MAX).unwrap();
+            assert_eq!(arrival.time, (usecs * cycles::cycles_per_us()) as u64);
+        }
+        assert!(replay.is_empty());
+    }
+}